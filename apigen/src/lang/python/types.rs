@@ -44,6 +44,7 @@ fn python_type_from_simple(simple: &SimpleType) -> SynResult<TokenStream> {
 
     let mut the_type = match simple.name.as_str() {
         "Id" => quote! { usize },
+        "i32" => quote! { i32 },
 
         "Either" => match &simple.generic_types {
             Some(args) if args.len() == 2 => {
@@ -112,7 +113,7 @@ fn python_conversion_simple<T: ToTokens>(pat: T, simple: &SimpleType) -> SynResu
     }
 
     let mut conversion = match simple.name.as_str() {
-        "Id" => quote! { #pat },
+        "Id" | "i32" => quote! { #pat },
 
         "Either" => match &simple.generic_types {
             Some(arg_types) if arg_types.len() == 2 => {
@@ -116,6 +116,13 @@ impl Jobs {
         self.jobs.clear();
     }
 
+    /// Cancel the job with the given Id, if it's still running
+    pub fn cancel(&mut self, id: Id) {
+        if let Some(record) = self.jobs.remove(&id) {
+            record.handle.abort();
+        }
+    }
+
     /// Start a job, returning ownership of its JobRecord so you can
     /// interact with it directly.
     pub fn start<T, F>(&mut self, task: T) -> JobRecord
@@ -157,7 +164,6 @@ impl Jobs {
     }
 
     /// Start a job in the background, returning its Id
-    #[allow(unused)]
     pub fn spawn<T, F>(&mut self, task: T) -> Id
     where
         T: Send + 'static + FnOnce(JobContext) -> F,
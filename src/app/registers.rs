@@ -14,6 +14,7 @@ use self::clipboard::ClipboardRegister;
 const UNNAMED_REGISTER: char = '"';
 const YANK_REGISTER: char = '0';
 const SMALL_DELETE_REGISTER: char = '-';
+pub const BLACKHOLE_REGISTER: char = '_';
 
 const NUMBERED_REGISTERS: [char; 9] = ['1', '2', '3', '4', '5', '6', '7', '8', '9'];
 
@@ -32,7 +33,7 @@ impl RegisterManager {
 
         // Some registers are special:
         registers.insert('*', ClipboardRegister::new());
-        registers.insert('_', BlackholeRegister::new());
+        registers.insert(BLACKHOLE_REGISTER, BlackholeRegister::new());
 
         Self { registers }
     }
@@ -39,6 +39,10 @@ pub struct AppState {
     pub showing_splash: bool,
     pub requested_redraw: bool,
 
+    /// Set when a connection rings the bell; the UI should act on
+    /// (and reset) this when it next renders
+    pub requested_bell: bool,
+
     pub buffers: Buffers,
     pub tabpages: Tabpages,
     pub echo_buffer: Box<dyn Buffer>,
@@ -185,6 +189,10 @@ impl AppState {
         self.requested_redraw = true;
     }
 
+    pub fn ring_bell(&mut self) {
+        self.requested_bell = true;
+    }
+
     pub fn on_pre_draw(&mut self) {
         if self.showing_splash
             && (self.buffers.most_recent_id().unwrap_or(0) > FIRST_USER_BUFFER_ID
@@ -311,6 +319,7 @@ impl Default for AppState {
             running: true,
             showing_splash: true,
             requested_redraw: false,
+            requested_bell: false,
             buffers,
             tabpages,
             registers: RegisterManager::new(),
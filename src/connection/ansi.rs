@@ -6,9 +6,21 @@ use tui::{
 };
 use vte::{ParamsIter, Parser, Perform};
 
-use crate::editing::text::TextLine;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use super::ReadValue;
+use crate::editing::text::{EditableLine, TextLine};
+
+use super::{
+    mxp::{self, MxpMode, MxpParser, MxpTag, MxpToken},
+    LinkTarget, ReadValue,
+};
+
+const TAB_WIDTH: usize = 8;
+
+/// A single (display) cell of a line being edited, along with its style.
+/// A wide character is followed by an empty continuation cell, and any
+/// zero-width characters are kept with the cell before them.
+type Cell = (String, Style);
 
 pub struct AnsiPipeline {
     parser: Parser,
@@ -32,6 +44,27 @@ impl AnsiPipeline {
     pub fn next(&mut self) -> Option<ReadValue> {
         return self.performer.next();
     }
+
+    /// Mark any pending partial line as a complete prompt (IE: in response
+    /// to IAC GA or IAC EOR). Once this has been called, partial lines are
+    /// held until either a newline or the next prompt marker arrives.
+    pub fn end_prompt(&mut self) {
+        self.performer.end_prompt();
+    }
+
+    /// Hold partial lines until we know whether they're a prompt; this is
+    /// used when the server has explicitly agreed to mark prompts with EOR
+    pub fn expect_prompts(&mut self) {
+        self.performer.expects_prompts = true;
+    }
+
+    /// Begin parsing MXP tags in output; this is used once the server
+    /// has agreed to enable MXP
+    pub fn enable_mxp(&mut self) {
+        if self.performer.mxp.is_none() {
+            self.performer.mxp = Some(MxpParser::default());
+        }
+    }
 }
 
 struct SpanBuilder {
@@ -64,12 +97,129 @@ impl SpanBuilder {
             None
         }
     }
+
+    pub fn width(&self) -> usize {
+        self.content
+            .as_ref()
+            .map(|content| content.width())
+            .unwrap_or(0)
+    }
+}
+
+fn line_to_cells(line: &TextLine) -> Vec<Cell> {
+    let mut cells = vec![];
+    for span in &line.0 {
+        for ch in span.content.chars() {
+            let end = cells.len();
+            put_cell(&mut cells, end, ch, span.style);
+        }
+    }
+    cells
+}
+
+fn cells_to_line(cells: Vec<Cell>) -> TextLine {
+    let mut spans: Vec<Span<'static>> = vec![];
+    let mut content = String::new();
+    let mut style = None;
+    for (cell, cell_style) in cells {
+        match style {
+            Some(style) if style != cell_style => {
+                spans.push(Span::styled(content.split_off(0), style));
+            }
+            _ => {}
+        }
+        style = Some(cell_style);
+        content.push_str(&cell);
+    }
+    if let Some(style) = style {
+        spans.push(Span::styled(content, style));
+    }
+    TextLine::from(spans)
+}
+
+/// Blank out the cell at `col`, along with the other half of any wide
+/// character it belongs to
+fn clear_cell(cells: &mut [Cell], col: usize) {
+    if col >= cells.len() {
+        return;
+    }
+    if cells[col].0.is_empty() && col > 0 {
+        cells[col - 1].0 = " ".to_string();
+    } else if matches!(cells.get(col + 1), Some((next, _)) if next.is_empty()) {
+        cells[col + 1].0 = " ".to_string();
+    }
+    cells[col].0 = " ".to_string();
+}
+
+/// Write `ch` into `cells` at `col`, returning the column after it
+fn put_cell(cells: &mut Vec<Cell>, col: usize, ch: char, style: Style) -> usize {
+    let width = ch.width().unwrap_or(0);
+    if width == 0 {
+        if let Some((cell, _)) = col.checked_sub(1).and_then(|prev| cells.get_mut(prev)) {
+            cell.push(ch);
+            return col;
+        }
+    }
+
+    if cells.len() < col {
+        cells.resize(col, (" ".to_string(), style));
+    }
+    for offset in 0..width.max(1) {
+        let col = col + offset;
+        clear_cell(cells, col);
+        let cell = if offset == 0 {
+            ch.to_string()
+        } else {
+            String::new()
+        };
+        if col < cells.len() {
+            cells[col] = (cell, style);
+        } else {
+            cells.push((cell, style));
+        }
+    }
+    col + width.max(1)
+}
+
+/// A link whose text is still being received
+enum OpenLink {
+    /// An MXP `<send>` tag, whose command may depend on the link text
+    Send(MxpTag),
+
+    /// An OSC 8 hyperlink, along with the style to restore when it's closed
+    Url(String, Style),
 }
 
 struct AnsiPerformer {
     buffer: VecDeque<ReadValue>,
     builder: SpanBuilder,
     current_line: Option<TextLine>,
+    expects_prompts: bool,
+
+    mxp: Option<MxpParser>,
+
+    /// Open MXP tags, along with the style to restore when each is closed
+    mxp_styles: Vec<(String, Style)>,
+
+    /// The currently-open `<send>` tag or OSC 8 hyperlink, if any
+    link: Option<OpenLink>,
+
+    /// The part of the current line that has already been emitted, in
+    /// case the server moves the cursor back over it
+    emitted: TextLine,
+
+    /// The column at which the next character will be written, if the
+    /// cursor has been moved back from the end of the current line.
+    /// Like a terminal, columns here count display cells, not chars
+    cursor: Option<usize>,
+
+    /// The whole current line, once the cursor has been used to edit it;
+    /// kept until the line is next emitted so each edit stays cheap
+    cells: Option<Vec<Cell>>,
+
+    /// If true, the current line was edited after some of it was emitted,
+    /// so it must be emitted again in full as a ReadValue::ReplaceLine
+    rewrite: bool,
 }
 
 impl AnsiPerformer {
@@ -78,14 +228,38 @@ impl AnsiPerformer {
             buffer: VecDeque::new(),
             builder: SpanBuilder::new(),
             current_line: None,
+            expects_prompts: false,
+            mxp: None,
+            mxp_styles: Vec::new(),
+            link: None,
+            emitted: TextLine::default(),
+            cursor: None,
+            cells: None,
+            rewrite: false,
         }
     }
 
     fn next(&mut self) -> Option<ReadValue> {
-        self.line_to_buffer();
+        if !self.expects_prompts {
+            self.line_to_buffer();
+        }
         self.buffer.pop_front()
     }
 
+    fn end_prompt(&mut self) {
+        self.expects_prompts = true;
+        self.cells_to_line();
+        self.builder_to_line();
+        let prompt = self.current_line.take().unwrap_or_default();
+
+        // A GA/EOR right after a complete line has no prompt text; sending it
+        // would just clear the previous prompt
+        if prompt.width() > 0 {
+            self.buffer.push_back(ReadValue::Prompt(prompt));
+        }
+        self.reset_line();
+    }
+
     fn builder_to_line(&mut self) {
         if let Some(span) = self.builder.take() {
             if let Some(ref mut line) = self.current_line {
@@ -96,27 +270,278 @@ impl AnsiPerformer {
         }
     }
 
+    fn cells_to_line(&mut self) {
+        if let Some(cells) = self.cells.take() {
+            if !cells.is_empty() || self.rewrite {
+                self.current_line = Some(cells_to_line(cells));
+            }
+        }
+    }
+
     fn line_to_buffer(&mut self) {
+        self.cells_to_line();
         self.builder_to_line();
-        if let Some(line) = self.current_line.take() {
+        if self.rewrite {
+            self.rewrite = false;
+            let line = self.current_line.take().unwrap_or_default();
+            self.emitted = line.clone();
+            self.buffer.push_back(ReadValue::ReplaceLine(line));
+        } else if let Some(line) = self.current_line.take() {
+            self.emitted.0.extend(line.0.iter().cloned());
             self.buffer.push_back(ReadValue::Text(line));
         }
     }
+
+    fn newline(&mut self) {
+        if let Some(MxpToken::Text(text)) = self.mxp.as_mut().and_then(|mxp| mxp.on_newline()) {
+            self.write_text(&text);
+        }
+        self.end_link();
+        self.line_to_buffer();
+        self.buffer.push_back(ReadValue::Newline);
+        self.reset_line();
+    }
+
+    fn reset_line(&mut self) {
+        self.emitted = TextLine::default();
+        self.cursor = None;
+        self.cells = None;
+        self.rewrite = false;
+    }
+
+    fn line_width(&self) -> usize {
+        if let Some(ref cells) = self.cells {
+            return cells.len();
+        }
+        let pending = self
+            .current_line
+            .as_ref()
+            .map(|line| line.width())
+            .unwrap_or(0);
+        self.emitted.width() + pending + self.builder.width()
+    }
+
+    fn column(&self) -> usize {
+        self.cursor.unwrap_or_else(|| self.line_width())
+    }
+
+    fn move_cursor(&mut self, col: usize) {
+        self.cursor = if col < self.line_width() {
+            Some(col)
+        } else {
+            None
+        };
+    }
+
+    /// The current line as a whole, including any part of it that has
+    /// already been emitted, for editing
+    fn edit_cells(&mut self) -> &mut Vec<Cell> {
+        let cells = match self.cells.take() {
+            Some(cells) => cells,
+            None => self.take_line_cells(),
+        };
+        self.cells.get_or_insert(cells)
+    }
+
+    fn take_line_cells(&mut self) -> Vec<Cell> {
+        self.builder_to_line();
+
+        let mut cells = line_to_cells(&self.emitted);
+        if !cells.is_empty() {
+            self.emitted = TextLine::default();
+            self.rewrite = true;
+        }
+        if let Some(line) = self.current_line.take() {
+            cells.extend(line_to_cells(&line));
+        }
+        cells
+    }
+
+    fn write_text(&mut self, text: &str) {
+        for ch in text.chars() {
+            self.write_char(ch);
+        }
+    }
+
+    fn write_char(&mut self, ch: char) {
+        if self.cursor.is_none() && self.cells.is_none() {
+            self.builder.push(ch);
+            return;
+        }
+
+        let col = self.column();
+        let style = self.builder.style;
+        let next = put_cell(self.edit_cells(), col, ch, style);
+        self.move_cursor(next);
+    }
+
+    fn tab(&mut self) {
+        let col = self.column();
+        let stop = (col / TAB_WIDTH + 1) * TAB_WIDTH;
+        let width = self.line_width();
+        if stop < width {
+            self.cursor = Some(stop);
+        } else if let Some(ref mut cells) = self.cells {
+            self.cursor = None;
+            cells.resize(stop, (" ".to_string(), self.builder.style));
+        } else {
+            self.cursor = None;
+            for _ in width..stop {
+                self.builder.push(' ');
+            }
+        }
+    }
+
+    /// Handle `CSI K`. Since we don't emulate a screen, `CSI J` is treated
+    /// the same way and only ever affects the current line
+    fn erase_in_line(&mut self, mode: u16) {
+        let col = self.column();
+        let blank = || (" ".to_string(), Style::default());
+        match mode {
+            0 if self.cursor.is_some() => {
+                let cells = self.edit_cells();
+                clear_cell(cells, col);
+                cells.truncate(col);
+            }
+            1 => {
+                let cells = self.edit_cells();
+                clear_cell(cells, col);
+                for cell in cells.iter_mut().take(col + 1) {
+                    *cell = blank();
+                }
+            }
+            2 => {
+                let cells = self.edit_cells();
+                cells.clear();
+                cells.resize_with(col, blank);
+            }
+            _ => {}
+        }
+        self.move_cursor(col);
+    }
+
+    fn apply_mxp_tag(&mut self, tag: MxpTag) {
+        if tag.closing {
+            if tag.name == "send" && matches!(self.link, Some(OpenLink::Send(_))) {
+                self.end_link();
+            }
+            self.close_mxp_style(&tag.name);
+            return;
+        }
+
+        let secure = matches!(self.mxp, Some(ref mxp) if mxp.mode() == MxpMode::Secure);
+        let style = self.builder.style;
+        let new_style = match tag.name.as_str() {
+            "b" | "h" => style.add_modifier(Modifier::BOLD),
+            "i" => style.add_modifier(Modifier::ITALIC),
+            "u" => style.add_modifier(Modifier::UNDERLINED),
+            "s" => style.add_modifier(Modifier::CROSSED_OUT),
+            "c" | "font" => {
+                let fg = tag.attr("fore", 0).or_else(|| tag.attr("color", 0));
+                let bg = tag.attr("back", 1);
+                let style = match fg.and_then(mxp::parse_color) {
+                    Some(color) => style.fg(color),
+                    None => style,
+                };
+                match bg.and_then(mxp::parse_color) {
+                    Some(color) => style.bg(color),
+                    None => style,
+                }
+            }
+            "send" if secure => {
+                self.start_link(OpenLink::Send(tag.clone()));
+                style.add_modifier(Modifier::UNDERLINED)
+            }
+            "br" => {
+                self.newline();
+                return;
+            }
+
+            // Unsupported or not allowed in the current mode
+            _ => return,
+        };
+
+        self.builder_to_line();
+        self.mxp_styles.push((tag.name, style));
+        self.builder.style = new_style;
+    }
+
+    fn close_mxp_style(&mut self, name: &str) {
+        if let Some(index) = self.mxp_styles.iter().rposition(|(open, _)| open == name) {
+            self.builder_to_line();
+            self.builder.style = self.mxp_styles[index].1;
+            self.mxp_styles.truncate(index);
+        }
+    }
+
+    fn start_link(&mut self, link: OpenLink) {
+        // Links are emitted separately from the text around them so
+        // the receiver knows where they are
+        self.end_link();
+        self.line_to_buffer();
+        self.cursor = None;
+        self.link = Some(link);
+    }
+
+    fn end_link(&mut self) {
+        if let Some(link) = self.link.take() {
+            self.cells_to_line();
+            self.builder_to_line();
+            let text = self.current_line.take().unwrap_or_default();
+            let target = match link {
+                OpenLink::Send(tag) => LinkTarget::Send(tag.to_link(&text.to_string())),
+                OpenLink::Url(url, style) => {
+                    self.builder.style = style;
+                    LinkTarget::Url(url)
+                }
+            };
+            self.emitted.0.extend(text.0.iter().cloned());
+            self.buffer.push_back(ReadValue::Link(text, target));
+        }
+    }
+
+    /// Handle an OSC 8 hyperlink; an empty URI closes the current one
+    fn hyperlink(&mut self, uri: String) {
+        if uri.is_empty() {
+            if matches!(self.link, Some(OpenLink::Url(..))) {
+                self.end_link();
+            }
+            return;
+        }
+
+        let style = self.builder.style;
+        self.start_link(OpenLink::Url(uri, style));
+        self.builder.style = style.add_modifier(Modifier::UNDERLINED);
+    }
 }
 
 impl Perform for AnsiPerformer {
     fn print(&mut self, c: char) {
-        self.builder.push(c);
+        let token = match self.mxp {
+            Some(ref mut mxp) => mxp.feed(c),
+            None => Some(MxpToken::Char(c)),
+        };
+
+        match token {
+            Some(MxpToken::Char(c)) => self.write_char(c),
+            Some(MxpToken::Tag(tag)) => self.apply_mxp_tag(tag),
+            Some(MxpToken::Text(text)) => self.write_text(&text),
+            None => {} // Consumed by MXP
+        }
     }
 
     fn execute(&mut self, byte: u8) {
-        match byte as char {
-            '\n' => {
-                self.line_to_buffer();
-                self.buffer.push_back(ReadValue::Newline);
+        match byte {
+            b'\n' => self.newline(),
+            b'\r' => self.move_cursor(0),
+            b'\t' => self.tab(),
+            0x07 => self.buffer.push_back(ReadValue::Bell),
+            0x08 => {
+                let col = self.column();
+                self.move_cursor(col.saturating_sub(1));
             }
-            _ => {} // nop
-        };
+            _ => {}
+        }
     }
 
     fn hook(&mut self, _params: &vte::Params, _intermediates: &[u8], _ignore: bool, _action: char) {
@@ -126,7 +551,20 @@ impl Perform for AnsiPerformer {
 
     fn unhook(&mut self) {}
 
-    fn osc_dispatch(&mut self, _params: &[&[u8]], _bell_terminated: bool) {}
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        match params.split_first() {
+            Some((&b"0", title)) | Some((&b"2", title)) => {
+                let title = String::from_utf8_lossy(&title.join(&b';')).to_string();
+                self.buffer.push_back(ReadValue::Title(title));
+            }
+            Some((&b"8", rest)) => {
+                // The first parameter holds (unsupported) key=value pairs
+                let uri = rest.get(1..).unwrap_or_default().join(&b';');
+                self.hyperlink(String::from_utf8_lossy(&uri).to_string());
+            }
+            _ => {} // Unsupported
+        }
+    }
 
     fn csi_dispatch(
         &mut self,
@@ -135,6 +573,21 @@ impl Perform for AnsiPerformer {
         _ignore: bool,
         action: char,
     ) {
+        if action == 'z' {
+            // MXP line mode
+            if let Some(ref mut mxp) = self.mxp {
+                let mode = params.iter().next().map(|p| p[0]).unwrap_or(0);
+                mxp.set_mode(mode);
+            }
+            return;
+        }
+
+        if action == 'K' || action == 'J' {
+            let mode = params.iter().next().map(|p| p[0]).unwrap_or(0);
+            self.erase_in_line(if action == 'J' && mode == 3 { 2 } else { mode });
+            return;
+        }
+
         if action != 'm' {
             // 'm' means "color"
             return;
@@ -278,6 +731,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn prompt_after_partial_line() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.feed_str("Take my love\nHP: 42>");
+        pipe.end_prompt();
+        assert_eq!(pipe.next(), Some(ReadValue::Text("Take my love".into())));
+        assert_eq!(pipe.next(), Some(ReadValue::Newline));
+        assert_eq!(pipe.next(), Some(ReadValue::Prompt("HP: 42>".into())));
+        assert_eq!(pipe.next(), None);
+    }
+
+    #[test]
+    fn skip_empty_prompt() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.feed_str("Take my love\n");
+        pipe.end_prompt();
+        assert_eq!(pipe.next(), Some(ReadValue::Text("Take my love".into())));
+        assert_eq!(pipe.next(), Some(ReadValue::Newline));
+        assert_eq!(pipe.next(), None);
+    }
+
+    #[test]
+    fn hold_partial_lines_when_expecting_prompts() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.expect_prompts();
+        pipe.feed_str("Take my ");
+        assert_eq!(pipe.next(), None);
+
+        pipe.feed_str("land\n");
+        assert_eq!(pipe.next(), Some(ReadValue::Text("Take my land".into())));
+        assert_eq!(pipe.next(), Some(ReadValue::Newline));
+    }
+
     #[test]
     fn simple_color() {
         let mut pipe = AnsiPipeline::new();
@@ -373,4 +859,129 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn mxp_formatting() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.enable_mxp();
+        pipe.feed_str("<b>Take</b> <color red>my</color> &lt;3");
+        assert_eq!(
+            pipe.next().unwrap(),
+            ReadValue::Text(TextLine::from(vec![
+                Span::styled("Take", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" "),
+                Span::styled("my", Style::default().fg(Color::Red)),
+                Span::raw(" <3"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn mxp_links_require_secure_mode() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.enable_mxp();
+        pipe.feed_str("Take <send>love</send>\n");
+        assert_eq!(pipe.next(), Some(ReadValue::Text("Take love".into())));
+        assert_eq!(pipe.next(), Some(ReadValue::Newline));
+
+        pipe.feed_str("\x1b[1zTake <send href=\"get love\">love</send>!");
+        assert_eq!(pipe.next(), Some(ReadValue::Text("Take ".into())));
+        assert_eq!(
+            pipe.next(),
+            Some(ReadValue::Link(
+                TextLine::from(vec![Span::styled(
+                    "love",
+                    Style::default().add_modifier(Modifier::UNDERLINED)
+                )]),
+                LinkTarget::Send(mxp::MxpLink {
+                    command: "get love".to_string(),
+                    hint: None,
+                    prompt: false,
+                })
+            ))
+        );
+        assert_eq!(pipe.next(), Some(ReadValue::Text("!".into())));
+    }
+
+    #[test]
+    fn carriage_return_overwrites() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.feed_str("Take my love");
+        assert_eq!(pipe.next(), Some(ReadValue::Text("Take my love".into())));
+
+        pipe.feed_str("\rMake");
+        assert_eq!(
+            pipe.next(),
+            Some(ReadValue::ReplaceLine("Make my love".into()))
+        );
+
+        pipe.feed_str("\r\x1b[KTake my land\n");
+        assert_eq!(
+            pipe.next(),
+            Some(ReadValue::ReplaceLine("Take my land".into()))
+        );
+        assert_eq!(pipe.next(), Some(ReadValue::Newline));
+    }
+
+    #[test]
+    fn backspace_and_tabs() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.feed_str("Take\tmy lovx\x08e\rT\tM");
+        assert_eq!(pipe.next(), Some(ReadValue::Text("Take    My love".into())));
+    }
+
+    #[test]
+    fn tabs_after_wide_chars() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.feed_str("見て\tmy love\n見て\rab\tc");
+        assert_eq!(pipe.next(), Some(ReadValue::Text("見て    my love".into())));
+        assert_eq!(pipe.next(), Some(ReadValue::Newline));
+        assert_eq!(pipe.next(), Some(ReadValue::Text("abて    c".into())));
+    }
+
+    #[test]
+    fn overwrite_wide_chars() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.feed_str("見て love");
+        assert_eq!(pipe.next(), Some(ReadValue::Text("見て love".into())));
+
+        pipe.feed_str("\rX");
+        assert_eq!(
+            pipe.next(),
+            Some(ReadValue::ReplaceLine("X て love".into()))
+        );
+
+        pipe.feed_str("\t\x1b[K!");
+        assert_eq!(
+            pipe.next(),
+            Some(ReadValue::ReplaceLine("X て lov!".into()))
+        );
+    }
+
+    #[test]
+    fn bell_and_title() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.feed_str("\x1b]2;Serenity\x07Take\x07");
+        assert_eq!(pipe.next(), Some(ReadValue::Title("Serenity".into())));
+        assert_eq!(pipe.next(), Some(ReadValue::Bell));
+        assert_eq!(pipe.next(), Some(ReadValue::Text("Take".into())));
+    }
+
+    #[test]
+    fn osc8_hyperlinks() {
+        let mut pipe = AnsiPipeline::new();
+        pipe.feed_str("Take \x1b]8;;https://example.com\x1b\\my love\x1b]8;;\x1b\\!");
+        assert_eq!(pipe.next(), Some(ReadValue::Text("Take ".into())));
+        assert_eq!(
+            pipe.next(),
+            Some(ReadValue::Link(
+                TextLine::from(vec![Span::styled(
+                    "my love",
+                    Style::default().add_modifier(Modifier::UNDERLINED)
+                )]),
+                LinkTarget::Url("https://example.com".to_string())
+            ))
+        );
+        assert_eq!(pipe.next(), Some(ReadValue::Text("!".into())));
+    }
 }
@@ -5,36 +5,86 @@ use url::Url;
 
 use crate::{
     app::jobs::{JobContext, JobRecord, Jobs},
-    editing::{ids::Ids, Id, Resizable, Size},
+    editing::{ids::Ids, tabpages::Tabpages, Id, Resizable, Size},
     game::engine::GameEngine,
 };
 
 use super::{
+    encoding::SharedEncoding,
     flags::Flags,
     game::GameConnection,
+    links,
+    queue::{OutgoingMessage, OutgoingQueue, RateLimit},
     reader::{StopSignal, TransportReader},
+    reconnect::ReconnectPolicy,
+    sizing::NawsSizing,
     transport::Transport,
-    ConnectParams, TransportFactories,
+    ConnectParams, EnvironVars, GmcpMessage, MsdpVariable, MsspData, OutputLinks, OutputOriginals,
+    ProxyConfig, SessionRecorder, TransportFactories,
 };
 
 enum OutgoingEvent {
-    Message(String),
     Resize(Size),
 }
 
 pub struct ConnectionRecord {
     pub id: Id,
+    pub uri: Url,
     #[allow(unused)]
     stop_read_signal: StopSignal,
     outgoing: mpsc::UnboundedSender<OutgoingEvent>,
     outgoing_results: std::sync::mpsc::Receiver<io::Result<()>>,
     connection: GameConnection,
+
+    /// The size most recently reported to the server
+    reported_size: Size,
+
+    /// Messages waiting to be sent, subject to any RateLimit
+    pub queue: OutgoingQueue,
+
     pub flags: Flags,
+    pub encoding: SharedEncoding,
+    pub environ: EnvironVars,
+    pub recorder: SessionRecorder,
+
+    /// Server status data, if the server has provided any via MSSP
+    pub mssp: Option<MsspData>,
+
+    /// The window title most recently set by the server, if any
+    pub title: Option<String>,
+
+    /// MXP links received, by their location in the output buffer
+    pub links: OutputLinks,
+
+    /// Received lines that were rewritten or gagged, by their location in the output buffer
+    pub originals: OutputOriginals,
 }
 
 impl ConnectionRecord {
+    /// Enqueue a message to be sent; this does not wait for the message to
+    /// actually be sent, since there may be a RateLimit in effect. Messages
+    /// sent while the server has disabled echo are treated as sensitive
     pub fn send(&mut self, message: String) -> io::Result<()> {
-        self.send_event(OutgoingEvent::Message(message))
+        self.queue.push(OutgoingMessage::Text {
+            text: message,
+            sensitive: !self.flags.can_echo(),
+        })
+    }
+
+    /// Returns true if the input buffer for this connection should be
+    /// masked, since the server has disabled echo (IE: for passwords)
+    pub fn is_input_masked(&self) -> bool {
+        !self.flags.can_echo()
+    }
+
+    /// Enqueue a GMCP message, to be sent after any messages already queued
+    pub fn send_gmcp(&mut self, message: GmcpMessage) -> io::Result<()> {
+        self.queue.push(OutgoingMessage::Gmcp(message))
+    }
+
+    /// Enqueue an MSDP variable, to be sent after any messages already queued
+    pub fn send_msdp(&mut self, variable: MsdpVariable) -> io::Result<()> {
+        self.queue.push(OutgoingMessage::Msdp(variable))
     }
 
     pub fn with_engine<R, F: FnOnce(&GameEngine) -> R>(&self, f: F) -> R {
@@ -55,6 +105,12 @@ impl ConnectionRecord {
     }
 }
 
+impl Drop for ConnectionRecord {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
 #[derive(Default)]
 pub struct Connections {
     ids: Ids,
@@ -70,23 +126,26 @@ pub struct Connections {
     factories: TransportFactories,
 
     buffer_engines: HashMap<Id, GameEngine>,
+
+    // Keyed by (output) buffer ID
+    reconnect_policies: HashMap<Id, ReconnectPolicy>,
+    reconnecting: HashMap<Id, Id>,
+    rate_limits: HashMap<Id, RateLimit>,
+
+    /// The full uri most recently used for each (output) buffer, which may include
+    /// secrets redacted from the buffer's source
+    uris: HashMap<Id, Url>,
+
+    proxy: Option<ProxyConfig>,
+    naws_sizing: NawsSizing,
+    url_opener: Option<String>,
 }
 
 impl Resizable for Connections {
     fn resize(&mut self, new_size: Size) {
-        let changed = self.app_size != new_size;
+        // NOTE: The size reported to the server is updated after layout
+        // (since it may depend on the output windows); see `resize_for_windows`
         self.app_size = new_size;
-
-        // NOTE: We currently do NOT base the NAWS-reported "size" on the
-        // actual output window's size, since there *could* be another
-        // window with a different size. Perhaps in the future we could come
-        // up with some heuristic based on the actual windows for the output
-        // buffer, but for now it's simpler to just use the terminal size
-        if changed {
-            for record in self.by_id.values_mut() {
-                record.send_event(OutgoingEvent::Resize(new_size)).ok();
-            }
-        }
     }
 }
 
@@ -141,6 +200,96 @@ impl Connections {
         }
     }
 
+    /// The default proxy used for new connections, if any
+    pub fn proxy(&self) -> Option<&ProxyConfig> {
+        self.proxy.as_ref()
+    }
+
+    pub fn set_proxy(&mut self, proxy: Option<ProxyConfig>) {
+        self.proxy = proxy;
+    }
+
+    /// Returns true if the given buffer is the input buffer of a connection
+    /// whose input should currently be masked
+    pub fn is_masked_input(&self, buffer_id: Id) -> bool {
+        match self.buffer_to_id(buffer_id) {
+            Some(id) if self.id_to_buffer(id) != Some(buffer_id) => self
+                .by_id(id)
+                .map(|conn| conn.is_input_masked())
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// The title set by the server for the connection associated with
+    /// the given (input or output) buffer, if any
+    pub fn title_for_buffer(&self, buffer_id: Id) -> Option<&str> {
+        self.buffer_to_id(buffer_id)
+            .and_then(|id| self.by_id(id))
+            .and_then(|conn| conn.title.as_deref())
+    }
+
+    pub fn naws_sizing(&self) -> NawsSizing {
+        self.naws_sizing
+    }
+
+    pub fn set_naws_sizing(&mut self, sizing: NawsSizing) {
+        self.naws_sizing = sizing;
+    }
+
+    /// The program used to open URLs in connection output
+    pub fn url_opener(&self) -> &str {
+        match self.url_opener {
+            Some(ref opener) => opener,
+            None => links::default_opener(),
+        }
+    }
+
+    pub fn set_url_opener(&mut self, opener: Option<String>) {
+        self.url_opener = opener;
+    }
+
+    /// The (output) buffer IDs of all active connections
+    pub fn buffer_ids(&self) -> Vec<Id> {
+        self.connection_to_buffer.values().cloned().collect()
+    }
+
+    /// Report sizes based on the Windows showing each connection's output,
+    /// per the current NawsSizing. This should be called after each layout
+    pub fn resize_for_windows(&mut self, tabpages: &mut Tabpages) {
+        for buffer_id in self.buffer_ids() {
+            let windows = tabpages.windows_for_buffer(buffer_id).map(|win| &**win);
+            if let Some(size) = self.naws_sizing.pick(self.app_size, windows) {
+                self.resize_buffer(buffer_id, size);
+            }
+        }
+    }
+
+    /// Report a new size to the server for the connection associated with the given
+    /// (output) buffer, if it differs from what we last reported
+    pub fn resize_buffer(&mut self, buffer_id: Id, size: Size) {
+        if let Some(record) = self.by_buffer_id(buffer_id) {
+            if record.reported_size != size {
+                record.reported_size = size;
+                record.send_event(OutgoingEvent::Resize(size)).ok();
+            }
+        }
+    }
+
+    /// Set (or clear) the RateLimit for messages sent to the connection associated
+    /// with the given (output) buffer, now and after any reconnect
+    pub fn set_rate_limit(&mut self, buffer_id: Id, limit: Option<RateLimit>) {
+        if let Some(limit) = limit {
+            self.rate_limits.insert(buffer_id, limit);
+        } else {
+            self.rate_limits.remove(&buffer_id);
+        }
+
+        if let Some(conn) = self.by_buffer_id(buffer_id) {
+            conn.queue.set_rate_limit(limit);
+        }
+    }
+
     /// Asynchronously create a new connection attached to the given buffer_id (and
     /// input_buffer_id). Returns a JobRecord for joining on the request
     pub fn create_async(
@@ -150,38 +299,194 @@ impl Connections {
         input_buffer_id: Id,
         uri: Url,
     ) -> JobRecord {
+        self.cancel_reconnect(jobs, buffer_id);
+        self.uris.insert(buffer_id, uri.clone());
+
         let id = self.ids.next();
         let factory = self.factories.clone();
         let size = self.app_size;
+        let proxy = self.proxy.clone();
 
         jobs.start(move |ctx| async move {
-            let params = ConnectParams::with_uri_and_size(uri, size);
-            let flags = params.flags.clone();
-            let connection = Mutex::new(factory.create(params)?);
-            let transport_context = Mutex::new(ctx.clone());
+            let mut params = ConnectParams::with_uri_and_size(uri, size);
+            params.proxy = proxy;
+            Connections::attach(&ctx, &factory, id, buffer_id, input_buffer_id, params)?;
+            Ok(())
+        })
+    }
 
-            ctx.run(move |state| {
-                state.connections.add_transport(
-                    transport_context.into_inner().unwrap(),
-                    id,
+    /// The full uri most recently used to connect the given (output) buffer
+    pub fn last_uri(&self, buffer_id: Id) -> Option<&Url> {
+        self.uris.get(&buffer_id)
+    }
+
+    /// Set (or clear) the policy for automatically reconnecting the connection
+    /// associated with the given (output) buffer when it's dropped by the server
+    pub fn set_reconnect_policy(&mut self, buffer_id: Id, policy: Option<ReconnectPolicy>) {
+        if let Some(policy) = policy {
+            self.reconnect_policies.insert(buffer_id, policy);
+        } else {
+            self.reconnect_policies.remove(&buffer_id);
+        }
+    }
+
+    /// Cancel any pending automatic reconnect for the given (output) buffer.
+    /// Returns true if there was one to cancel
+    pub fn cancel_reconnect(&mut self, jobs: &mut Jobs, buffer_id: Id) -> bool {
+        if let Some(job_id) = self.reconnecting.remove(&buffer_id) {
+            jobs.cancel(job_id);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Called when a connection has been dropped by the server (as opposed to
+    /// being closed by the user). If there's a ReconnectPolicy for its buffer,
+    /// a Job will be started to reconnect it, reusing its buffers and GameEngine
+    pub fn on_dropped(&mut self, jobs: &mut Jobs, connection_id: Id) {
+        let (buffer_id, uri) = match (self.id_to_buffer(connection_id), self.by_id(connection_id)) {
+            (Some(buffer_id), Some(record)) => (buffer_id, record.uri.clone()),
+            _ => return,
+        };
+        let input_buffer_id = self
+            .id_to_buffers(connection_id)
+            .into_iter()
+            .find(|id| *id != buffer_id)
+            .unwrap_or(buffer_id);
+
+        self.disconnect(connection_id).ok();
+
+        if uri.scheme() == "replay" {
+            // The recording simply ended; there's nothing to reconnect to
+            return;
+        }
+
+        if let Some(policy) = self.reconnect_policies.get(&buffer_id).cloned() {
+            let job_id = self.reconnect_async(jobs, buffer_id, input_buffer_id, uri, policy);
+            self.reconnecting.insert(buffer_id, job_id);
+        }
+    }
+
+    fn reconnect_async(
+        &mut self,
+        jobs: &mut Jobs,
+        buffer_id: Id,
+        input_buffer_id: Id,
+        uri: Url,
+        policy: ReconnectPolicy,
+    ) -> Id {
+        let id = self.ids.next();
+        let factory = self.factories.clone();
+        let size = self.app_size;
+        let proxy = self.proxy.clone();
+
+        jobs.spawn(move |ctx| async move {
+            for attempt in 1..=policy.max_attempts {
+                let delay = policy.delay_for_attempt(attempt);
+                Connections::report(
+                    &ctx,
                     buffer_id,
-                    input_buffer_id,
-                    flags,
-                    connection.into_inner().unwrap(),
+                    format!(
+                        "Reconnecting in {}s (attempt {}/{}; <ctrl-c> to cancel)...",
+                        delay.as_secs(),
+                        attempt,
+                        policy.max_attempts
+                    ),
                 );
-            });
+                tokio::time::sleep(delay).await;
+
+                let mut params = ConnectParams::with_uri_and_size(uri.clone(), size);
+                params.proxy = proxy.clone();
+                match Connections::attach(&ctx, &factory, id, buffer_id, input_buffer_id, params) {
+                    Ok(_) => {
+                        Connections::report(&ctx, buffer_id, "Reconnected.".to_string());
+                        ctx.run(move |state| {
+                            state.connections.reconnecting.remove(&buffer_id);
+                        });
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        Connections::report(&ctx, buffer_id, format!("Reconnect failed: {}", e));
+                    }
+                }
+            }
 
+            Connections::report(
+                &ctx,
+                buffer_id,
+                format!("Giving up after {} attempts.", policy.max_attempts),
+            );
+            ctx.run(move |state| {
+                state.connections.reconnecting.remove(&buffer_id);
+            });
             Ok(())
         })
     }
 
+    fn report(ctx: &JobContext, buffer_id: Id, message: String) {
+        ctx.run(move |state| {
+            if let Some(mut win) = state.winsbuf_by_id(buffer_id) {
+                win.append_line(message);
+            }
+        });
+    }
+
+    /// Create a Transport for the given params and (asynchronously) attach it
+    /// to the given buffers
+    fn attach(
+        ctx: &JobContext,
+        factory: &TransportFactories,
+        id: Id,
+        buffer_id: Id,
+        input_buffer_id: Id,
+        params: ConnectParams,
+    ) -> io::Result<()> {
+        let connection = Mutex::new(factory.create(&params)?);
+        let transport_context = Mutex::new(ctx.clone());
+
+        ctx.run(move |state| {
+            state.connections.add_transport(
+                transport_context.into_inner().unwrap(),
+                id,
+                buffer_id,
+                input_buffer_id,
+                &params,
+                connection.into_inner().unwrap(),
+            );
+        });
+
+        Ok(())
+    }
+
     fn launch(
         id: Id,
         ctx: JobContext,
         buffer_id: Id,
         connection: GameConnection,
-        flags: Flags,
+        params: &ConnectParams,
     ) -> ConnectionRecord {
+        let queue = OutgoingQueue::default();
+        let mut sendable = connection.clone();
+        let send_ctx = ctx.clone();
+        tokio::spawn(queue.clone().pump(move |message| {
+            let result = match message {
+                OutgoingMessage::Text { text, sensitive } if sensitive => {
+                    sendable.send_sensitive(&text)
+                }
+                OutgoingMessage::Text { text, .. } => sendable.send(&text),
+                OutgoingMessage::Gmcp(message) => sendable.send_gmcp(message),
+                OutgoingMessage::Msdp(variable) => sendable.send_msdp(variable),
+            };
+            if let Err(e) = result {
+                Connections::report(&send_ctx, buffer_id, format!("Error sending: {}", e));
+            } else {
+                // Nothing to do on the main thread, but the pending count has changed
+                // so it should be redrawn:
+                send_ctx.run(|_| {});
+            }
+        }));
+
         let stop_read_signal = TransportReader::spawn(ctx, id, buffer_id, connection.clone());
 
         let (tx, mut rx) = mpsc::unbounded_channel::<OutgoingEvent>();
@@ -190,11 +495,6 @@ impl Connections {
         tokio::spawn(async move {
             while let Some(to_send) = rx.recv().await {
                 match to_send {
-                    OutgoingEvent::Message(message) => {
-                        let result = writable.send(&message);
-                        result_tx.send(result).ok();
-                    }
-
                     OutgoingEvent::Resize(size) => {
                         let result = writable.resize(size);
                         result_tx.send(result).ok();
@@ -205,22 +505,36 @@ impl Connections {
 
         ConnectionRecord {
             id,
+            uri: params.uri.clone(),
             stop_read_signal,
             outgoing: tx,
             outgoing_results: result_rx,
             connection,
-            flags,
+            reported_size: params.size,
+            queue,
+            flags: params.flags.clone(),
+            encoding: params.encoding.clone(),
+            environ: params.environ.clone(),
+            recorder: params.recorder.clone(),
+            mssp: None,
+            title: None,
+            links: Default::default(),
+            originals: Default::default(),
         }
     }
 
     /// Returns the associated buffer ID
     pub fn disconnect(&mut self, connection_id: Id) -> io::Result<Id> {
-        if self.by_id.remove(&connection_id).is_some() {
+        if let Some(record) = self.by_id.remove(&connection_id) {
             let buffer = self
                 .connection_to_buffer
                 .remove(&connection_id)
                 .expect("No buffer associated with connection");
 
+            // Hang onto the engine so aliases, history, etc. survive reconnecting
+            let engine = std::mem::take(&mut *record.connection.game.lock().unwrap());
+            self.buffer_engines.insert(buffer, engine);
+
             // Clean up all buffer->conn mappings
             self.buffer_to_connection
                 .retain(|_buf_id, conn_id| *conn_id != connection_id);
@@ -252,7 +566,7 @@ impl Connections {
         id: Id,
         buffer_id: Id,
         input_buffer_id: Id,
-        flags: Flags,
+        params: &ConnectParams,
         transport: Box<dyn Transport + Send>,
     ) {
         let engine = self
@@ -262,12 +576,15 @@ impl Connections {
 
         let transport = GameConnection::with_engine(transport, engine);
 
-        let record = Connections::launch(id, ctx.clone(), buffer_id, transport, flags);
+        let record = Connections::launch(id, ctx.clone(), buffer_id, transport, params);
 
         self.add_record(id, buffer_id, input_buffer_id, record);
     }
 
     fn add_record(&mut self, id: Id, buffer_id: Id, input_buffer_id: Id, record: ConnectionRecord) {
+        if let Some(limit) = self.rate_limits.get(&buffer_id) {
+            record.queue.set_rate_limit(Some(*limit));
+        }
         self.connection_to_buffer.insert(id, buffer_id);
         self.buffer_to_connection.insert(buffer_id, id);
         self.buffer_to_connection.insert(input_buffer_id, id);
@@ -285,10 +602,20 @@ impl Connections {
             0,
             ConnectionRecord {
                 id: 0,
+                uri: Url::parse("test://localhost").unwrap(),
                 stop_read_signal,
                 outgoing,
                 outgoing_results,
+                reported_size: Default::default(),
+                queue: Default::default(),
                 flags: Default::default(),
+                encoding: Default::default(),
+                environ: Default::default(),
+                recorder: Default::default(),
+                mssp: None,
+                title: None,
+                links: Default::default(),
+                originals: Default::default(),
                 connection: GameConnection::with_engine(transport, Default::default()),
             },
         );
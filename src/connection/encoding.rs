@@ -0,0 +1,167 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// The high half (0x80-0xFF) of Code Page 437, used by a lot of ANSI art.
+/// The low half is treated as ASCII, since we need control characters
+/// (like ESC and newlines) to retain their usual meaning.
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+const UNMAPPABLE: u8 = b'?';
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Latin1,
+    Cp437,
+    Ascii,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::Utf8
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 4] = [
+        Encoding::Utf8,
+        Encoding::Latin1,
+        Encoding::Cp437,
+        Encoding::Ascii,
+    ];
+
+    /// Look up an Encoding by (case-insensitive) name, including common
+    /// aliases like those used in CHARSET negotiation
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name.trim().to_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "latin-1" => {
+                Some(Encoding::Latin1)
+            }
+            "cp437" | "ibm437" | "437" => Some(Encoding::Cp437),
+            "ascii" | "us-ascii" => Some(Encoding::Ascii),
+            _ => None,
+        }
+    }
+
+    /// The canonical name of this Encoding, suitable for CHARSET negotiation
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "ISO-8859-1",
+            Encoding::Cp437 => "CP437",
+            Encoding::Ascii => "US-ASCII",
+        }
+    }
+
+    /// Convert bytes in this encoding into UTF-8 bytes
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, [u8]> {
+        if *self == Encoding::Utf8 || bytes.is_ascii() {
+            return Cow::Borrowed(bytes);
+        }
+
+        let decoded: String = bytes.iter().map(|b| self.decode_byte(*b)).collect();
+        Cow::Owned(decoded.into_bytes())
+    }
+
+    fn decode_byte(&self, b: u8) -> char {
+        match self {
+            _ if b < 0x80 => b as char,
+            Encoding::Cp437 => CP437_HIGH[(b - 0x80) as usize],
+            Encoding::Latin1 => b as char,
+            Encoding::Utf8 | Encoding::Ascii => char::REPLACEMENT_CHARACTER,
+        }
+    }
+
+    /// Convert text into bytes in this encoding. Characters that cannot
+    /// be represented are replaced with `?`
+    pub fn encode<'a>(&self, text: &'a str) -> Cow<'a, [u8]> {
+        if *self == Encoding::Utf8 || text.is_ascii() {
+            return Cow::Borrowed(text.as_bytes());
+        }
+
+        Cow::Owned(text.chars().map(|ch| self.encode_char(ch)).collect())
+    }
+
+    fn encode_char(&self, ch: char) -> u8 {
+        match self {
+            _ if ch.is_ascii() => ch as u8,
+            Encoding::Latin1 if (ch as u32) < 0x100 => ch as u8,
+            Encoding::Cp437 => CP437_HIGH
+                .iter()
+                .position(|c| *c == ch)
+                .map(|index| 0x80 + index as u8)
+                .unwrap_or(UNMAPPABLE),
+            _ => UNMAPPABLE,
+        }
+    }
+}
+
+/// The Encoding used by a Connection, shared between the Connection and
+/// the app so it may be changed at runtime (IE: via CHARSET negotiation)
+#[derive(Clone, Default)]
+pub struct SharedEncoding(Arc<Mutex<Encoding>>);
+
+impl SharedEncoding {
+    pub fn new(encoding: Encoding) -> Self {
+        Self(Arc::new(Mutex::new(encoding)))
+    }
+
+    pub fn get(&self) -> Encoding {
+        *self.0.lock().unwrap()
+    }
+
+    pub fn set(&mut self, encoding: Encoding) {
+        *self.0.lock().unwrap() = encoding;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin1_round_trip() {
+        let decoded = Encoding::Latin1.decode(b"caf\xe9");
+        assert_eq!(std::str::from_utf8(&decoded).unwrap(), "café");
+        assert_eq!(Encoding::Latin1.encode("café").as_ref(), b"caf\xe9");
+    }
+
+    #[test]
+    fn cp437_round_trip() {
+        let decoded = Encoding::Cp437.decode(b"\xc9\xcd\xbb");
+        assert_eq!(std::str::from_utf8(&decoded).unwrap(), "╔═╗");
+        assert_eq!(Encoding::Cp437.encode("╔═╗").as_ref(), b"\xc9\xcd\xbb");
+    }
+
+    #[test]
+    fn unmappable() {
+        assert_eq!(Encoding::Latin1.encode("言葉").as_ref(), b"??");
+        assert_eq!(Encoding::Ascii.encode("café").as_ref(), b"caf?");
+    }
+
+    #[test]
+    fn from_name() {
+        assert_eq!(Encoding::from_name("latin-1"), Some(Encoding::Latin1));
+        assert_eq!(Encoding::from_name("UTF-8"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_name("KOI8-R"), None);
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    io,
+    process::{Command, Stdio},
+};
+
+use url::Url;
+
+use super::{
+    raw::{RawSource, RawTransport},
+    transport::Transport,
+    ConnectParams, TransportFactory,
+};
+
+pub(super) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Extract the program and its arguments from a uri like
+/// `exec:program?arg=first&arg=second` or `exec:///path/to/program`
+fn parse_command(uri: &Url) -> Option<(String, Vec<String>)> {
+    let program = match uri.host_str() {
+        Some(host) => format!("{}{}", host, uri.path()),
+        None => uri.path().to_string(),
+    };
+    let program = percent_decode(&program);
+    if program.is_empty() {
+        return None;
+    }
+
+    let args = uri
+        .query_pairs()
+        .filter(|(key, _)| key == "arg")
+        .map(|(_, value)| value.to_string())
+        .collect();
+
+    Some((program, args))
+}
+
+fn spawn(program: &str, args: &[String], params: &ConnectParams) -> io::Result<RawTransport> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let stdin = child.stdin.take().expect("stdin was not piped");
+    let stdout = child.stdout.take().expect("stdout was not piped");
+    Ok(RawTransport::spawn(
+        RawSource::Process(child),
+        stdout,
+        Box::new(stdin),
+        b"\n",
+        params.encoding.clone(),
+        params.recorder.clone(),
+    ))
+}
+
+/// Local programs, via `exec:program` (or `cmd:program`), whose stdout is
+/// read as output and whose stdin receives anything sent
+pub struct ExecConnectionFactory;
+impl TransportFactory for ExecConnectionFactory {
+    fn clone_boxed(&self) -> Box<dyn TransportFactory> {
+        Box::new(ExecConnectionFactory)
+    }
+
+    fn create(&self, params: &ConnectParams) -> Option<io::Result<Box<dyn Transport + Send>>> {
+        match params.uri.scheme() {
+            "exec" | "cmd" => {}
+            _ => return None,
+        }
+
+        match parse_command(&params.uri) {
+            Some((program, args)) => Some(
+                spawn(&program, &args, params)
+                    .map(|conn| Box::new(conn) as Box<dyn Transport + Send>),
+            ),
+
+            None => Some(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: missing program", params.display_uri()),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::connection::ReadValue;
+
+    use super::*;
+
+    #[test]
+    fn parse_command_uris() {
+        let uri = Url::parse("exec:my%20server?arg=--port&arg=4000").unwrap();
+        assert_eq!(
+            parse_command(&uri),
+            Some((
+                "my server".to_string(),
+                vec!["--port".to_string(), "4000".to_string()]
+            ))
+        );
+
+        let uri = Url::parse("cmd:///usr/bin/mud").unwrap();
+        assert_eq!(
+            parse_command(&uri),
+            Some(("/usr/bin/mud".to_string(), vec![]))
+        );
+    }
+
+    #[test]
+    fn echo_through_process() {
+        let uri = Url::parse("exec:cat").unwrap();
+        let params = ConnectParams::with_uri_and_size(uri, Default::default());
+        let mut transport = spawn("cat", &[], &params).unwrap();
+        transport.send("Take my love").unwrap();
+
+        let mut read = None;
+        for _ in 0..50 {
+            read = transport.read_timeout(Duration::from_millis(100)).unwrap();
+            if read.is_some() {
+                break;
+            }
+        }
+        assert_eq!(read, Some(ReadValue::Text("Take my love".into())));
+    }
+}
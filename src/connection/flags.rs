@@ -6,6 +6,12 @@ use std::{
 #[derive(Eq, PartialEq, Hash)]
 pub enum Flag {
     NoEcho,
+
+    /// Data from the server is being decompressed (MCCP2)
+    CompressedInput,
+
+    /// Data sent to the server is being compressed (MCCP3)
+    CompressedOutput,
 }
 
 #[derive(Clone, Default)]
@@ -21,6 +27,10 @@ impl Flags {
     }
 
     pub fn can_echo(&self) -> bool {
-        !self.0.lock().unwrap().contains(&Flag::NoEcho)
+        !self.has(Flag::NoEcho)
+    }
+
+    pub fn has(&self, flag: Flag) -> bool {
+        self.0.lock().unwrap().contains(&flag)
     }
 }
@@ -6,7 +6,7 @@ use std::{
 
 use crate::{editing::Size, game::engine::GameEngine};
 
-use super::{transport::Transport, ReadValue};
+use super::{transport::Transport, GmcpMessage, MsdpVariable, ReadValue};
 
 #[derive(Clone)]
 pub struct GameConnection {
@@ -21,6 +21,12 @@ impl GameConnection {
             game: Arc::new(Mutex::new(game)),
         }
     }
+
+    /// Send text directly to the Transport, bypassing the GameEngine so it isn't
+    /// recorded in history, learned for completion, or processed by aliases
+    pub fn send_sensitive(&mut self, text: &str) -> io::Result<()> {
+        self.conn.lock().unwrap().send(text)
+    }
 }
 
 impl Transport for GameConnection {
@@ -32,10 +38,59 @@ impl Transport for GameConnection {
         // NOTE: The explicit scoping here and in send() are to ensure this
         // lock gets released before we attempt to access the next lock
         let read = { self.conn.lock().unwrap().read_timeout(duration) };
-        if let Some(value) = read? {
-            Ok(self.game.lock().unwrap().process_received(value))
-        } else {
-            Ok(None)
+        match read? {
+            Some(ReadValue::Gmcp(message)) => {
+                // NOTE: Listeners are invoked without holding the game lock so
+                // they're free to interact with this connection (IE: to send)
+                let listeners = { self.game.lock().unwrap().gmcp.matching(&message.package) };
+                for listener in listeners {
+                    if let Err(e) = listener(message.clone()) {
+                        crate::log_error!("GMCP listener for {}: {:?}", message.package, e);
+                    }
+                }
+                Ok(None)
+            }
+            Some(ReadValue::Msdp(variables)) => {
+                let listeners = {
+                    let mut game = self.game.lock().unwrap();
+                    let mut listeners = vec![];
+                    for variable in variables {
+                        for listener in game.msdp.matching(&variable.name) {
+                            listeners.push((listener, variable.clone()));
+                        }
+                        game.msdp_values
+                            .insert(variable.name.clone(), variable.value);
+                    }
+                    listeners
+                };
+                for (listener, variable) in listeners {
+                    let name = variable.name.clone();
+                    if let Err(e) = listener(variable) {
+                        crate::log_error!("MSDP listener for {}: {:?}", name, e);
+                    }
+                }
+                Ok(None)
+            }
+            Some(value) => {
+                let (processed, fired) = {
+                    let mut game = self.game.lock().unwrap();
+                    let processed = game.process_received(value);
+                    (processed, game.take_fired_triggers())
+                };
+
+                // NOTE: As with listeners, trigger actions are run without the game lock
+                for trigger in fired {
+                    let description = trigger.description.clone();
+                    if let Err(e) = trigger.run() {
+                        crate::log_error!("Trigger {}: {:?}", description, e);
+                    }
+                }
+                Ok(processed)
+            }
+            None => {
+                self.game.lock().unwrap().process_idle();
+                Ok(None)
+            }
         }
     }
 
@@ -52,4 +107,12 @@ impl Transport for GameConnection {
             Ok(())
         }
     }
+
+    fn send_gmcp(&mut self, message: GmcpMessage) -> io::Result<()> {
+        self.conn.lock().unwrap().send_gmcp(message)
+    }
+
+    fn send_msdp(&mut self, variable: MsdpVariable) -> io::Result<()> {
+        self.conn.lock().unwrap().send_msdp(variable)
+    }
 }
@@ -0,0 +1,340 @@
+use std::{
+    io,
+    ops::Range,
+    process::{Command, Stdio},
+    thread,
+};
+
+use tui::style::{Modifier, Style};
+use url::Url;
+
+use crate::editing::{
+    text::{EditableLine, TextLine},
+    CursorPosition,
+};
+
+use super::MxpLink;
+
+const URL_PREFIXES: &[&str] = &["https://", "http://", "www."];
+
+/// URLs come from the server, so only schemes known to be safe to hand
+/// off to an external program may be opened
+const OPENABLE_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// What happens when an OutputLink is activated
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkTarget {
+    /// An MXP `<send>` link, which sends a command to the server
+    Send(MxpLink),
+
+    /// A URL, either from an OSC 8 hyperlink or detected in plain text,
+    /// which is opened with an external program
+    Url(String),
+}
+
+impl LinkTarget {
+    /// The command or URL this link targets
+    pub fn text(&self) -> &str {
+        match self {
+            LinkTarget::Send(link) => &link.command,
+            LinkTarget::Url(url) => url,
+        }
+    }
+}
+
+/// A LinkTarget along with its location in an output Buffer
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputLink {
+    pub line: usize,
+
+    /// The columns the link occupies on its line. Like CursorPosition,
+    /// these count chars rather than display cells
+    pub cols: Range<usize>,
+    pub link: LinkTarget,
+}
+
+impl OutputLink {
+    pub fn start(&self) -> CursorPosition {
+        CursorPosition {
+            line: self.line,
+            col: self.cols.start,
+        }
+    }
+
+    pub fn contains(&self, pos: CursorPosition) -> bool {
+        pos.line == self.line && self.cols.contains(&pos.col)
+    }
+}
+
+/// The links received on a Connection, ordered by their location
+#[derive(Default)]
+pub struct OutputLinks {
+    links: Vec<OutputLink>,
+}
+
+impl OutputLinks {
+    pub fn push(&mut self, link: OutputLink) {
+        let index = self
+            .links
+            .partition_point(|existing| existing.start() <= link.start());
+        self.links.insert(index, link);
+    }
+
+    pub fn clear(&mut self) {
+        self.links.clear();
+    }
+
+    /// Forget any links on the given line, IE: because it was replaced
+    pub fn remove_line(&mut self, line: usize) {
+        self.links.retain(|link| link.line != line);
+    }
+
+    /// Returns true if any link on the given line overlaps the given columns
+    pub fn overlaps(&self, line: usize, cols: &Range<usize>) -> bool {
+        self.links.iter().any(|link| {
+            link.line == line && link.cols.start < cols.end && cols.start < link.cols.end
+        })
+    }
+
+    pub fn at(&self, pos: CursorPosition) -> Option<&OutputLink> {
+        self.links.iter().find(|link| link.contains(pos))
+    }
+
+    pub fn next_after(&self, pos: CursorPosition) -> Option<&OutputLink> {
+        self.links.iter().find(|link| link.start() > pos)
+    }
+
+    pub fn prev_before(&self, pos: CursorPosition) -> Option<&OutputLink> {
+        self.links
+            .iter()
+            .rev()
+            .find(|link| link.start() < pos && !link.contains(pos))
+    }
+}
+
+fn is_url_char(ch: char) -> bool {
+    !ch.is_whitespace() && !ch.is_control() && !matches!(ch, '<' | '>' | '"' | '\'' | '`')
+}
+
+/// Trailing punctuation is more likely to belong to the surrounding
+/// sentence than to the URL, unless it closes a bracket within the URL
+fn trim_url_end(chars: &[char], start: usize, mut end: usize) -> usize {
+    while end > start {
+        let url = &chars[start..end];
+        let count = |target: char| url.iter().filter(|ch| **ch == target).count();
+        let trim = match chars[end - 1] {
+            '.' | ',' | ';' | ':' | '!' | '?' => true,
+            ')' => count(')') > count('('),
+            ']' => count(']') > count('['),
+            _ => false,
+        };
+        if !trim {
+            break;
+        }
+        end -= 1;
+    }
+    end
+}
+
+fn url_prefix_at(chars: &[char], start: usize) -> Option<&'static str> {
+    if start > 0 && chars[start - 1].is_alphanumeric() {
+        return None;
+    }
+
+    URL_PREFIXES.iter().copied().find(|prefix| {
+        let len = prefix.chars().count();
+        chars.len() >= start + len
+            && chars[start..start + len]
+                .iter()
+                .zip(prefix.chars())
+                .all(|(a, b)| a.to_ascii_lowercase() == b)
+    })
+}
+
+/// Find any bare URLs in the given text, returning the columns of each
+/// along with the URL to open for it
+pub fn find_urls(text: &str) -> Vec<(Range<usize>, String)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut urls = vec![];
+    let mut start = 0;
+    while start < chars.len() {
+        let prefix = match url_prefix_at(&chars, start) {
+            Some(prefix) => prefix,
+            None => {
+                start += 1;
+                continue;
+            }
+        };
+
+        let mut end = start;
+        while end < chars.len() && is_url_char(chars[end]) {
+            end += 1;
+        }
+        end = trim_url_end(&chars, start, end);
+
+        if end <= start + prefix.len() {
+            // Just the prefix on its own
+            start += 1;
+            continue;
+        }
+
+        let url: String = chars[start..end].iter().collect();
+        let url = if prefix == "www." {
+            format!("http://{}", url)
+        } else {
+            url
+        };
+        urls.push((start..end, url));
+        start = end;
+    }
+    urls
+}
+
+/// Underline the given column ranges of a line, to mark them as links
+pub fn underline(mut line: TextLine, ranges: &[Range<usize>]) -> TextLine {
+    let underlined = Style::default().add_modifier(Modifier::UNDERLINED);
+    for range in ranges {
+        line = line.patch_style(range.clone(), underlined);
+    }
+    line
+}
+
+/// The program used to open URLs if none has been configured
+pub fn default_opener() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// Returns the given URL if it is safe to open, or an error if not
+fn openable_url(url: &str) -> io::Result<Url> {
+    let refuse = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Refusing to open url: {}", url),
+        )
+    };
+
+    if url.starts_with('-') {
+        return Err(refuse());
+    }
+
+    match Url::parse(url) {
+        Ok(parsed) if OPENABLE_SCHEMES.contains(&parsed.scheme()) => Ok(parsed),
+        _ => Err(refuse()),
+    }
+}
+
+/// Open the given URL with an external program, without waiting for it.
+/// Only http, https and mailto URLs may be opened.
+pub fn open_url(opener: &str, url: &str) -> io::Result<()> {
+    let url = openable_url(url)?;
+    let mut child = Command::new(opener)
+        .arg(url.as_str())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // Reap the process whenever it finishes
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tui::text::Span;
+
+    use super::*;
+
+    #[test]
+    fn navigate_links() {
+        let link = |line, cols| OutputLink {
+            line,
+            cols,
+            link: LinkTarget::Send(MxpLink {
+                command: "look".to_string(),
+                hint: None,
+                prompt: false,
+            }),
+        };
+        let mut links = OutputLinks::default();
+        links.push(link(2, 0..4));
+        links.push(link(0, 4..8));
+
+        assert_eq!(links.at((0, 5).into()), Some(&link(0, 4..8)));
+        assert_eq!(links.at((0, 8).into()), None);
+        assert_eq!(links.next_after((0, 4).into()), Some(&link(2, 0..4)));
+        assert_eq!(links.prev_before((0, 6).into()), None);
+        assert_eq!(links.prev_before((1, 0).into()), Some(&link(0, 4..8)));
+        assert!(links.overlaps(0, &(7..12)));
+        assert!(!links.overlaps(0, &(8..12)));
+    }
+
+    #[test]
+    fn find_bare_urls() {
+        let found = find_urls("See https://example.com/a_(b). Or (www.example.org)!");
+        assert_eq!(
+            found,
+            vec![
+                (4..29, "https://example.com/a_(b)".to_string()),
+                (35..50, "http://www.example.org".to_string()),
+            ]
+        );
+
+        assert_eq!(find_urls("http:// is not a url"), vec![]);
+        assert_eq!(find_urls("awww.example.com"), vec![]);
+    }
+
+    #[test]
+    fn find_urls_after_wide_chars() {
+        let found = find_urls("見て https://example.com");
+        assert_eq!(found, vec![(3..22, "https://example.com".to_string())]);
+    }
+
+    #[test]
+    fn underline_after_wide_chars() {
+        let underlined = Style::default().add_modifier(Modifier::UNDERLINED);
+        let line = underline("見て my love".into(), &[3..5, 6..10]);
+        assert_eq!(
+            line,
+            TextLine::from(vec![
+                Span::raw("見て "),
+                Span::styled("my", underlined),
+                Span::raw(" "),
+                Span::styled("love", underlined),
+            ])
+        );
+    }
+
+    #[test]
+    fn openable_urls() {
+        assert!(openable_url("https://example.com").is_ok());
+        assert!(openable_url("http://example.com/a?b=c").is_ok());
+        assert!(openable_url("mailto:mal@serenity.com").is_ok());
+
+        assert!(openable_url("file:///etc/passwd").is_err());
+        assert!(openable_url("javascript:alert(1)").is_err());
+        assert!(openable_url("-n").is_err());
+        assert!(openable_url("--help").is_err());
+        assert!(openable_url("example.com").is_err());
+    }
+
+    #[test]
+    fn underline_ranges() {
+        let underlined = Style::default().add_modifier(Modifier::UNDERLINED);
+        let line = underline("Take my love".into(), &[0..4, 8..12]);
+        assert_eq!(
+            line,
+            TextLine::from(vec![
+                Span::styled("Take", underlined),
+                Span::raw(" my "),
+                Span::styled("love", underlined),
+            ])
+        );
+    }
+}
@@ -1,40 +1,155 @@
-use std::io;
+use std::{io, net::TcpStream};
 
 use url::Url;
 
 use crate::editing::{text::TextLine, Size};
 
-use self::{telnet::TelnetConnectionFactory, transport::Transport};
+use self::{
+    exec::ExecConnectionFactory,
+    tcp::TcpConnectionFactory,
+    telnet::{ReplayConnectionFactory, TelnetConnectionFactory},
+    transport::Transport,
+};
 
 mod ansi;
 pub mod connections;
+pub mod encoding;
+mod exec;
 mod flags;
 pub mod game;
+pub mod links;
+pub mod mxp;
+pub mod originals;
+pub mod proxy;
+pub mod queue;
+mod raw;
 mod reader;
+pub mod reconnect;
+pub mod recording;
+pub mod redact;
+pub mod sizing;
+mod tcp;
 mod telnet;
 mod tls;
 pub mod transport;
 
-pub use flags::Flags;
+pub use encoding::{Encoding, SharedEncoding};
+pub use flags::{Flag, Flags};
+pub use links::{LinkTarget, OutputLinks};
+pub use mxp::MxpLink;
+pub use originals::OutputOriginals;
+pub use proxy::ProxyConfig;
+pub use queue::RateLimit;
+pub use reconnect::ReconnectPolicy;
+pub use recording::SessionRecorder;
+pub use sizing::NawsSizing;
+pub use telnet::environ::EnvironVars;
+pub use telnet::gmcp::GmcpMessage;
+pub use telnet::msdp::{MsdpValue, MsdpVariable};
+pub use telnet::mssp::MsspData;
 
 #[derive(Debug, PartialEq)]
 pub enum ReadValue {
     Newline,
     Text(TextLine),
+
+    /// Replaces the current (last) line in its entirety, as when the server
+    /// moves the cursor back over text already received (IE: via `\r`)
+    ReplaceLine(TextLine),
+
+    /// A complete prompt line, as indicated by the server via IAC GA
+    /// or IAC EOR. Only the most recent prompt is generally displayed
+    Prompt(TextLine),
+
+    /// Text that, when selected, should either send a command to the server
+    /// (from MXP `<send>` tags) or open a URL (from OSC 8 hyperlinks)
+    Link(TextLine, LinkTarget),
+
+    /// An out-of-band GMCP message; these are not rendered, but are
+    /// dispatched to any script listeners
+    Gmcp(GmcpMessage),
+
+    /// Updated MSDP variables; like GMCP, these are not rendered
+    Msdp(Vec<MsdpVariable>),
+
+    /// Server status information; this is stored on the ConnectionRecord
+    Mssp(MsspData),
+
+    /// The server rang the bell (BEL)
+    Bell,
+
+    /// The server set the window title via OSC 0 or 2
+    Title(String),
+
+    /// Ends a line (like Newline) that was rewritten by the GameEngine, or gagged
+    /// entirely if there's no replacement. The line as it was received is kept
+    /// so it can be shown for debugging
+    Rewritten(TextLine, Option<TextLine>),
 }
 
 pub struct ConnectParams {
     uri: Url,
     size: Size,
     flags: Flags,
+    encoding: SharedEncoding,
+    environ: EnvironVars,
+    recorder: SessionRecorder,
+
+    /// The default proxy, used unless overridden by the uri
+    proxy: Option<ProxyConfig>,
 }
 
 impl ConnectParams {
     pub fn with_uri_and_size(uri: Url, size: Size) -> Self {
+        let encoding = Self::encoding_from_uri(&uri).unwrap_or_default();
         Self {
             uri,
             size,
             flags: Flags::default(),
+            encoding: SharedEncoding::new(encoding),
+            environ: EnvironVars::default(),
+            recorder: SessionRecorder::default(),
+            proxy: None,
+        }
+    }
+
+    /// The uri, with any secrets redacted for display
+    pub fn display_uri(&self) -> String {
+        redact::display_uri(&self.uri)
+    }
+
+    /// The encoding may be requested via the `encoding` query param, like:
+    /// `telnet://serenity.co:4000?encoding=latin1`
+    pub fn encoding_from_uri(uri: &Url) -> Option<Encoding> {
+        uri.query_pairs()
+            .find(|(key, _)| key == "encoding")
+            .and_then(|(_, value)| Encoding::from_name(&value))
+    }
+
+    /// A proxy may be requested via the `proxy` query param, like:
+    /// `telnet://serenity.co:4000?proxy=socks5://localhost:1080`, which
+    /// overrides any default proxy; `proxy=none` disables the default.
+    /// Returns None if the uri does not specify a proxy
+    pub fn proxy_from_uri(uri: &Url) -> Option<io::Result<Option<ProxyConfig>>> {
+        uri.query_pairs()
+            .find(|(key, _)| key == "proxy")
+            .map(|(_, value)| match value.as_ref() {
+                "" | "none" => Ok(None),
+                value => ProxyConfig::parse(value).map(Some),
+            })
+    }
+
+    /// Open a TcpStream to the given host and port, through the
+    /// appropriate proxy (if any)
+    pub fn connect_tcp(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let proxy = match Self::proxy_from_uri(&self.uri) {
+            Some(requested) => requested?,
+            None => self.proxy.clone(),
+        };
+
+        match proxy {
+            Some(proxy) => proxy.connect(host, port),
+            None => TcpStream::connect((host, port)),
         }
     }
 }
@@ -51,7 +166,12 @@ pub struct TransportFactories {
 impl Default for TransportFactories {
     fn default() -> Self {
         TransportFactories {
-            factories: vec![Box::new(TelnetConnectionFactory)],
+            factories: vec![
+                Box::new(TelnetConnectionFactory),
+                Box::new(TcpConnectionFactory),
+                Box::new(ExecConnectionFactory),
+                Box::new(ReplayConnectionFactory),
+            ],
         }
     }
 }
@@ -63,9 +183,9 @@ impl TransportFactories {
         }
     }
 
-    pub fn create(&self, params: ConnectParams) -> io::Result<Box<dyn Transport + Send>> {
+    pub fn create(&self, params: &ConnectParams) -> io::Result<Box<dyn Transport + Send>> {
         for f in &self.factories {
-            match f.create(&params) {
+            match f.create(params) {
                 None => {} // unsupported
                 Some(Ok(conn)) => return Ok(conn),
                 Some(Err(e)) => return Err(e),
@@ -73,7 +193,7 @@ impl TransportFactories {
         }
         Err(io::Error::new(
             io::ErrorKind::InvalidInput,
-            format!("{}: Unsupported uri", params.uri),
+            format!("{}: Unsupported uri", params.display_uri()),
         ))
     }
 }
@@ -0,0 +1,469 @@
+use tui::style::Color;
+
+/// MXP "line modes," as selected via `ESC [ n z`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MxpMode {
+    /// Only formatting tags are allowed
+    Open,
+
+    /// All tags are allowed, including links
+    Secure,
+
+    /// No tags are parsed; text is displayed as-is
+    Locked,
+}
+
+/// The target of a `<send>` link
+#[derive(Clone, Debug, PartialEq)]
+pub struct MxpLink {
+    pub command: String,
+    pub hint: Option<String>,
+
+    /// If true, the command should be placed in the input buffer
+    /// for editing instead of being sent immediately
+    pub prompt: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MxpTag {
+    pub name: String,
+    pub closing: bool,
+
+    /// Attributes in the order provided. MXP allows positional
+    /// attributes, which will have no key
+    pub attrs: Vec<(Option<String>, String)>,
+}
+
+impl MxpTag {
+    fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        if raw.starts_with('!') {
+            // Definitions like <!ELEMENT>; we don't support custom elements
+            return None;
+        }
+
+        let (closing, raw) = match raw.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, raw),
+        };
+        let raw = raw.trim_end_matches('/');
+
+        let mut tokens = tokenize(raw).into_iter();
+        let name = canonical_tag_name(&tokens.next()?.to_lowercase());
+        let attrs = tokens
+            .map(|token| match token.split_once('=') {
+                Some((key, value)) => (Some(key.to_lowercase()), unquote(value)),
+                None => (None, unquote(&token)),
+            })
+            .collect();
+
+        Some(Self {
+            name,
+            closing,
+            attrs,
+        })
+    }
+
+    /// Fetch an attribute by key, falling back to the positional
+    /// attribute at `index` (among those without a key)
+    pub fn attr(&self, key: &str, index: usize) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k.as_deref() == Some(key))
+            .or_else(|| self.attrs.iter().filter(|(k, _)| k.is_none()).nth(index))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.attrs
+            .iter()
+            .any(|(k, v)| k.is_none() && v.eq_ignore_ascii_case(flag))
+    }
+
+    /// Build the link target for a `<send>` tag, given the text it wrapped
+    pub fn to_link(&self, text: &str) -> MxpLink {
+        let href = self.attr("href", 0).unwrap_or(text);
+
+        // Multiple commands may be provided as a menu; we just use the first
+        let command = href.split('|').next().unwrap_or_default();
+        MxpLink {
+            command: command.replace("&text;", text),
+            hint: self.attr("hint", 1).map(|hint| hint.to_string()),
+            prompt: self.has_flag("prompt"),
+        }
+    }
+}
+
+fn canonical_tag_name(name: &str) -> String {
+    match name {
+        "bold" | "strong" => "b",
+        "italic" | "em" => "i",
+        "underline" => "u",
+        "strikeout" => "s",
+        "color" => "c",
+        "high" => "h",
+        _ => name,
+    }
+    .to_string()
+}
+
+fn tokenize(raw: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+
+    for ch in raw.chars() {
+        match quote {
+            Some(q) if ch == q => {
+                quote = None;
+                current.push(ch);
+            }
+            Some(_) => current.push(ch),
+            None if ch == '"' || ch == '\'' => {
+                quote = Some(ch);
+                current.push(ch);
+            }
+            None if ch.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            None => current.push(ch),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+fn unquote(value: &str) -> String {
+    let trimmed = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value);
+    decode_entities(trimmed)
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "amp" => Some('&'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let code = name.strip_prefix('#')?;
+            let value = match code.strip_prefix('x') {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(value)
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..]
+            .find(';')
+            .and_then(|end| decode_entity(&rest[1..end + 1]).map(|ch| (ch, end + 2)));
+        if let Some((ch, len)) = entity {
+            decoded.push(ch);
+            rest = &rest[len..];
+        } else {
+            // Not an entity we understand (or &text;); leave it as-is
+            decoded.push('&');
+            rest = &rest[1..];
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Parse an MXP color, which may be a name or an `#RRGGBB` hex code
+pub fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let rgb = u32::from_str_radix(hex, 16).ok()?;
+        return Some(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+    }
+
+    match value.to_lowercase().as_str() {
+        "black" => Some(Color::Black),
+        "red" | "maroon" => Some(Color::Red),
+        "green" => Some(Color::Green),
+        "yellow" | "olive" => Some(Color::Yellow),
+        "blue" | "navy" => Some(Color::Blue),
+        "magenta" | "purple" => Some(Color::Magenta),
+        "cyan" | "teal" => Some(Color::Cyan),
+        "silver" | "gray" | "grey" => Some(Color::Gray),
+        "white" => Some(Color::White),
+        "lime" => Some(Color::LightGreen),
+        "fuchsia" => Some(Color::LightMagenta),
+        "aqua" => Some(Color::LightCyan),
+        _ => None,
+    }
+}
+
+/// Entity names are short; anything longer is just text that happens to include a `&`
+const MAX_ENTITY_LENGTH: usize = 32;
+
+/// Likewise, a `<` without a `>` for this long is not a tag
+const MAX_TAG_LENGTH: usize = 1024;
+
+#[derive(Debug, PartialEq)]
+pub enum MxpToken {
+    Char(char),
+    Tag(MxpTag),
+
+    /// Text that looked like the start of a tag or entity, but wasn't one
+    Text(String),
+}
+
+enum PendingState {
+    Text,
+    Tag(String),
+    Entity(String),
+}
+
+/// Splits printable characters into text and MXP tags, according to
+/// the current line mode
+pub struct MxpParser {
+    default_mode: MxpMode,
+    line_mode: Option<MxpMode>,
+    pending: PendingState,
+}
+
+impl Default for MxpParser {
+    fn default() -> Self {
+        Self {
+            default_mode: MxpMode::Open,
+            line_mode: None,
+            pending: PendingState::Text,
+        }
+    }
+}
+
+impl MxpParser {
+    pub fn mode(&self) -> MxpMode {
+        self.line_mode.unwrap_or(self.default_mode)
+    }
+
+    /// Handle the `n` from an `ESC [ n z` sequence
+    pub fn set_mode(&mut self, n: u16) {
+        match n {
+            0 => self.line_mode = Some(MxpMode::Open),
+            1 | 4 => self.line_mode = Some(MxpMode::Secure),
+            2 => self.line_mode = Some(MxpMode::Locked),
+            3 => {
+                self.line_mode = None;
+                self.default_mode = MxpMode::Open;
+            }
+            5 => self.default_mode = MxpMode::Open,
+            6 => self.default_mode = MxpMode::Secure,
+            7 => self.default_mode = MxpMode::Locked,
+            _ => {} // Unsupported; ignore
+        }
+    }
+
+    /// Line modes only apply until the end of the line. Any unfinished tag or entity
+    /// is returned as text
+    pub fn on_newline(&mut self) -> Option<MxpToken> {
+        self.line_mode = None;
+        match std::mem::replace(&mut self.pending, PendingState::Text) {
+            PendingState::Text => None,
+            PendingState::Tag(tag) => Some(MxpToken::Text(format!("<{}", tag))),
+            PendingState::Entity(entity) => Some(MxpToken::Text(format!("&{}", entity))),
+        }
+    }
+
+    pub fn feed(&mut self, ch: char) -> Option<MxpToken> {
+        if self.mode() == MxpMode::Locked {
+            return Some(MxpToken::Char(ch));
+        }
+
+        match &mut self.pending {
+            PendingState::Text => match ch {
+                '<' => {
+                    self.pending = PendingState::Tag(String::new());
+                    None
+                }
+                '&' => {
+                    self.pending = PendingState::Entity(String::new());
+                    None
+                }
+                _ => Some(MxpToken::Char(ch)),
+            },
+
+            PendingState::Tag(tag) => {
+                if ch == '>' {
+                    let raw = std::mem::take(tag);
+                    self.pending = PendingState::Text;
+                    if raw.starts_with('!') {
+                        // Definitions like <!ELEMENT>; we don't support custom elements
+                        return None;
+                    }
+                    match MxpTag::parse(&raw) {
+                        Some(tag) => Some(MxpToken::Tag(tag)),
+                        None => Some(MxpToken::Text(format!("<{}>", raw))),
+                    }
+                } else if (tag.is_empty() && !is_tag_start(ch)) || tag.len() >= MAX_TAG_LENGTH {
+                    let raw = format!("<{}", tag);
+                    self.reject(raw, ch)
+                } else {
+                    tag.push(ch);
+                    None
+                }
+            }
+
+            PendingState::Entity(entity) => {
+                if ch == ';' && !entity.is_empty() {
+                    let raw = std::mem::take(entity);
+                    self.pending = PendingState::Text;
+                    match decode_entity(&raw.to_lowercase()) {
+                        Some(decoded) => Some(MxpToken::Char(decoded)),
+                        None => Some(MxpToken::Text(format!("&{};", raw))),
+                    }
+                } else if !(ch.is_ascii_alphanumeric() || ch == '#')
+                    || entity.len() >= MAX_ENTITY_LENGTH
+                {
+                    let raw = format!("&{}", entity);
+                    self.reject(raw, ch)
+                } else {
+                    entity.push(ch);
+                    None
+                }
+            }
+        }
+    }
+
+    /// Give up on a pending tag or entity, returning its `raw` text along with `ch`,
+    /// unless `ch` starts another one
+    fn reject(&mut self, mut raw: String, ch: char) -> Option<MxpToken> {
+        self.pending = PendingState::Text;
+        match ch {
+            '<' => self.pending = PendingState::Tag(String::new()),
+            '&' => self.pending = PendingState::Entity(String::new()),
+            _ => raw.push(ch),
+        }
+        Some(MxpToken::Text(raw))
+    }
+}
+
+fn is_tag_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '/' || ch == '!'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_str(parser: &mut MxpParser, text: &str) -> Vec<MxpToken> {
+        text.chars().filter_map(|ch| parser.feed(ch)).collect()
+    }
+
+    #[test]
+    fn parse_send_tag() {
+        let tag = MxpTag::parse(r#"send href="buy &text;" hint='Buy it' prompt"#).unwrap();
+        assert_eq!(tag.name, "send");
+        assert_eq!(
+            tag.to_link("sword"),
+            MxpLink {
+                command: "buy sword".to_string(),
+                hint: Some("Buy it".to_string()),
+                prompt: true,
+            }
+        );
+
+        let tag = MxpTag::parse(r#"SEND "look|examine""#).unwrap();
+        assert_eq!(tag.to_link("box").command, "look");
+    }
+
+    #[test]
+    fn strips_tags_and_entities() {
+        let mut parser = MxpParser::default();
+        let tokens = feed_str(&mut parser, "<B>a&lt;b</bold>");
+        assert_eq!(
+            tokens,
+            vec![
+                MxpToken::Tag(MxpTag {
+                    name: "b".to_string(),
+                    closing: false,
+                    attrs: vec![],
+                }),
+                MxpToken::Char('a'),
+                MxpToken::Char('<'),
+                MxpToken::Char('b'),
+                MxpToken::Tag(MxpTag {
+                    name: "b".to_string(),
+                    closing: true,
+                    attrs: vec![],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn locked_line_mode() {
+        let mut parser = MxpParser::default();
+        parser.set_mode(2);
+        assert_eq!(feed_str(&mut parser, "<b>").len(), 3);
+
+        parser.on_newline();
+        assert_eq!(parser.mode(), MxpMode::Open);
+        assert_eq!(feed_str(&mut parser, "<b>").len(), 1);
+    }
+
+    fn text(tokens: Vec<MxpToken>) -> String {
+        tokens
+            .into_iter()
+            .map(|token| match token {
+                MxpToken::Char(ch) => ch.to_string(),
+                MxpToken::Text(text) => text,
+                MxpToken::Tag(tag) => panic!("Unexpected tag: {:?}", tag),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bare_ampersand() {
+        let mut parser = MxpParser::default();
+        assert_eq!(text(feed_str(&mut parser, "a & b")), "a & b");
+        assert_eq!(
+            text(feed_str(&mut parser, "Tom &Jerry; &amp;")),
+            "Tom &Jerry; &"
+        );
+        assert_eq!(text(feed_str(&mut parser, "&&lt;")), "&<");
+    }
+
+    #[test]
+    fn bare_less_than() {
+        let mut parser = MxpParser::default();
+        assert_eq!(text(feed_str(&mut parser, "x < y")), "x < y");
+        assert_eq!(text(feed_str(&mut parser, "<3 <>")), "<3 <>");
+    }
+
+    #[test]
+    fn unterminated_at_end_of_line() {
+        let mut parser = MxpParser::default();
+        let mut tokens = feed_str(&mut parser, "hi &foo");
+        tokens.extend(parser.on_newline());
+        assert_eq!(text(tokens), "hi &foo");
+
+        let mut tokens = feed_str(&mut parser, "if a <b");
+        tokens.extend(parser.on_newline());
+        assert_eq!(text(tokens), "if a <b");
+    }
+}
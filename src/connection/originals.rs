@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use crate::editing::text::TextLine;
+
+#[derive(Default)]
+struct Originals {
+    /// Lines that were gagged entirely just before this one
+    gagged: Vec<TextLine>,
+
+    /// This line as it was received, if it was rewritten
+    received: Option<TextLine>,
+}
+
+/// Lines of a Connection's output Buffer as they were received, for any
+/// lines rewritten or gagged by the GameEngine. Windows that SHOW_GAGGED
+/// use these in place of what's actually in the Buffer.
+#[derive(Default)]
+pub struct OutputOriginals {
+    // Keyed by line index in the output buffer
+    lines: HashMap<usize, Originals>,
+}
+
+impl OutputOriginals {
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Remember that the given line was received as `original`
+    pub fn rewritten(&mut self, line: usize, original: TextLine) {
+        self.lines.entry(line).or_default().received = Some(original);
+    }
+
+    /// Remember that `original` was gagged just before the given line
+    pub fn gagged(&mut self, line: usize, original: TextLine) {
+        self.lines.entry(line).or_default().gagged.push(original);
+    }
+
+    /// The lines as received in place of the given line, which is
+    /// currently `shown` in the output buffer
+    pub fn received(&self, line: usize, shown: &TextLine) -> Vec<TextLine> {
+        match self.lines.get(&line) {
+            Some(originals) => {
+                let mut lines = originals.gagged.clone();
+                lines.push(originals.received.as_ref().unwrap_or(shown).clone());
+                lines
+            }
+            None => vec![shown.clone()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editing::text::EditableLine;
+
+    use super::*;
+
+    fn lines(lines: Vec<TextLine>) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn received_lines() {
+        let mut originals = OutputOriginals::default();
+        originals.gagged(1, "The weather is nice".into());
+        originals.gagged(1, "The weather is awful".into());
+        originals.rewritten(1, "Take my love".into());
+
+        assert_eq!(lines(originals.received(0, &"Shiny".into())), vec!["Shiny"]);
+        assert_eq!(
+            lines(originals.received(1, &"Take my land".into())),
+            vec![
+                "The weather is nice",
+                "The weather is awful",
+                "Take my love"
+            ]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+
+use super::{GmcpMessage, MsdpVariable};
+
+/// The most messages we'll hold onto; anything beyond this is almost
+/// certainly a runaway alias or script
+pub const MAX_PENDING: usize = 500;
+
+/// Allow at most `commands` text messages to be sent within any `per` window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub commands: usize,
+    pub per: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutgoingMessage {
+    Text {
+        text: String,
+
+        /// If true, this message was entered while the server had disabled
+        /// echo (IE: it's probably a password) so it must not be recorded
+        /// anywhere, nor processed by aliases
+        sensitive: bool,
+    },
+
+    /// Out-of-band data is queued so it stays in order with any text sent
+    /// before it, but does not count against the RateLimit
+    Gmcp(GmcpMessage),
+    Msdp(MsdpVariable),
+}
+
+impl OutgoingMessage {
+    fn is_limited(&self) -> bool {
+        matches!(self, OutgoingMessage::Text { .. })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Next {
+    Send(OutgoingMessage),
+    Wait(Duration),
+    Idle,
+    Closed,
+}
+
+#[derive(Default)]
+struct QueueState {
+    pending: VecDeque<OutgoingMessage>,
+    sent: VecDeque<Instant>,
+    limit: Option<RateLimit>,
+
+    /// The number of messages at the front of `pending` that should be
+    /// sent regardless of any RateLimit
+    flushing: usize,
+    closed: bool,
+}
+
+impl QueueState {
+    fn next_at(&mut self, now: Instant) -> Next {
+        if self.closed {
+            return Next::Closed;
+        }
+
+        let limited = match self.pending.front() {
+            Some(message) => message.is_limited(),
+            None => return Next::Idle,
+        };
+
+        if let Some(limit) = self.limit.filter(|_| limited) {
+            while let Some(sent) = self.sent.front() {
+                if now.duration_since(*sent) >= limit.per {
+                    self.sent.pop_front();
+                } else {
+                    break;
+                }
+            }
+
+            if self.flushing == 0 && self.sent.len() >= limit.commands {
+                let oldest = self.sent.front().cloned().unwrap_or(now);
+                return Next::Wait((oldest + limit.per).saturating_duration_since(now));
+            }
+
+            self.sent.push_back(now);
+        }
+
+        self.flushing = self.flushing.saturating_sub(1);
+        match self.pending.pop_front() {
+            Some(message) => Next::Send(message),
+            None => Next::Idle,
+        }
+    }
+}
+
+/// Messages waiting to be sent to a Connection. Messages are sent in
+/// order, as fast as the (optional) RateLimit allows
+#[derive(Clone, Default)]
+pub struct OutgoingQueue {
+    state: Arc<Mutex<QueueState>>,
+    notify: Arc<Notify>,
+}
+
+impl OutgoingQueue {
+    pub fn push(&self, message: OutgoingMessage) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(io::ErrorKind::NotConnected.into());
+        }
+        if state.pending.len() >= MAX_PENDING {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!(
+                    "Outgoing queue is full ({} pending); see :queue",
+                    MAX_PENDING
+                ),
+            ));
+        }
+
+        state.pending.push_back(message);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    /// Drop all pending messages, returning how many were dropped
+    pub fn clear(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let count = state.pending.len();
+        state.pending.clear();
+        state.flushing = 0;
+        count
+    }
+
+    /// Send all currently-pending messages immediately, ignoring any
+    /// RateLimit. Returns the number of messages to be flushed
+    pub fn flush(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.flushing = state.pending.len();
+        self.notify.notify_one();
+        state.flushing
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.state.lock().unwrap().limit
+    }
+
+    pub fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        self.state.lock().unwrap().limit = limit;
+        self.notify.notify_one();
+    }
+
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.notify.notify_one();
+    }
+
+    /// Deliver messages to `send` as they become ready, until closed
+    pub async fn pump<F: FnMut(OutgoingMessage)>(self, mut send: F) {
+        loop {
+            let next = self.state.lock().unwrap().next_at(Instant::now());
+            match next {
+                Next::Send(message) => send(message),
+                Next::Wait(duration) => {
+                    tokio::time::timeout(duration, self.notify.notified())
+                        .await
+                        .ok();
+                }
+                Next::Idle => self.notify.notified().await,
+                Next::Closed => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> OutgoingMessage {
+        OutgoingMessage::Text {
+            text: text.to_string(),
+            sensitive: false,
+        }
+    }
+
+    fn state_with(messages: &[&str], limit: Option<RateLimit>) -> QueueState {
+        QueueState {
+            pending: messages.iter().map(|m| message(m)).collect(),
+            limit,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unlimited() {
+        let mut state = state_with(&["take", "my", "love"], None);
+        let now = Instant::now();
+        assert_eq!(state.next_at(now), Next::Send(message("take")));
+        assert_eq!(state.next_at(now), Next::Send(message("my")));
+        assert_eq!(state.next_at(now), Next::Send(message("love")));
+        assert_eq!(state.next_at(now), Next::Idle);
+    }
+
+    #[test]
+    fn rate_limited() {
+        let limit = RateLimit {
+            commands: 2,
+            per: Duration::from_secs(1),
+        };
+        let mut state = state_with(&["take", "my", "love"], Some(limit));
+        let now = Instant::now();
+        assert_eq!(state.next_at(now), Next::Send(message("take")));
+        assert_eq!(
+            state.next_at(now + Duration::from_millis(500)),
+            Next::Send(message("my"))
+        );
+        assert_eq!(
+            state.next_at(now + Duration::from_millis(600)),
+            Next::Wait(Duration::from_millis(400))
+        );
+        assert_eq!(
+            state.next_at(now + Duration::from_secs(1)),
+            Next::Send(message("love"))
+        );
+    }
+
+    #[test]
+    fn flush_ignores_limit() {
+        let limit = RateLimit {
+            commands: 1,
+            per: Duration::from_secs(1),
+        };
+        let mut state = state_with(&["take", "my"], Some(limit));
+        let now = Instant::now();
+        assert_eq!(state.next_at(now), Next::Send(message("take")));
+        assert!(matches!(state.next_at(now), Next::Wait(_)));
+
+        state.flushing = 1;
+        assert_eq!(state.next_at(now), Next::Send(message("my")));
+        assert_eq!(state.next_at(now), Next::Idle);
+        assert_eq!(state.flushing, 0);
+    }
+
+    #[test]
+    fn flush_only_pending() {
+        let limit = RateLimit {
+            commands: 1,
+            per: Duration::from_secs(1),
+        };
+        let mut state = state_with(&["take", "my"], Some(limit));
+        let now = Instant::now();
+        assert_eq!(state.next_at(now), Next::Send(message("take")));
+
+        state.flushing = state.pending.len();
+        state.pending.push_back(message("love"));
+        assert_eq!(state.next_at(now), Next::Send(message("my")));
+        assert!(matches!(state.next_at(now), Next::Wait(_)));
+    }
+
+    #[test]
+    fn out_of_band_in_order() {
+        let limit = RateLimit {
+            commands: 1,
+            per: Duration::from_secs(1),
+        };
+        let gmcp = OutgoingMessage::Gmcp(GmcpMessage::new("Char.Items.Inv", None));
+        let mut state = state_with(&["take", "my"], Some(limit));
+        state.pending.push_back(gmcp.clone());
+        let now = Instant::now();
+        assert_eq!(state.next_at(now), Next::Send(message("take")));
+        assert!(matches!(state.next_at(now), Next::Wait(_)));
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(state.next_at(later), Next::Send(message("my")));
+        assert_eq!(state.next_at(later), Next::Send(gmcp));
+        assert_eq!(state.next_at(later), Next::Idle);
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    process::Child,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use super::{ansi::AnsiPipeline, transport::Transport, ReadValue, SessionRecorder, SharedEncoding};
+
+const BUFFER_SIZE: usize = 2048;
+
+/// The underlying resource for a RawTransport, which must be cleaned up
+/// when the Transport is dropped so the reader thread can stop
+pub enum RawSource {
+    Tcp(TcpStream),
+    Process(Child),
+}
+
+/// A Transport that feeds bytes directly into the AnsiPipeline, with no
+/// telnet interpretation whatsoever. Reads happen on a separate thread
+/// since not all sources (IE: process stdout) support read timeouts.
+pub struct RawTransport {
+    source: RawSource,
+    incoming: mpsc::Receiver<io::Result<Vec<u8>>>,
+    writer: Box<dyn Write + Send>,
+    line_ending: &'static [u8],
+    pipeline: AnsiPipeline,
+    encoding: SharedEncoding,
+}
+
+impl RawTransport {
+    pub fn spawn<R: Read + Send + 'static>(
+        source: RawSource,
+        reader: R,
+        writer: Box<dyn Write + Send>,
+        line_ending: &'static [u8],
+        encoding: SharedEncoding,
+        recorder: SessionRecorder,
+    ) -> Self {
+        let (tx, incoming) = mpsc::channel();
+        thread::spawn(move || read_into(reader, tx, recorder));
+
+        Self {
+            source,
+            incoming,
+            writer,
+            line_ending,
+            pipeline: AnsiPipeline::new(),
+            encoding,
+        }
+    }
+}
+
+fn read_into<R: Read>(
+    mut reader: R,
+    tx: mpsc::Sender<io::Result<Vec<u8>>>,
+    recorder: SessionRecorder,
+) {
+    let mut buf = [0u8; BUFFER_SIZE];
+    loop {
+        let result = match reader.read(&mut buf) {
+            Ok(0) => return, // EOF; dropping the Sender signals this
+            Ok(n) => {
+                recorder.record(&buf[..n]);
+                Ok(buf[..n].to_vec())
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => Err(e),
+        };
+
+        let failed = result.is_err();
+        if tx.send(result).is_err() || failed {
+            // Either the Transport was dropped, or we can't read anymore
+            return;
+        }
+    }
+}
+
+impl Transport for RawTransport {
+    fn read_timeout(&mut self, duration: Duration) -> io::Result<Option<ReadValue>> {
+        if let Some(pending) = self.pipeline.next() {
+            return Ok(Some(pending));
+        }
+
+        match self.incoming.recv_timeout(duration) {
+            Ok(Ok(data)) => {
+                let data = self.encoding.get().decode(&data);
+                self.pipeline.feed(&data, data.len());
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Connection closed",
+                ));
+            }
+        }
+
+        Ok(self.pipeline.next())
+    }
+
+    fn send(&mut self, text: &str) -> io::Result<()> {
+        self.writer.write_all(&self.encoding.get().encode(text))?;
+        self.writer.write_all(self.line_ending)?;
+        self.writer.flush()
+    }
+}
+
+impl Drop for RawTransport {
+    fn drop(&mut self) {
+        match &mut self.source {
+            RawSource::Tcp(stream) => {
+                stream.shutdown(Shutdown::Both).ok();
+            }
+            RawSource::Process(child) => {
+                child.kill().ok();
+                child.wait().ok();
+            }
+        }
+    }
+}
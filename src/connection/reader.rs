@@ -2,9 +2,19 @@ use std::{thread::yield_now, time::Duration};
 
 use tokio::sync::oneshot::{self, error::TryRecvError, Sender};
 
-use crate::{app::jobs::JobContext, editing::Id};
-
-use super::transport::Transport;
+use crate::{
+    app::{self, jobs::JobContext},
+    editing::{
+        text::{EditableLine, TextLine},
+        Id,
+    },
+};
+
+use super::{
+    links::{self, LinkTarget, OutputLink},
+    transport::Transport,
+    ReadValue,
+};
 
 pub struct StopSignal {
     tx: Option<Sender<()>>,
@@ -46,11 +56,15 @@ impl<T: Transport + Send + 'static> TransportReader<T> {
                 buffer_id,
                 transport,
             };
-            reader.loop_until(rx);
+            let dropped = reader.loop_until(rx);
             reader
                 .ctx
                 .spawn(move |ctx| {
-                    ctx.connections.disconnect(id).ok();
+                    if dropped {
+                        ctx.connections.on_dropped(&mut ctx.jobs, id);
+                    } else {
+                        ctx.connections.disconnect(id).ok();
+                    }
                 })
                 .join()
                 .ok();
@@ -59,15 +73,17 @@ impl<T: Transport + Send + 'static> TransportReader<T> {
         signal
     }
 
-    pub fn loop_until(&mut self, mut signal: oneshot::Receiver<()>) {
+    /// Returns true if we stopped because the connection was dropped,
+    /// rather than due to the signal
+    pub fn loop_until(&mut self, mut signal: oneshot::Receiver<()>) -> bool {
         loop {
             match signal.try_recv() {
                 Err(TryRecvError::Empty) => {} // Nop
-                _ => break,                    // Any other message, we should drop the connection
+                _ => return false,             // Any other message, we should drop the connection
             }
 
             if !self.read_once() {
-                break;
+                return true;
             }
 
             yield_now();
@@ -87,9 +103,73 @@ impl<T: Transport + Send + 'static> TransportReader<T> {
 
         let buffer_id = self.buffer_id;
         self.ctx.run(move |state| {
+            let read = match read {
+                Ok(Some(ReadValue::Rewritten(original, rewritten))) => {
+                    Ok(Some(rewrite_line(state, buffer_id, original, rewritten)))
+                }
+                read => read,
+            };
+
+            if let Ok(Some(ReadValue::Mssp(data))) = read {
+                if let Some(conn) = state.connections.by_buffer_id(buffer_id) {
+                    conn.mssp = Some(data);
+                }
+                return;
+            }
+
+            if let Ok(Some(ReadValue::Title(title))) = read {
+                if let Some(conn) = state.connections.by_buffer_id(buffer_id) {
+                    conn.title = Some(title);
+                }
+                return;
+            }
+
+            if let Ok(Some(ReadValue::Bell)) = read {
+                state.ring_bell();
+                return;
+            }
+
+            if let Ok(Some(ReadValue::ReplaceLine(_))) = read {
+                let buffer = state.buffers.by_id(buffer_id);
+                let line = buffer.map(|buffer| buffer.lines_count().saturating_sub(1));
+                let conn = state.connections.by_buffer_id(buffer_id);
+                if let (Some(conn), Some(line)) = (conn, line) {
+                    conn.links.remove_line(line);
+                }
+            }
+
+            if let Ok(Some(ReadValue::Newline)) = read {
+                mark_urls(state, buffer_id);
+            }
+
+            let buffer_id = match &read {
+                Ok(Some(ReadValue::Prompt(_))) => state
+                    .buffers
+                    .prompt_buffer_for(buffer_id)
+                    .unwrap_or(buffer_id),
+                _ => buffer_id,
+            };
             let mut buffer = state
                 .winsbuf_by_id(buffer_id)
                 .expect("Could not find buffer for connection");
+
+            if let Ok(Some(ReadValue::Link(text, link))) = read {
+                let line = buffer.buffer.lines_count().saturating_sub(1);
+                let chars = |line: &TextLine| line.to_string().chars().count();
+                let start = buffer.buffer.checked_get(line).map(chars).unwrap_or(0);
+                let output_link = OutputLink {
+                    line,
+                    cols: start..start + chars(&text),
+                    link: link.clone(),
+                };
+                buffer.append_value(ReadValue::Link(text, link));
+
+                if let Some(conn) = state.connections.by_buffer_id(buffer_id) {
+                    conn.links.push(output_link);
+                }
+                return;
+            }
+
             match read {
                 Ok(Some(value)) => buffer.append_value(value),
                 Ok(None) => (), // nop
@@ -100,3 +180,75 @@ impl<T: Transport + Send + 'static> TransportReader<T> {
         return result;
     }
 }
+
+/// Replace the last line of the given connection output buffer with the
+/// `rewritten` version of what was received (or nothing, if gagged), keeping
+/// the `original` for any windows that SHOW_GAGGED. Returns the value that
+/// should be appended to finish the line
+fn rewrite_line(
+    state: &mut app::State,
+    buffer_id: Id,
+    original: TextLine,
+    rewritten: Option<TextLine>,
+) -> ReadValue {
+    let line = match state.buffers.by_id(buffer_id) {
+        Some(buffer) => buffer.lines_count().saturating_sub(1),
+        None => return ReadValue::Newline,
+    };
+
+    if let Some(conn) = state.connections.by_buffer_id(buffer_id) {
+        conn.links.remove_line(line);
+        if rewritten.is_some() {
+            conn.originals.rewritten(line, original);
+        } else {
+            conn.originals.gagged(line, original);
+        }
+    }
+
+    match rewritten {
+        Some(rewritten) => {
+            if let Some(buffer) = state.buffers.by_id_mut(buffer_id) {
+                buffer.append_value(ReadValue::ReplaceLine(rewritten));
+            }
+            ReadValue::Newline
+        }
+
+        // The next line will be received in place of the gagged one:
+        None => ReadValue::ReplaceLine(TextLine::default()),
+    }
+}
+
+/// Detect any bare URLs on the last (now complete) line of the given
+/// connection output buffer, marking them as links
+fn mark_urls(state: &mut app::State, buffer_id: Id) {
+    let (line_index, line) = match state.buffers.by_id(buffer_id) {
+        Some(buffer) if buffer.lines_count() > 0 => {
+            let line_index = buffer.lines_count() - 1;
+            (line_index, buffer.get(line_index).clone())
+        }
+        _ => return,
+    };
+
+    let conn = match state.connections.by_buffer_id(buffer_id) {
+        Some(conn) => conn,
+        None => return,
+    };
+
+    let mut ranges = vec![];
+    for (cols, url) in links::find_urls(&line.to_string()) {
+        if !conn.links.overlaps(line_index, &cols) {
+            ranges.push(cols.clone());
+            conn.links.push(OutputLink {
+                line: line_index,
+                cols,
+                link: LinkTarget::Url(url),
+            });
+        }
+    }
+
+    if !ranges.is_empty() {
+        if let Some(buffer) = state.buffers.by_id_mut(buffer_id) {
+            buffer.append_value(ReadValue::ReplaceLine(links::underline(line, &ranges)));
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+const DEFAULT_DELAY: Duration = Duration::from_secs(2);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60);
+const DEFAULT_MAX_ATTEMPTS: usize = 5;
+
+/// Describes how (and whether) to automatically reconnect when a
+/// connection is dropped by the server
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReconnectPolicy {
+    /// The delay before the first attempt; this is doubled for each
+    /// subsequent attempt, up to `max_delay` (or `delay` itself, if longer)
+    pub delay: Duration,
+    pub max_delay: Duration,
+    pub max_attempts: usize,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// The delay to wait before the given (1-based) attempt
+    pub fn delay_for_attempt(&self, attempt: usize) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31) as u32;
+        let max_delay = self.max_delay.max(self.delay);
+        self.delay
+            .checked_mul(2u32.saturating_pow(exponent))
+            .unwrap_or(max_delay)
+            .min(max_delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = ReconnectPolicy {
+            delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(10),
+            max_attempts: 5,
+        };
+        assert_eq!(policy.delay_for_attempt(1), Duration::from_secs(2));
+        assert_eq!(policy.delay_for_attempt(2), Duration::from_secs(4));
+        assert_eq!(policy.delay_for_attempt(3), Duration::from_secs(8));
+        assert_eq!(policy.delay_for_attempt(4), Duration::from_secs(10));
+        assert_eq!(policy.delay_for_attempt(100), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn delay_beyond_max() {
+        let policy = ReconnectPolicy {
+            delay: Duration::from_secs(120),
+            ..Default::default()
+        };
+        assert_eq!(policy.delay_for_attempt(1), Duration::from_secs(120));
+        assert_eq!(policy.delay_for_attempt(3), Duration::from_secs(120));
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const HEADER: &str = "iaido-recording 1";
+
+/// A chunk of bytes received some time after the recording started
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedChunk {
+    pub elapsed: Duration,
+    pub bytes: Vec<u8>,
+}
+
+struct Recording {
+    writer: Box<dyn Write + Send>,
+    start: Instant,
+}
+
+/// Records the bytes received by a Connection (after any MCCP decompression), with timestamps. Each
+/// chunk is written as a `<elapsed millis> <length>` line, followed by
+/// the bytes themselves and a newline.
+#[derive(Clone, Default)]
+pub struct SessionRecorder(Arc<Mutex<Option<Recording>>>);
+
+impl SessionRecorder {
+    pub fn start(&mut self, path: &Path) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        self.start_with(Box::new(file))
+    }
+
+    fn start_with(&mut self, mut writer: Box<dyn Write + Send>) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        *self.0.lock().unwrap() = Some(Recording {
+            writer,
+            start: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Returns true if we were recording
+    pub fn stop(&mut self) -> bool {
+        if let Some(mut recording) = self.0.lock().unwrap().take() {
+            recording.writer.flush().ok();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.0.lock().unwrap().is_some()
+    }
+
+    pub fn record(&self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        let mut lock = self.0.lock().unwrap();
+        if let Some(recording) = lock.as_mut() {
+            let elapsed = recording.start.elapsed();
+            if let Err(e) = write_chunk(&mut recording.writer, elapsed, bytes) {
+                crate::log_error!("Failed to record session: {}", e);
+                lock.take();
+            }
+        }
+    }
+}
+
+fn write_chunk<W: Write>(writer: &mut W, elapsed: Duration, bytes: &[u8]) -> io::Result<()> {
+    writeln!(writer, "{} {}", elapsed.as_millis(), bytes.len())?;
+    writer.write_all(bytes)?;
+    writeln!(writer)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub fn parse_recording<R: BufRead>(mut reader: R) -> io::Result<Vec<RecordedChunk>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim_end() != HEADER {
+        return Err(invalid("Not a session recording"));
+    }
+
+    let mut chunks = vec![];
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+
+        let mut parts = line.split_whitespace().map(|part| part.parse::<u64>());
+        let (elapsed, length) = match (parts.next(), parts.next()) {
+            (Some(Ok(elapsed)), Some(Ok(length))) => (elapsed, length),
+            _ => return Err(invalid("Malformed chunk header")),
+        };
+
+        let mut bytes = vec![0; length as usize];
+        reader.read_exact(&mut bytes)?;
+
+        let mut newline = [0u8];
+        reader.read_exact(&mut newline)?;
+
+        chunks.push(RecordedChunk {
+            elapsed: Duration::from_millis(elapsed),
+            bytes,
+        });
+    }
+
+    Ok(chunks)
+}
+
+pub fn read_recording(path: &Path) -> io::Result<Vec<RecordedChunk>> {
+    parse_recording(BufReader::new(File::open(path)?))
+}
+
+/// Wraps a telnet Stream to record everything read from it
+pub struct RecordingStream {
+    stream: Box<dyn telnet::Stream>,
+    recorder: SessionRecorder,
+}
+
+impl RecordingStream {
+    pub fn new(stream: Box<dyn telnet::Stream>, recorder: SessionRecorder) -> Self {
+        Self { stream, recorder }
+    }
+}
+
+impl Read for RecordingStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.stream.read(buf)?;
+        self.recorder.record(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl Write for RecordingStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl telnet::Stream for RecordingStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.stream.set_nonblocking(nonblocking)
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(dur)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut recorder = SessionRecorder::default();
+        recorder
+            .start_with(Box::new(SharedWriter(output.clone())))
+            .unwrap();
+        recorder.record(b"Take my love\r\n");
+        recorder.record(b"\xff\xf9\n");
+        assert!(recorder.stop());
+        recorder.record(b"Ignored");
+
+        let bytes = output.lock().unwrap().clone();
+        let chunks = parse_recording(&bytes[..]).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].bytes, b"Take my love\r\n");
+        assert_eq!(chunks[1].bytes, b"\xff\xf9\n");
+        assert!(chunks[0].elapsed <= chunks[1].elapsed);
+    }
+
+    #[test]
+    fn reject_non_recordings() {
+        assert!(parse_recording(&b"Take my love"[..]).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::editing::{window::Window, window::WindowFlags, Size};
+
+/// How to pick the size reported to the server (via NAWS) for a Connection
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NawsSizing {
+    /// The whole terminal
+    Terminal,
+
+    /// The connection's main output Window
+    Main,
+
+    /// The smallest width and height among all Windows showing the
+    /// connection's output, so text fits in each of them
+    Smallest,
+
+    /// The focused Window showing the connection's output, if any;
+    /// otherwise, the main output Window
+    Focused,
+}
+
+impl Default for NawsSizing {
+    fn default() -> Self {
+        NawsSizing::Main
+    }
+}
+
+impl NawsSizing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "terminal" => Some(NawsSizing::Terminal),
+            "main" => Some(NawsSizing::Main),
+            "smallest" => Some(NawsSizing::Smallest),
+            "focused" => Some(NawsSizing::Focused),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NawsSizing::Terminal => "terminal",
+            NawsSizing::Main => "main",
+            NawsSizing::Smallest => "smallest",
+            NawsSizing::Focused => "focused",
+        }
+    }
+
+    /// Pick the size to report, given the Windows showing a connection's
+    /// output. Returns None if there are no such windows (and we're not
+    /// using the terminal size)
+    pub fn pick<'a>(
+        &self,
+        terminal: Size,
+        windows: impl Iterator<Item = &'a Window>,
+    ) -> Option<Size> {
+        if *self == NawsSizing::Terminal {
+            return Some(terminal);
+        }
+
+        let windows: Vec<&Window> = windows.collect();
+        let main = windows
+            .iter()
+            .find(|win| win.flags.contains(WindowFlags::PROTECTED))
+            .or_else(|| windows.first());
+
+        match self {
+            NawsSizing::Terminal => Some(terminal),
+            NawsSizing::Main => main.map(|win| win.size),
+            NawsSizing::Smallest => windows.iter().map(|win| win.size).reduce(|a, b| Size {
+                w: a.w.min(b.w),
+                h: a.h.min(b.h),
+            }),
+            NawsSizing::Focused => windows
+                .iter()
+                .find(|win| win.focused)
+                .or(main)
+                .map(|win| win.size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(id: usize, size: Size, main: bool, focused: bool) -> Window {
+        let mut window = Window::with_focused(id, 0, focused);
+        window.size = size;
+        if main {
+            window.flags = WindowFlags::PROTECTED;
+        }
+        window
+    }
+
+    #[test]
+    fn pick_sizes() {
+        let terminal = Size { w: 120, h: 40 };
+        let windows = [
+            window(0, Size { w: 60, h: 10 }, false, true),
+            window(1, Size { w: 80, h: 30 }, true, false),
+            window(2, Size { w: 100, h: 5 }, false, false),
+        ];
+
+        assert_eq!(
+            NawsSizing::Terminal.pick(terminal, windows.iter()),
+            Some(terminal)
+        );
+        assert_eq!(
+            NawsSizing::Main.pick(terminal, windows.iter()),
+            Some(Size { w: 80, h: 30 })
+        );
+        assert_eq!(
+            NawsSizing::Smallest.pick(terminal, windows.iter()),
+            Some(Size { w: 60, h: 5 })
+        );
+        assert_eq!(
+            NawsSizing::Focused.pick(terminal, windows.iter()),
+            Some(Size { w: 60, h: 10 })
+        );
+        assert_eq!(
+            NawsSizing::Focused.pick(terminal, windows[1..].iter()),
+            Some(Size { w: 80, h: 30 })
+        );
+        assert_eq!(NawsSizing::Main.pick(terminal, std::iter::empty()), None);
+    }
+}
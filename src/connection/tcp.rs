@@ -0,0 +1,81 @@
+use std::io;
+
+use super::{
+    raw::{RawSource, RawTransport},
+    transport::Transport,
+    ConnectParams, TransportFactory,
+};
+
+fn connect(host: &str, port: u16, params: &ConnectParams) -> io::Result<RawTransport> {
+    let stream = params.connect_tcp(host, port)?;
+    let reader = stream.try_clone()?;
+    let writer = stream.try_clone()?;
+    Ok(RawTransport::spawn(
+        RawSource::Tcp(stream),
+        reader,
+        Box::new(writer),
+        b"\r\n",
+        params.encoding.clone(),
+        params.recorder.clone(),
+    ))
+}
+
+/// Plain TCP connections, via `tcp://host:port`, with no telnet interpretation
+pub struct TcpConnectionFactory;
+impl TransportFactory for TcpConnectionFactory {
+    fn clone_boxed(&self) -> Box<dyn TransportFactory> {
+        Box::new(TcpConnectionFactory)
+    }
+
+    fn create(&self, params: &ConnectParams) -> Option<io::Result<Box<dyn Transport + Send>>> {
+        if params.uri.scheme() != "tcp" {
+            return None;
+        }
+
+        match (params.uri.host_str(), params.uri.port()) {
+            (Some(host), Some(port)) => Some(
+                connect(host, port, params).map(|conn| Box::new(conn) as Box<dyn Transport + Send>),
+            ),
+
+            _ => Some(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: invalid tcp uri", params.display_uri()),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, net::TcpListener, thread, time::Duration};
+
+    use url::Url;
+
+    use crate::connection::ReadValue;
+
+    use super::*;
+
+    #[test]
+    fn read_until_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"Take my love\nTake").unwrap();
+        });
+
+        let uri = Url::parse("tcp://127.0.0.1").unwrap();
+        let params = ConnectParams::with_uri_and_size(uri, Default::default());
+        let mut transport = connect("127.0.0.1", port, &params).unwrap();
+        server.join().unwrap();
+
+        let mut read = vec![];
+        while let Ok(value) = transport.read_timeout(Duration::from_millis(100)) {
+            read.extend(value);
+        }
+
+        assert_eq!(read.len(), 3);
+        assert_eq!(read[1], ReadValue::Newline);
+        assert_eq!(read[2], ReadValue::Text("Take".into()));
+    }
+}
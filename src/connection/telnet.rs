@@ -1,29 +1,46 @@
-use native_tls::TlsConnector;
 use std::io;
-use std::net::TcpStream;
 
+mod charset;
 mod echo;
+pub mod environ;
+mod eor;
+pub mod gmcp;
 mod handler;
 mod handlers;
+mod mccp;
+pub mod msdp;
+pub mod mssp;
+mod mxp;
 mod naws;
+mod replay;
 mod ttype;
 
 use telnet::{Telnet, TelnetOption};
 
+use self::gmcp::GmcpMessage;
 use self::handler::{TelnetHandler, TelnetOptionInteractor};
 use self::handlers::TelnetHandlers;
+use self::mccp::MccpStream;
+use self::msdp::MsdpVariable;
 
-use super::ConnectParams;
-use super::{ansi::AnsiPipeline, tls, transport::Transport, ReadValue, TransportFactory};
+use super::tls::{self, TlsOptions};
+use super::{ansi::AnsiPipeline, transport::Transport, ReadValue, TransportFactory};
+use super::{recording::RecordingStream, ConnectParams, SharedEncoding};
 
 const BUFFER_SIZE: usize = 2048;
 
+const IAC_EOR: u8 = 239;
+const IAC_GA: u8 = 249;
+
+pub use self::replay::ReplayConnectionFactory;
+
 struct TelnetWrapper(Telnet);
 
 pub struct TelnetConnection {
     telnet: TelnetWrapper,
     handlers: TelnetHandlers,
     pipeline: AnsiPipeline,
+    encoding: SharedEncoding,
 }
 
 /// NOTE: this `unsafe` is probably a terrible idea, but *should* be
@@ -34,11 +51,34 @@ pub struct TelnetConnection {
 unsafe impl Send for TelnetWrapper {}
 
 impl TelnetConnection {
+    fn from_stream(stream: Box<dyn telnet::Stream>, params: &ConnectParams) -> Self {
+        let stream = MccpStream::new(stream, params.flags.clone());
+        Self::from_decompressed_stream(Box::new(stream), params)
+    }
+
+    /// Like [Self::from_stream], but for a stream that never uses MCCP compression,
+    /// like a recording (which is always recorded after decompression)
+    fn from_decompressed_stream(stream: Box<dyn telnet::Stream>, params: &ConnectParams) -> Self {
+        // NOTE: We record the decompressed stream so a recording started after
+        // compression began can still be replayed
+        let stream = RecordingStream::new(stream, params.recorder.clone());
+        Self {
+            telnet: TelnetWrapper(Telnet::from_stream(Box::new(stream), BUFFER_SIZE)),
+            handlers: TelnetHandlers::with_params(params),
+            pipeline: AnsiPipeline::new(),
+            encoding: params.encoding.clone(),
+        }
+    }
+
     fn process_event(&mut self, event: telnet::Event) -> io::Result<Option<ReadValue>> {
         match event {
             telnet::Event::Data(data) => {
+                let data = self.encoding.get().decode(&data);
                 self.pipeline.feed(&data, data.len());
             }
+            telnet::Event::UnknownIAC(IAC_GA) | telnet::Event::UnknownIAC(IAC_EOR) => {
+                self.pipeline.end_prompt();
+            }
             telnet::Event::UnknownIAC(_) => {}
 
             telnet::Event::Negotiation(action, option) => {
@@ -48,6 +88,16 @@ impl TelnetConnection {
                 } else {
                     TelnetOptionInteractor::default(option).negotiate(&action, &mut self.telnet.0)
                 }?;
+
+                if option.as_byte() == eor::OPTION_EOR && matches!(action, telnet::Action::Will) {
+                    // The server has promised to mark prompts, so we can
+                    // wait for it instead of rendering partial lines
+                    self.pipeline.expect_prompts();
+                }
+
+                if option.as_byte() == mxp::OPTION_MXP && matches!(action, telnet::Action::Will) {
+                    self.pipeline.enable_mxp();
+                }
             }
 
             telnet::Event::Subnegotiation(option, bytes) => {
@@ -63,6 +113,10 @@ impl TelnetConnection {
                     if let Err(e) = result {
                         return Err(io::Error::new(io::ErrorKind::Other, e));
                     }
+
+                    if let Some(value) = handler.take_value() {
+                        return Ok(Some(value));
+                    }
                 }
             }
 
@@ -93,26 +147,61 @@ impl Transport for TelnetConnection {
         }
 
         let event = self.telnet.0.read_timeout(duration)?;
-        self.process_event(event)
+        let value = self.process_event(event)?;
+        self.handlers.poll(&mut self.telnet.0)?;
+        Ok(value)
     }
 
     fn send(&mut self, text: &str) -> io::Result<()> {
-        self.telnet.0.write(text.as_bytes())?;
+        self.telnet.0.write(&self.encoding.get().encode(text))?;
         self.telnet.0.write(b"\r\n")?;
         Ok(())
     }
+
+    fn send_gmcp(&mut self, message: GmcpMessage) -> io::Result<()> {
+        let option = TelnetOption::UnknownOption(gmcp::OPTION_GMCP);
+        match self.handlers.get_mut(&option) {
+            Some(handler) if handler.interactor.is_remote_enabled() => {
+                if let Err(e) = gmcp::send(&mut self.telnet.0, &message) {
+                    return Err(io::Error::new(io::ErrorKind::Other, e));
+                }
+                Ok(())
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "GMCP is not enabled for this connection",
+            )),
+        }
+    }
+
+    fn send_msdp(&mut self, variable: MsdpVariable) -> io::Result<()> {
+        let option = TelnetOption::UnknownOption(msdp::OPTION_MSDP);
+        match self.handlers.get_mut(&option) {
+            Some(handler) if handler.interactor.is_remote_enabled() => {
+                if let Err(e) = msdp::send(&mut self.telnet.0, &variable) {
+                    return Err(io::Error::new(io::ErrorKind::Other, e));
+                }
+                Ok(())
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "MSDP is not enabled for this connection",
+            )),
+        }
+    }
 }
 
-fn connect(host: &str, port: u16, secure: bool, buffer_size: usize) -> io::Result<Telnet> {
-    let tcp = TcpStream::connect((host, port))?;
-    if !secure {
-        Ok(Telnet::from_stream(Box::new(tcp), buffer_size))
+fn connect(
+    params: &ConnectParams,
+    host: &str,
+    port: u16,
+    tls_options: Option<TlsOptions>,
+) -> io::Result<Box<dyn telnet::Stream>> {
+    let tcp = params.connect_tcp(host, port)?;
+    if let Some(options) = tls_options {
+        tls::connect(host, port, tcp, &options)
     } else {
-        let connector = TlsConnector::new().expect("Failed to initialize TLS");
-        match connector.connect(host, tcp) {
-            Ok(raw_tls) => Ok(tls::create_telnet(raw_tls, buffer_size)),
-            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
-        }
+        Ok(Box::new(tcp))
     }
 }
 
@@ -123,25 +212,24 @@ impl TransportFactory for TelnetConnectionFactory {
     }
 
     fn create(&self, params: &ConnectParams) -> Option<std::io::Result<Box<dyn Transport + Send>>> {
-        let secure = match params.uri.scheme() {
-            "telnet" => false,
-            "ssl" => true,
+        let tls_options = match params.uri.scheme() {
+            "telnet" => None,
+            "ssl" => match TlsOptions::from_uri(&params.uri) {
+                Ok(options) => Some(options),
+                Err(e) => return Some(Err(e)),
+            },
             _ => return None,
         };
 
         match (params.uri.host_str(), params.uri.port()) {
-            (Some(host), Some(port)) => match connect(host, port, secure, BUFFER_SIZE) {
-                Ok(conn) => Some(Ok(Box::new(TelnetConnection {
-                    telnet: TelnetWrapper(conn),
-                    handlers: TelnetHandlers::with_params(params),
-                    pipeline: AnsiPipeline::new(),
-                }))),
+            (Some(host), Some(port)) => match connect(params, host, port, tls_options) {
+                Ok(stream) => Some(Ok(Box::new(TelnetConnection::from_stream(stream, params)))),
                 Err(e) => Some(Err(e)),
             },
 
             _ => Some(Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
-                format!("{}: invalid telnet uri", params.uri),
+                format!("{}: invalid telnet uri", params.display_uri()),
             ))),
         }
     }
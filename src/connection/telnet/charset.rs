@@ -0,0 +1,132 @@
+use telnet::{self, TelnetError, TelnetOption};
+
+use crate::connection::encoding::{Encoding, SharedEncoding};
+
+use super::handler::{TelnetHandler, TelnetOptionHandler, TelnetOptionInteractor};
+
+pub const OPTION_CHARSET: u8 = 42;
+
+const CHARSET_REQUEST: u8 = 1;
+const CHARSET_ACCEPTED: u8 = 2;
+const CHARSET_REJECTED: u8 = 3;
+
+const TTABLE_PREFIX: &[u8] = b"[TTABLE]";
+
+pub struct CharsetHandler {
+    encoding: SharedEncoding,
+}
+
+impl CharsetHandler {
+    /// Pick the best of the offered charsets, preferring whatever we're already
+    /// using (which may have been explicitly requested by the user), then UTF-8
+    fn choose<'a>(&self, offered: &[&'a str]) -> Option<(&'a str, Encoding)> {
+        let supported: Vec<(&str, Encoding)> = offered
+            .iter()
+            .filter_map(|name| Encoding::from_name(name).map(|encoding| (*name, encoding)))
+            .collect();
+
+        let current = self.encoding.get();
+        supported
+            .iter()
+            .find(|(_, encoding)| *encoding == current)
+            .or_else(|| {
+                supported
+                    .iter()
+                    .find(|(_, encoding)| *encoding == Encoding::Utf8)
+            })
+            .or_else(|| supported.first())
+            .cloned()
+    }
+}
+
+fn parse_request(bytes: &[u8]) -> Vec<String> {
+    let mut bytes = bytes;
+    if bytes.starts_with(TTABLE_PREFIX) {
+        // Skip the prefix and the version byte
+        bytes = bytes.get(TTABLE_PREFIX.len() + 1..).unwrap_or_default();
+    }
+
+    if let Some((separator, names)) = bytes.split_first() {
+        names
+            .split(|b| b == separator)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+impl TelnetHandler for CharsetHandler {
+    fn on_subnegotiate(
+        &mut self,
+        telnet: &mut telnet::Telnet,
+        bytes: &[u8],
+    ) -> Result<(), TelnetError> {
+        let request = match bytes.split_first() {
+            Some((&CHARSET_REQUEST, request)) => request,
+
+            // We never send requests, so we don't expect any other messages
+            _ => return Ok(()),
+        };
+
+        let offered = parse_request(request);
+        let offered: Vec<&str> = offered.iter().map(|s| s.as_str()).collect();
+        crate::info!("## TELNET < CHARSET REQUEST {:?}", offered);
+
+        let option = TelnetOption::UnknownOption(OPTION_CHARSET);
+        if let Some((name, encoding)) = self.choose(&offered) {
+            crate::info!("## TELNET > CHARSET ACCEPTED {}", name);
+            self.encoding.set(encoding);
+            let response = [&[CHARSET_ACCEPTED], name.as_bytes()].concat();
+            telnet.subnegotiate(option, &response)
+        } else {
+            crate::info!("## TELNET > CHARSET REJECTED");
+            telnet.subnegotiate(option, &[CHARSET_REJECTED])
+        }
+    }
+}
+
+pub fn create(encoding: SharedEncoding) -> TelnetOptionHandler {
+    TelnetOptionHandler {
+        interactor: TelnetOptionInteractor::accept_do(TelnetOption::UnknownOption(OPTION_CHARSET)),
+        handler: Box::new(CharsetHandler { encoding }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_simple_request() {
+        assert_eq!(
+            parse_request(b";UTF-8;ISO-8859-1"),
+            vec!["UTF-8", "ISO-8859-1"]
+        );
+    }
+
+    #[test]
+    fn parse_ttable_request() {
+        assert_eq!(
+            parse_request(b"[TTABLE]\x01 UTF-8 CP437"),
+            vec!["UTF-8", "CP437"]
+        );
+    }
+
+    #[test]
+    fn prefer_current_encoding() {
+        let handler = CharsetHandler {
+            encoding: SharedEncoding::new(Encoding::Cp437),
+        };
+        assert_eq!(
+            handler.choose(&["UTF-8", "cp437"]),
+            Some(("cp437", Encoding::Cp437))
+        );
+        assert_eq!(
+            handler.choose(&["KOI8-R", "latin1", "utf-8"]),
+            Some(("utf-8", Encoding::Utf8))
+        );
+        assert_eq!(handler.choose(&["KOI8-R"]), None);
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use clap::crate_version;
+use telnet::{self, TelnetError, TelnetOption};
+
+use crate::connection::encoding::SharedEncoding;
+
+use super::{
+    handler::{TelnetHandler, TelnetOptionHandler, TelnetOptionInteractor},
+    ttype,
+};
+
+pub const OPTION_NEW_ENVIRON: u8 = 39;
+
+const ENVIRON_IS: u8 = 0;
+const ENVIRON_SEND: u8 = 1;
+const ENVIRON_INFO: u8 = 2;
+
+const ENVIRON_VAR: u8 = 0;
+const ENVIRON_VALUE: u8 = 1;
+const ENVIRON_ESC: u8 = 2;
+const ENVIRON_USERVAR: u8 = 3;
+
+/// Client variables reported to the server via NEW-ENVIRON (per MNES).
+/// The defaults are computed from the connection's state, but may be
+/// overridden (or extended) per connection. Notably, `IPADDRESS` is
+/// only ever reported if explicitly provided here.
+#[derive(Clone, Default)]
+pub struct EnvironVars(Arc<Mutex<BTreeMap<String, String>>>);
+
+impl EnvironVars {
+    pub fn set(&mut self, name: String, value: String) {
+        self.0.lock().unwrap().insert(name, value);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.0.lock().unwrap().remove(name)
+    }
+
+    /// Build the full set of variables to report, including defaults
+    pub fn variables(&self, encoding: &SharedEncoding) -> BTreeMap<String, String> {
+        let encoding = encoding.get();
+        let mut variables = BTreeMap::new();
+        variables.insert("CLIENT_NAME".to_string(), "IAIDO".to_string());
+        variables.insert("CLIENT_VERSION".to_string(), crate_version!().to_string());
+        variables.insert("CHARSET".to_string(), encoding.name().to_string());
+        variables.insert(
+            "MTTS".to_string(),
+            ttype::mtts_bitvector(encoding).to_string(),
+        );
+        variables.insert(
+            "TERMINAL_TYPE".to_string(),
+            ttype::TERMINAL_TYPE.to_string(),
+        );
+
+        for (name, value) in self.0.lock().unwrap().iter() {
+            variables.insert(name.clone(), value.clone());
+        }
+
+        variables
+    }
+}
+
+fn parse_send(bytes: &[u8]) -> Vec<String> {
+    let mut names = vec![];
+    let mut current: Option<Vec<u8>> = None;
+    let mut escaped = false;
+
+    for &b in bytes {
+        if escaped {
+            escaped = false;
+            if let Some(ref mut name) = current {
+                name.push(b);
+            }
+            continue;
+        }
+
+        match b {
+            ENVIRON_ESC => escaped = true,
+            ENVIRON_VAR | ENVIRON_USERVAR => {
+                if let Some(name) = current.take() {
+                    names.push(String::from_utf8_lossy(&name).to_string());
+                }
+                current = Some(vec![]);
+            }
+            _ => {
+                if let Some(ref mut name) = current {
+                    name.push(b);
+                }
+            }
+        }
+    }
+
+    if let Some(name) = current {
+        names.push(String::from_utf8_lossy(&name).to_string());
+    }
+
+    names.into_iter().filter(|name| !name.is_empty()).collect()
+}
+
+fn push_escaped(bytes: &mut Vec<u8>, text: &str) {
+    for &b in text.as_bytes() {
+        if b <= ENVIRON_USERVAR {
+            bytes.push(ENVIRON_ESC);
+        }
+        bytes.push(b);
+    }
+}
+
+/// Encode a list of variables for an IS or INFO message; a variable
+/// without a value is reported as undefined
+fn encode(kind: u8, variables: &[(String, Option<String>)]) -> Vec<u8> {
+    let mut bytes = vec![kind];
+    for (name, value) in variables {
+        bytes.push(ENVIRON_VAR);
+        push_escaped(&mut bytes, name);
+        if let Some(value) = value {
+            bytes.push(ENVIRON_VALUE);
+            push_escaped(&mut bytes, value);
+        }
+    }
+    bytes
+}
+
+pub struct EnvironHandler {
+    vars: EnvironVars,
+    encoding: SharedEncoding,
+
+    /// The values we've reported to the server, by name
+    reported: BTreeMap<String, Option<String>>,
+
+    /// If true, the server asked for all variables, so any new ones
+    /// should be reported as well
+    report_all: bool,
+}
+
+impl EnvironHandler {
+    fn send(
+        &mut self,
+        telnet: &mut telnet::Telnet,
+        kind: u8,
+        variables: Vec<(String, Option<String>)>,
+    ) -> Result<(), TelnetError> {
+        crate::info!(
+            "## TELNET > NEW-ENVIRON {} {:?}",
+            if kind == ENVIRON_IS { "IS" } else { "INFO" },
+            variables
+        );
+
+        let message = encode(kind, &variables);
+        for (name, value) in variables {
+            self.reported.insert(name, value);
+        }
+
+        let option = TelnetOption::UnknownOption(OPTION_NEW_ENVIRON);
+        telnet.subnegotiate(option, &message)
+    }
+}
+
+impl TelnetHandler for EnvironHandler {
+    fn on_subnegotiate(
+        &mut self,
+        telnet: &mut telnet::Telnet,
+        bytes: &[u8],
+    ) -> Result<(), TelnetError> {
+        let requested = match bytes.split_first() {
+            Some((&ENVIRON_SEND, requested)) => parse_send(requested),
+
+            // The server shouldn't send us anything else
+            _ => return Ok(()),
+        };
+        crate::info!("## TELNET < NEW-ENVIRON SEND {:?}", requested);
+
+        let mut current = self.vars.variables(&self.encoding);
+        let variables: Vec<(String, Option<String>)> = if requested.is_empty() {
+            self.report_all = true;
+            current.into_iter().map(|(k, v)| (k, Some(v))).collect()
+        } else {
+            requested
+                .into_iter()
+                .map(|name| {
+                    let value = current.remove(&name);
+                    (name, value)
+                })
+                .collect()
+        };
+
+        self.send(telnet, ENVIRON_IS, variables)
+    }
+
+    fn poll(&mut self, telnet: &mut telnet::Telnet) -> Result<(), TelnetError> {
+        if self.reported.is_empty() {
+            // The server hasn't asked for anything (yet)
+            return Ok(());
+        }
+
+        let current = self.vars.variables(&self.encoding);
+        let mut changed = vec![];
+        for (name, reported) in &self.reported {
+            let value = current.get(name);
+            if value != reported.as_ref() {
+                changed.push((name.clone(), value.cloned()));
+            }
+        }
+        if self.report_all {
+            for (name, value) in &current {
+                if !self.reported.contains_key(name) {
+                    changed.push((name.clone(), Some(value.clone())));
+                }
+            }
+        }
+
+        if changed.is_empty() {
+            Ok(())
+        } else {
+            self.send(telnet, ENVIRON_INFO, changed)
+        }
+    }
+}
+
+pub fn create(vars: EnvironVars, encoding: SharedEncoding) -> TelnetOptionHandler {
+    TelnetOptionHandler {
+        interactor: TelnetOptionInteractor::accept_do(TelnetOption::UnknownOption(
+            OPTION_NEW_ENVIRON,
+        )),
+        handler: Box::new(EnvironHandler {
+            vars,
+            encoding,
+            reported: Default::default(),
+            report_all: false,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::Encoding;
+
+    use super::*;
+
+    #[test]
+    fn parse_send_request() {
+        assert_eq!(
+            parse_send(b"\x00CLIENT_NAME\x03CHARSET\x00"),
+            vec!["CLIENT_NAME", "CHARSET"]
+        );
+        assert_eq!(parse_send(b""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn encode_escapes() {
+        let encoded = encode(
+            ENVIRON_IS,
+            &[
+                ("A".to_string(), Some("x\x01y".to_string())),
+                ("B".to_string(), None),
+            ],
+        );
+        assert_eq!(encoded, b"\x00\x00A\x01x\x02\x01y\x00B");
+    }
+
+    #[test]
+    fn variables_follow_encoding() {
+        let mut encoding = SharedEncoding::new(Encoding::Utf8);
+        let mut vars = EnvironVars::default();
+        vars.set("IPADDRESS".to_string(), "127.0.0.1".to_string());
+        assert_eq!(
+            vars.variables(&encoding).get("CHARSET").map(|s| s.as_str()),
+            Some("UTF-8")
+        );
+
+        encoding.set(Encoding::Latin1);
+        let variables = vars.variables(&encoding);
+        assert_eq!(
+            variables.get("CHARSET").map(|s| s.as_str()),
+            Some("ISO-8859-1")
+        );
+        assert_eq!(
+            variables.get("IPADDRESS").map(|s| s.as_str()),
+            Some("127.0.0.1")
+        );
+    }
+}
@@ -0,0 +1,17 @@
+use telnet::TelnetOption;
+
+use super::handler::{TelnetHandler, TelnetOptionHandler, TelnetOptionInteractor};
+
+pub const OPTION_EOR: u8 = 25;
+
+/// Prompts marked with IAC EOR are handled by the TelnetConnection; we
+/// just need to accept the option
+struct EorHandler;
+impl TelnetHandler for EorHandler {}
+
+pub fn create() -> TelnetOptionHandler {
+    TelnetOptionHandler {
+        interactor: TelnetOptionInteractor::accept_will(TelnetOption::UnknownOption(OPTION_EOR)),
+        handler: Box::new(EorHandler),
+    }
+}
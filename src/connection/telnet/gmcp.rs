@@ -0,0 +1,126 @@
+use clap::crate_version;
+use telnet::{self, TelnetError, TelnetOption};
+
+use crate::connection::ReadValue;
+
+use super::handler::{TelnetHandler, TelnetOptionHandler, TelnetOptionInteractor};
+
+pub const OPTION_GMCP: u8 = 201;
+
+/// Packages we advertise support for by default; scripts may request
+/// more via `Core.Supports.Add`
+const DEFAULT_SUPPORTS: &str = r#"["Core 1", "Char 1", "Room 1", "Comm 1"]"#;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GmcpMessage {
+    pub package: String,
+    pub json: Option<String>,
+}
+
+impl GmcpMessage {
+    pub fn new<P: Into<String>>(package: P, json: Option<String>) -> Self {
+        Self {
+            package: package.into(),
+            json,
+        }
+    }
+
+    pub fn parse(bytes: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim();
+        if let Some(index) = text.find(char::is_whitespace) {
+            let json = text[index..].trim();
+            Self::new(
+                &text[..index],
+                if json.is_empty() {
+                    None
+                } else {
+                    Some(json.to_string())
+                },
+            )
+        } else {
+            Self::new(text, None)
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if let Some(json) = &self.json {
+            format!("{} {}", self.package, json).into_bytes()
+        } else {
+            self.package.as_bytes().to_vec()
+        }
+    }
+}
+
+pub fn send(telnet: &mut telnet::Telnet, message: &GmcpMessage) -> Result<(), TelnetError> {
+    crate::info!("## TELNET > IAC SB GMCP {}", message.package);
+    telnet.subnegotiate(
+        TelnetOption::UnknownOption(OPTION_GMCP),
+        &message.to_bytes(),
+    )
+}
+
+#[derive(Default)]
+pub struct GmcpHandler {
+    received: Option<GmcpMessage>,
+}
+
+impl TelnetHandler for GmcpHandler {
+    fn on_remote_will(&mut self, telnet: &mut telnet::Telnet) -> Result<(), TelnetError> {
+        let hello = format!(
+            r#"{{"client": "iaido", "version": "{}"}}"#,
+            crate_version!()
+        );
+        send(telnet, &GmcpMessage::new("Core.Hello", Some(hello)))?;
+        send(
+            telnet,
+            &GmcpMessage::new("Core.Supports.Set", Some(DEFAULT_SUPPORTS.to_string())),
+        )
+    }
+
+    fn on_subnegotiate(
+        &mut self,
+        _telnet: &mut telnet::Telnet,
+        bytes: &[u8],
+    ) -> Result<(), TelnetError> {
+        let message = GmcpMessage::parse(bytes);
+        crate::info!("## TELNET < GMCP {}", message.package);
+        self.received = Some(message);
+        Ok(())
+    }
+
+    fn take_value(&mut self) -> Option<ReadValue> {
+        self.received.take().map(ReadValue::Gmcp)
+    }
+}
+
+pub fn create() -> TelnetOptionHandler {
+    TelnetOptionHandler {
+        interactor: TelnetOptionInteractor::accept_will(TelnetOption::UnknownOption(OPTION_GMCP)),
+        handler: Box::new(GmcpHandler::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_with_json() {
+        let message = GmcpMessage::parse(br#"Char.Vitals {"hp": 42}"#);
+        assert_eq!(message.package, "Char.Vitals");
+        assert_eq!(message.json, Some(r#"{"hp": 42}"#.to_string()));
+    }
+
+    #[test]
+    fn parse_without_json() {
+        let message = GmcpMessage::parse(b"Core.Ping");
+        assert_eq!(message, GmcpMessage::new("Core.Ping", None));
+    }
+
+    #[test]
+    fn round_trip() {
+        let message = GmcpMessage::new("Core.Supports.Add", Some(r#"["Char 1"]"#.to_string()));
+        assert_eq!(GmcpMessage::parse(&message.to_bytes()), message);
+    }
+}
@@ -2,7 +2,7 @@ use std::io;
 
 use telnet::{self, Telnet, TelnetError, TelnetOption};
 
-use crate::editing::Size;
+use crate::{connection::ReadValue, editing::Size};
 
 pub trait TelnetHandler {
     fn negotiate(&mut self, action: &telnet::Action, telnet: &mut Telnet) -> io::Result<()> {
@@ -40,6 +40,18 @@ pub trait TelnetHandler {
     fn on_subnegotiate(&mut self, _telnet: &mut Telnet, _bytes: &[u8]) -> Result<(), TelnetError> {
         Ok(())
     }
+
+    /// Called after every read attempt, so handlers may inform the server
+    /// about changes to state that's shared with the rest of the app
+    fn poll(&mut self, _telnet: &mut Telnet) -> Result<(), TelnetError> {
+        Ok(())
+    }
+
+    /// Handlers may produce a value for the app in response to a subnegotiation;
+    /// it should be returned from here
+    fn take_value(&mut self) -> Option<ReadValue> {
+        None
+    }
 }
 
 pub struct TelnetOptionInteractor {
@@ -61,6 +73,10 @@ impl TelnetOptionInteractor {
         Self::new(option, false, true)
     }
 
+    pub fn accept_will(option: TelnetOption) -> Self {
+        Self::new(option, true, false)
+    }
+
     fn new(option: TelnetOption, accept_will: bool, accept_do: bool) -> Self {
         Self {
             option,
@@ -70,6 +86,16 @@ impl TelnetOptionInteractor {
             acked_do: false,
         }
     }
+
+    /// Returns true if we've agreed to let the server enable this option
+    pub fn is_remote_enabled(&self) -> bool {
+        self.accept_will && self.acked_will
+    }
+
+    /// Returns true if we've agreed to enable this option at the server's request
+    pub fn is_local_enabled(&self) -> bool {
+        self.accept_do && self.acked_do
+    }
 }
 
 impl TelnetHandler for TelnetOptionInteractor {
@@ -124,4 +150,16 @@ impl TelnetHandler for TelnetOptionHandler {
             Ok(())
         }
     }
+
+    fn poll(&mut self, telnet: &mut Telnet) -> Result<(), TelnetError> {
+        if self.interactor.is_local_enabled() || self.interactor.is_remote_enabled() {
+            self.handler.poll(telnet)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn take_value(&mut self) -> Option<ReadValue> {
+        self.handler.take_value()
+    }
 }
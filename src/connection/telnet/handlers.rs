@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, io};
 
-use telnet::TelnetOption;
+use telnet::{Telnet, TelnetOption};
 
 use crate::connection::ConnectParams;
 
-use super::{echo, handler::TelnetOptionHandler, naws, ttype};
+use super::{
+    charset, echo, environ, eor, gmcp,
+    handler::{TelnetHandler, TelnetOptionHandler},
+    mccp, msdp, mssp, mxp, naws, ttype,
+};
 
 pub struct TelnetHandlers {
     handlers: HashMap<u8, TelnetOptionHandler>,
@@ -22,7 +26,19 @@ impl TelnetHandlers {
 
         handlers.register(echo::create(params.flags.clone()));
         handlers.register(naws::create(params.size));
-        handlers.register(ttype::create());
+        handlers.register(ttype::create(params.encoding.clone()));
+        handlers.register(eor::create());
+        handlers.register(gmcp::create());
+        handlers.register(msdp::create());
+        handlers.register(mssp::create());
+        handlers.register(mxp::create());
+        handlers.register(charset::create(params.encoding.clone()));
+        handlers.register(environ::create(
+            params.environ.clone(),
+            params.encoding.clone(),
+        ));
+        handlers.register(mccp::create_mccp2());
+        handlers.register(mccp::create_mccp3(params.flags.clone()));
 
         handlers
     }
@@ -33,6 +49,16 @@ impl TelnetHandlers {
         self.handlers.get_mut(&option.as_byte())
     }
 
+    /// Give each handler a chance to send any updates to the server
+    pub fn poll(&mut self, telnet: &mut Telnet) -> io::Result<()> {
+        for handler in self.handlers.values_mut() {
+            if let Err(e) = handler.poll(telnet) {
+                return Err(io::Error::new(io::ErrorKind::Other, e));
+            }
+        }
+        Ok(())
+    }
+
     pub fn register(&mut self, handler: TelnetOptionHandler) {
         self.handlers
             .insert(handler.interactor.option.as_byte(), handler);
@@ -0,0 +1,391 @@
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use miniz_oxide::{
+    deflate::core::{create_comp_flags_from_zip_params, CompressorOxide},
+    inflate::stream::InflateState,
+    DataFormat, MZError, MZFlush, MZStatus,
+};
+use telnet::{self, TelnetError, TelnetOption};
+
+use crate::connection::{flags::Flag, Flags};
+
+use super::handler::{TelnetHandler, TelnetOptionHandler, TelnetOptionInteractor};
+
+pub const OPTION_MCCP2: u8 = 86;
+pub const OPTION_MCCP3: u8 = 87;
+
+const IAC: u8 = 255;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const READ_BUFFER_SIZE: usize = 4096;
+const COMPRESSION_LEVEL: i32 = 6;
+
+/// Watches the (uncompressed) inbound byte stream for the
+/// `IAC SB COMPRESS2 IAC SE` sequence that signals the start of compression
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StartScanState {
+    Data,
+    Iac,
+    Sb,
+    Option,
+    OptionIac,
+}
+
+impl StartScanState {
+    fn next(self, byte: u8) -> Self {
+        match (self, byte) {
+            (StartScanState::Data, IAC) => StartScanState::Iac,
+            (StartScanState::Iac, SB) => StartScanState::Sb,
+            (StartScanState::Sb, OPTION_MCCP2) => StartScanState::Option,
+            (StartScanState::Option, IAC) => StartScanState::OptionIac,
+            _ => StartScanState::Data,
+        }
+    }
+}
+
+/// Wraps a telnet Stream to transparently handle MCCP. Inbound data is
+/// decompressed after the server starts MCCP2, and outbound data is
+/// compressed once the [Flag::CompressedOutput] flag has been set
+/// (by the MCCP3 handler)
+pub struct MccpStream {
+    stream: Box<dyn telnet::Stream>,
+    flags: Flags,
+    scan_state: StartScanState,
+    inflate: Option<Box<InflateState>>,
+    deflate: Option<Box<CompressorOxide>>,
+    read_buffer: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl MccpStream {
+    pub fn new(stream: Box<dyn telnet::Stream>, flags: Flags) -> Self {
+        Self {
+            stream,
+            flags,
+            scan_state: StartScanState::Data,
+            inflate: None,
+            deflate: None,
+            read_buffer: vec![0; READ_BUFFER_SIZE],
+            pending: Vec::with_capacity(READ_BUFFER_SIZE),
+        }
+    }
+
+    fn process_incoming(&mut self, mut bytes: &[u8]) -> io::Result<()> {
+        while !bytes.is_empty() {
+            if self.inflate.is_some() {
+                let consumed = self.inflate_incoming(bytes)?;
+                bytes = &bytes[consumed..];
+            } else {
+                let mut consumed = 0;
+                let mut started = false;
+                for &b in bytes {
+                    consumed += 1;
+                    started = self.scan_state == StartScanState::OptionIac && b == SE;
+                    self.scan_state = self.scan_state.next(b);
+                    if started {
+                        break;
+                    }
+                }
+
+                // NOTE: The start sequence is itself passed along uncompressed
+                // so the telnet parser sees a complete subnegotiation
+                self.pending.extend_from_slice(&bytes[..consumed]);
+                if started {
+                    crate::info!("## TELNET: MCCP2 compression started");
+                    self.inflate = Some(InflateState::new_boxed(DataFormat::Zlib));
+                    self.flags.add(Flag::CompressedInput);
+                }
+                bytes = &bytes[consumed..];
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of bytes consumed from `bytes`. If the compressed
+    /// stream ends, any remaining bytes are left for uncompressed processing
+    fn inflate_incoming(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let inflate = self.inflate.as_mut().expect("No inflate state");
+        let mut consumed = 0;
+        let mut output = [0u8; READ_BUFFER_SIZE];
+
+        loop {
+            let result = miniz_oxide::inflate::stream::inflate(
+                inflate,
+                &bytes[consumed..],
+                &mut output,
+                MZFlush::None,
+            );
+            consumed += result.bytes_consumed;
+            self.pending
+                .extend_from_slice(&output[..result.bytes_written]);
+
+            match result.status {
+                Ok(MZStatus::StreamEnd) => {
+                    crate::info!("## TELNET: MCCP2 compression ended");
+                    self.inflate = None;
+                    self.flags.remove(Flag::CompressedInput);
+                    return Ok(consumed);
+                }
+
+                // More output may be pending; keep going
+                Ok(_) if result.bytes_written == output.len() => {}
+
+                // No progress could be made; we need more input
+                Err(MZError::Buf) | Ok(_) if consumed == bytes.len() => return Ok(consumed),
+                Ok(_) => {}
+
+                Err(e) => {
+                    self.inflate = None;
+                    self.flags.remove(Flag::CompressedInput);
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("MCCP2 decompression failed: {:?}", e),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn deflate_outgoing(&mut self, bytes: &[u8], flush: MZFlush) -> io::Result<Vec<u8>> {
+        let deflate = self.deflate.as_mut().expect("No deflate state");
+        let mut consumed = 0;
+        let mut compressed = Vec::with_capacity(bytes.len() + 64);
+        let mut output = [0u8; READ_BUFFER_SIZE];
+
+        loop {
+            let result = miniz_oxide::deflate::stream::deflate(
+                deflate,
+                &bytes[consumed..],
+                &mut output,
+                flush,
+            );
+            consumed += result.bytes_consumed;
+            compressed.extend_from_slice(&output[..result.bytes_written]);
+
+            match result.status {
+                Ok(MZStatus::StreamEnd) => return Ok(compressed),
+                Ok(_) | Err(MZError::Buf)
+                    if consumed == bytes.len() && result.bytes_written < output.len() =>
+                {
+                    return Ok(compressed)
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("MCCP3 compression failed: {:?}", e),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Start or stop outbound compression to match the flags
+    fn update_deflate(&mut self) -> io::Result<()> {
+        match (
+            self.deflate.is_some(),
+            self.flags.has(Flag::CompressedOutput),
+        ) {
+            (false, true) => {
+                crate::info!("## TELNET: MCCP3 compression started");
+                let flags = create_comp_flags_from_zip_params(COMPRESSION_LEVEL, 15, 0);
+                self.deflate = Some(Box::new(CompressorOxide::new(flags)));
+            }
+            (true, false) => {
+                crate::info!("## TELNET: MCCP3 compression ended");
+                let finished = self.deflate_outgoing(&[], MZFlush::Finish)?;
+                self.deflate = None;
+                self.stream.write_all(&finished)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Read for MccpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() {
+            let read = self.stream.read(&mut self.read_buffer)?;
+            if read == 0 {
+                return Ok(0);
+            }
+
+            let incoming = std::mem::take(&mut self.read_buffer);
+            let result = self.process_incoming(&incoming[..read]);
+            self.read_buffer = incoming;
+            result?;
+        }
+
+        let count = buf.len().min(self.pending.len());
+        buf[..count].copy_from_slice(&self.pending[..count]);
+        self.pending.drain(..count);
+        Ok(count)
+    }
+}
+
+impl Write for MccpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update_deflate()?;
+        if self.deflate.is_some() {
+            let compressed = self.deflate_outgoing(buf, MZFlush::Sync)?;
+            self.stream.write_all(&compressed)?;
+            Ok(buf.len())
+        } else {
+            self.stream.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl telnet::Stream for MccpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.stream.set_nonblocking(nonblocking)
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(dur)
+    }
+}
+
+/// MCCP2 decompression is handled entirely by [MccpStream]; we just need
+/// to accept the option
+struct Mccp2Handler;
+impl TelnetHandler for Mccp2Handler {}
+
+struct Mccp3Handler {
+    flags: Flags,
+}
+
+impl TelnetHandler for Mccp3Handler {
+    fn on_remote_will(&mut self, telnet: &mut telnet::Telnet) -> Result<(), TelnetError> {
+        // NOTE: The start sequence must be sent uncompressed, so only set the
+        // flag (which enables compression in MccpStream) once it's out
+        crate::info!("## TELNET > IAC SB MCCP3 IAC SE");
+        telnet.subnegotiate(TelnetOption::UnknownOption(OPTION_MCCP3), &[])?;
+        self.flags.add(Flag::CompressedOutput);
+        Ok(())
+    }
+
+    fn on_remote_wont(&mut self, _telnet: &mut telnet::Telnet) -> Result<(), TelnetError> {
+        self.flags.remove(Flag::CompressedOutput);
+        Ok(())
+    }
+}
+
+pub fn create_mccp2() -> TelnetOptionHandler {
+    TelnetOptionHandler {
+        interactor: TelnetOptionInteractor::accept_will(TelnetOption::UnknownOption(OPTION_MCCP2)),
+        handler: Box::new(Mccp2Handler),
+    }
+}
+
+pub fn create_mccp3(flags: Flags) -> TelnetOptionHandler {
+    TelnetOptionHandler {
+        interactor: TelnetOptionInteractor::accept_will(TelnetOption::UnknownOption(OPTION_MCCP3)),
+        handler: Box::new(Mccp3Handler { flags }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    struct TestStream {
+        input: Cursor<Vec<u8>>,
+        output: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Read for TestStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for TestStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl telnet::Stream for TestStream {
+        fn set_nonblocking(&self, _nonblocking: bool) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_read_timeout(&self, _dur: Option<Duration>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn stream_of(input: Vec<u8>, output: Arc<Mutex<Vec<u8>>>) -> MccpStream {
+        MccpStream::new(
+            Box::new(TestStream {
+                input: Cursor::new(input),
+                output,
+            }),
+            Flags::default(),
+        )
+    }
+
+    fn read_all(stream: &mut MccpStream) -> Vec<u8> {
+        let mut output = vec![];
+        stream.read_to_end(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn passthrough_without_compression() {
+        let mut stream = stream_of(b"Take my love\xff\xff\xfa\x56".to_vec(), Default::default());
+        assert_eq!(read_all(&mut stream), b"Take my love\xff\xff\xfa\x56");
+        assert!(!stream.flags.has(Flag::CompressedInput));
+    }
+
+    #[test]
+    fn decompress_after_start() {
+        let start = [IAC, SB, OPTION_MCCP2, IAC, SE];
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(b"Take my land", 6);
+        let input = [b"Hi".as_ref(), &start, &compressed, b"Uncompressed"].concat();
+
+        let mut stream = stream_of(input, Default::default());
+        let expected = [b"Hi".as_ref(), &start, b"Take my land", b"Uncompressed"].concat();
+        assert_eq!(read_all(&mut stream), expected);
+
+        // The compressed stream ended:
+        assert!(!stream.flags.has(Flag::CompressedInput));
+    }
+
+    #[test]
+    fn compress_output() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut stream = stream_of(vec![], output.clone());
+        stream.flags.add(Flag::CompressedOutput);
+        stream.write_all(b"Take me where").unwrap();
+        stream.write_all(b" I cannot stand").unwrap();
+        stream.flags.remove(Flag::CompressedOutput);
+        stream.write_all(b"!").unwrap();
+
+        let output = output.lock().unwrap();
+        let (compressed, uncompressed) = output.split_at(output.len() - 1);
+        assert_eq!(uncompressed, b"!");
+        assert_eq!(
+            miniz_oxide::inflate::decompress_to_vec_zlib(compressed).unwrap(),
+            b"Take me where I cannot stand"
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+
+use clap::crate_version;
+use telnet::{self, TelnetError, TelnetOption};
+
+use crate::connection::ReadValue;
+
+use super::handler::{TelnetHandler, TelnetOptionHandler, TelnetOptionInteractor};
+
+pub const OPTION_MSDP: u8 = 69;
+
+const MSDP_VAR: u8 = 1;
+const MSDP_VAL: u8 = 2;
+const MSDP_TABLE_OPEN: u8 = 3;
+const MSDP_TABLE_CLOSE: u8 = 4;
+const MSDP_ARRAY_OPEN: u8 = 5;
+const MSDP_ARRAY_CLOSE: u8 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MsdpValue {
+    String(String),
+    Array(Vec<MsdpValue>),
+    Table(HashMap<String, MsdpValue>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MsdpVariable {
+    pub name: String,
+    pub value: MsdpValue,
+}
+
+impl MsdpVariable {
+    pub fn new<N: Into<String>>(name: N, value: MsdpValue) -> Self {
+        Self {
+            name: name.into(),
+            value,
+        }
+    }
+
+    /// Convenience for creating a command to send to the server, like
+    /// `REPORT` or `LIST`, with the given (variable name) argument
+    pub fn command<C: Into<String>, V: Into<String>>(command: C, value: V) -> Self {
+        Self::new(command, MsdpValue::String(value.into()))
+    }
+
+    /// Parse all the variables from the body of an MSDP subnegotiation
+    pub fn parse_all(bytes: &[u8]) -> Vec<MsdpVariable> {
+        let mut parser = MsdpParser { bytes, offset: 0 };
+        let mut variables = vec![];
+        while let Some(variable) = parser.variable() {
+            variables.push(variable);
+        }
+        variables
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![MSDP_VAR];
+        bytes.extend(self.name.as_bytes());
+        bytes.push(MSDP_VAL);
+        self.value.write_to(&mut bytes);
+        bytes
+    }
+}
+
+impl MsdpValue {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        match self {
+            MsdpValue::String(s) => bytes.extend(s.as_bytes()),
+            MsdpValue::Array(values) => {
+                bytes.push(MSDP_ARRAY_OPEN);
+                for value in values {
+                    bytes.push(MSDP_VAL);
+                    value.write_to(bytes);
+                }
+                bytes.push(MSDP_ARRAY_CLOSE);
+            }
+            MsdpValue::Table(table) => {
+                bytes.push(MSDP_TABLE_OPEN);
+                for (name, value) in table {
+                    bytes.push(MSDP_VAR);
+                    bytes.extend(name.as_bytes());
+                    bytes.push(MSDP_VAL);
+                    value.write_to(bytes);
+                }
+                bytes.push(MSDP_TABLE_CLOSE);
+            }
+        }
+    }
+}
+
+struct MsdpParser<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> MsdpParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.offset).cloned()
+    }
+
+    fn consume_if(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.offset += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn text(&mut self) -> String {
+        let start = self.offset;
+        while let Some(b) = self.peek() {
+            if (MSDP_VAR..=MSDP_ARRAY_CLOSE).contains(&b) {
+                break;
+            }
+            self.offset += 1;
+        }
+        String::from_utf8_lossy(&self.bytes[start..self.offset]).to_string()
+    }
+
+    /// Read a VAR and all of its VALs; per the spec, a VAR with multiple
+    /// VALs is treated as an array
+    fn variable(&mut self) -> Option<MsdpVariable> {
+        // Skip any garbage until the next VAR
+        while self.peek().is_some() && !self.consume_if(MSDP_VAR) {
+            self.offset += 1;
+        }
+        self.peek()?;
+
+        let name = self.text();
+        let mut values = vec![];
+        while self.consume_if(MSDP_VAL) {
+            values.push(self.value());
+        }
+
+        let value = if values.len() == 1 {
+            values.swap_remove(0)
+        } else if values.is_empty() {
+            MsdpValue::String("".to_string())
+        } else {
+            MsdpValue::Array(values)
+        };
+
+        Some(MsdpVariable::new(name, value))
+    }
+
+    fn value(&mut self) -> MsdpValue {
+        if self.consume_if(MSDP_TABLE_OPEN) {
+            let mut table = HashMap::default();
+            while self.consume_if(MSDP_VAR) {
+                let name = self.text();
+                let value = if self.consume_if(MSDP_VAL) {
+                    self.value()
+                } else {
+                    MsdpValue::String("".to_string())
+                };
+                table.insert(name, value);
+            }
+            self.consume_if(MSDP_TABLE_CLOSE);
+            MsdpValue::Table(table)
+        } else if self.consume_if(MSDP_ARRAY_OPEN) {
+            let mut values = vec![];
+            while self.consume_if(MSDP_VAL) {
+                values.push(self.value());
+            }
+            self.consume_if(MSDP_ARRAY_CLOSE);
+            MsdpValue::Array(values)
+        } else {
+            MsdpValue::String(self.text())
+        }
+    }
+}
+
+pub fn send(telnet: &mut telnet::Telnet, variable: &MsdpVariable) -> Result<(), TelnetError> {
+    crate::info!("## TELNET > IAC SB MSDP {}", variable.name);
+    telnet.subnegotiate(
+        TelnetOption::UnknownOption(OPTION_MSDP),
+        &variable.to_bytes(),
+    )
+}
+
+#[derive(Default)]
+pub struct MsdpHandler {
+    received: Vec<MsdpVariable>,
+}
+
+impl TelnetHandler for MsdpHandler {
+    fn on_remote_will(&mut self, telnet: &mut telnet::Telnet) -> Result<(), TelnetError> {
+        send(telnet, &MsdpVariable::command("CLIENT_ID", "iaido"))?;
+        send(
+            telnet,
+            &MsdpVariable::command("CLIENT_VERSION", crate_version!()),
+        )
+    }
+
+    fn on_subnegotiate(
+        &mut self,
+        _telnet: &mut telnet::Telnet,
+        bytes: &[u8],
+    ) -> Result<(), TelnetError> {
+        let variables = MsdpVariable::parse_all(bytes);
+        crate::info!("## TELNET < MSDP ({} variables)", variables.len());
+        self.received.extend(variables);
+        Ok(())
+    }
+
+    fn take_value(&mut self) -> Option<ReadValue> {
+        if self.received.is_empty() {
+            None
+        } else {
+            Some(ReadValue::Msdp(std::mem::take(&mut self.received)))
+        }
+    }
+}
+
+pub fn create() -> TelnetOptionHandler {
+    TelnetOptionHandler {
+        interactor: TelnetOptionInteractor::accept_will(TelnetOption::UnknownOption(OPTION_MSDP)),
+        handler: Box::new(MsdpHandler::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> MsdpValue {
+        MsdpValue::String(s.to_string())
+    }
+
+    #[test]
+    fn parse_simple_variables() {
+        let variables = MsdpVariable::parse_all(b"\x01HEALTH\x0242\x01MANA\x0210");
+        assert_eq!(
+            variables,
+            vec![
+                MsdpVariable::new("HEALTH", string("42")),
+                MsdpVariable::new("MANA", string("10")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_multiple_vals_as_array() {
+        let variables = MsdpVariable::parse_all(b"\x01REPORTABLE\x02HEALTH\x02MANA");
+        assert_eq!(
+            variables,
+            vec![MsdpVariable::new(
+                "REPORTABLE",
+                MsdpValue::Array(vec![string("HEALTH"), string("MANA")])
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_nested() {
+        let variables = MsdpVariable::parse_all(
+            b"\x01ROOM\x02\x03\x01VNUM\x026008\x01EXITS\x02\x05\x02n\x02s\x06\x04",
+        );
+        let mut room = HashMap::default();
+        room.insert("VNUM".to_string(), string("6008"));
+        room.insert(
+            "EXITS".to_string(),
+            MsdpValue::Array(vec![string("n"), string("s")]),
+        );
+        assert_eq!(
+            variables,
+            vec![MsdpVariable::new("ROOM", MsdpValue::Table(room))]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let variable = MsdpVariable::new(
+            "REPORT",
+            MsdpValue::Array(vec![string("HEALTH"), string("MANA")]),
+        );
+        assert_eq!(
+            MsdpVariable::parse_all(&variable.to_bytes()),
+            vec![variable]
+        );
+    }
+}
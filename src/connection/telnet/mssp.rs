@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use telnet::{self, TelnetError, TelnetOption};
+
+use crate::connection::ReadValue;
+
+use super::handler::{TelnetHandler, TelnetOptionHandler, TelnetOptionInteractor};
+
+pub const OPTION_MSSP: u8 = 70;
+
+const MSSP_VAR: u8 = 1;
+const MSSP_VAL: u8 = 2;
+
+/// Mud Server Status Protocol variables, like `NAME` or `PLAYERS`. A
+/// variable may have more than one value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MsspData {
+    pub variables: BTreeMap<String, Vec<String>>,
+}
+
+impl MsspData {
+    pub fn parse(bytes: &[u8]) -> Self {
+        let mut data = MsspData::default();
+        let mut current_var: Option<String> = None;
+
+        // Each chunk starts with the separator describing its contents
+        let mut chunk_start = None;
+        for (i, &b) in bytes.iter().enumerate() {
+            if b == MSSP_VAR || b == MSSP_VAL {
+                if let Some(start) = chunk_start {
+                    data.consume(&mut current_var, &bytes[start..i]);
+                }
+                chunk_start = Some(i);
+            }
+        }
+        if let Some(start) = chunk_start {
+            data.consume(&mut current_var, &bytes[start..]);
+        }
+
+        data
+    }
+
+    fn consume(&mut self, current_var: &mut Option<String>, chunk: &[u8]) {
+        let text = String::from_utf8_lossy(&chunk[1..]).to_string();
+        if chunk[0] == MSSP_VAR {
+            self.variables.entry(text.clone()).or_default();
+            *current_var = Some(text);
+        } else if let Some(var) = current_var {
+            self.variables.entry(var.clone()).or_default().push(text);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Vec<String>> {
+        self.variables.get(name)
+    }
+}
+
+#[derive(Default)]
+pub struct MsspHandler {
+    received: Option<MsspData>,
+}
+
+impl TelnetHandler for MsspHandler {
+    fn on_subnegotiate(
+        &mut self,
+        _telnet: &mut telnet::Telnet,
+        bytes: &[u8],
+    ) -> Result<(), TelnetError> {
+        let data = MsspData::parse(bytes);
+        crate::info!("## TELNET < MSSP ({} variables)", data.variables.len());
+        self.received = Some(data);
+        Ok(())
+    }
+
+    fn take_value(&mut self) -> Option<ReadValue> {
+        self.received.take().map(ReadValue::Mssp)
+    }
+}
+
+pub fn create() -> TelnetOptionHandler {
+    TelnetOptionHandler {
+        interactor: TelnetOptionInteractor::accept_will(TelnetOption::UnknownOption(OPTION_MSSP)),
+        handler: Box::new(MsspHandler::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_variables() {
+        let data = MsspData::parse(b"\x01NAME\x02Serenity\x01PORT\x024000\x025000\x01EMPTY");
+        assert_eq!(data.get("NAME"), Some(&vec!["Serenity".to_string()]));
+        assert_eq!(
+            data.get("PORT"),
+            Some(&vec!["4000".to_string(), "5000".to_string()])
+        );
+        assert_eq!(data.get("EMPTY"), Some(&vec![]));
+        assert_eq!(data.variables.len(), 3);
+    }
+}
@@ -0,0 +1,17 @@
+use telnet::TelnetOption;
+
+use super::handler::{TelnetHandler, TelnetOptionHandler, TelnetOptionInteractor};
+
+pub const OPTION_MXP: u8 = 91;
+
+/// MXP tags are parsed by the AnsiPipeline once the TelnetConnection sees
+/// that the option was enabled; we just need to accept it
+struct MxpHandler;
+impl TelnetHandler for MxpHandler {}
+
+pub fn create() -> TelnetOptionHandler {
+    TelnetOptionHandler {
+        interactor: TelnetOptionInteractor::accept_will(TelnetOption::UnknownOption(OPTION_MXP)),
+        handler: Box::new(MxpHandler),
+    }
+}
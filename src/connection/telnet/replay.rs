@@ -0,0 +1,203 @@
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use url::Url;
+
+use crate::connection::{
+    recording::{read_recording, RecordedChunk},
+    transport::Transport,
+    ConnectParams, TransportFactory,
+};
+
+use super::TelnetConnection;
+
+/// Plays back a session recording as a telnet Stream. Chunks are
+/// delivered at their original times, scaled by `speed`; if `speed`
+/// is None, everything is delivered immediately
+pub struct ReplayStream {
+    chunks: VecDeque<RecordedChunk>,
+    pending: Vec<u8>,
+    speed: Option<f64>,
+    start: Instant,
+    read_timeout: Cell<Option<Duration>>,
+}
+
+impl ReplayStream {
+    pub fn new(chunks: Vec<RecordedChunk>, speed: Option<f64>) -> Self {
+        Self {
+            chunks: chunks.into(),
+            pending: vec![],
+            speed,
+            start: Instant::now(),
+            read_timeout: Cell::new(None),
+        }
+    }
+
+    fn due(&self, elapsed: Duration) -> Instant {
+        match self.speed {
+            Some(speed) => self.start + elapsed.div_f64(speed),
+            None => self.start,
+        }
+    }
+}
+
+impl Read for ReplayStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            let due = match self.chunks.front() {
+                Some(chunk) => self.due(chunk.elapsed),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "End of recording",
+                    ))
+                }
+            };
+
+            let now = Instant::now();
+            if due > now {
+                let wait = due - now;
+                match self.read_timeout.get() {
+                    Some(timeout) if timeout < wait => {
+                        thread::sleep(timeout);
+                        return Err(io::ErrorKind::WouldBlock.into());
+                    }
+                    _ => thread::sleep(wait),
+                }
+            }
+
+            if let Some(chunk) = self.chunks.pop_front() {
+                self.pending = chunk.bytes;
+            }
+        }
+
+        let count = buf.len().min(self.pending.len());
+        buf[..count].copy_from_slice(&self.pending[..count]);
+        self.pending.drain(..count);
+        Ok(count)
+    }
+}
+
+impl Write for ReplayStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Nobody's listening
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl telnet::Stream for ReplayStream {
+    fn set_nonblocking(&self, _nonblocking: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.read_timeout.set(dur);
+        Ok(())
+    }
+}
+
+/// The playback speed may be requested via the `speed` query param; it may
+/// be a multiplier (like `2` or `0.5`), or `instant`
+fn parse_speed(uri: &Url) -> Option<f64> {
+    match uri.query_pairs().find(|(key, _)| key == "speed") {
+        Some((_, value)) if value == "instant" => None,
+        Some((_, value)) => match value.parse::<f64>() {
+            Ok(speed) if speed > 0. => Some(speed),
+            _ => None,
+        },
+        None => Some(1.),
+    }
+}
+
+/// Replays session recordings (see `:record`) via `replay:///path/to/file`,
+/// through the usual telnet processing
+pub struct ReplayConnectionFactory;
+impl TransportFactory for ReplayConnectionFactory {
+    fn clone_boxed(&self) -> Box<dyn TransportFactory> {
+        Box::new(ReplayConnectionFactory)
+    }
+
+    fn create(&self, params: &ConnectParams) -> Option<io::Result<Box<dyn Transport + Send>>> {
+        if params.uri.scheme() != "replay" {
+            return None;
+        }
+
+        let path = match params.uri.to_file_path() {
+            Ok(path) => path,
+            Err(_) => {
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}: invalid replay uri", params.display_uri()),
+                )))
+            }
+        };
+
+        Some(read_recording(&path).map(|chunks| {
+            let stream = ReplayStream::new(chunks, parse_speed(&params.uri));
+            Box::new(TelnetConnection::from_decompressed_stream(
+                Box::new(stream),
+                params,
+            )) as Box<dyn Transport + Send>
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use telnet::Stream;
+
+    use super::*;
+
+    fn chunk(millis: u64, bytes: &[u8]) -> RecordedChunk {
+        RecordedChunk {
+            elapsed: Duration::from_millis(millis),
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn instant_replay() {
+        let mut stream =
+            ReplayStream::new(vec![chunk(0, b"Take my "), chunk(60_000, b"love")], None);
+        let mut buf = [0u8; 5];
+        assert_eq!(stream.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf, b"Take ");
+        assert_eq!(stream.read(&mut buf).unwrap(), 3);
+        assert_eq!(stream.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"love");
+        assert_eq!(
+            stream.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn timed_replay() {
+        let mut stream = ReplayStream::new(vec![chunk(60_000, b"love")], Some(1.));
+        stream
+            .set_read_timeout(Some(Duration::from_millis(1)))
+            .unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(
+            stream.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+    }
+
+    #[test]
+    fn speeds() {
+        let uri = |s: &str| Url::parse(s).unwrap();
+        assert_eq!(parse_speed(&uri("replay:///tmp/rec")), Some(1.));
+        assert_eq!(parse_speed(&uri("replay:///tmp/rec?speed=2.5")), Some(2.5));
+        assert_eq!(parse_speed(&uri("replay:///tmp/rec?speed=instant")), None);
+    }
+}
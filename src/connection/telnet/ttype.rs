@@ -1,6 +1,8 @@
 use clap::crate_version;
 use telnet::{self, TelnetError, TelnetOption};
 
+use crate::connection::encoding::{Encoding, SharedEncoding};
+
 use super::handler::{TelnetHandler, TelnetOptionHandler, TelnetOptionInteractor};
 
 const MTTS_ANSI: u16 = 1;
@@ -10,6 +12,18 @@ const MTTS_TRUE_COLOR: u16 = 256;
 
 const TELNET_IS: u8 = 0;
 
+pub const TERMINAL_TYPE: &str = "ANSI-TRUECOLOR";
+
+/// Build the MTTS bit vector describing our capabilities
+pub fn mtts_bitvector(encoding: Encoding) -> u16 {
+    let base = MTTS_ANSI + MTTS_256COLOR + MTTS_TRUE_COLOR;
+    if encoding == Encoding::Utf8 {
+        base + MTTS_UTF8
+    } else {
+        base
+    }
+}
+
 #[derive(Clone, Copy)]
 enum TTypeRequestState {
     ClientName,
@@ -19,21 +33,16 @@ enum TTypeRequestState {
 
 pub struct TTypeHandler {
     state: TTypeRequestState,
+    encoding: SharedEncoding,
 }
 
-impl Default for TTypeHandler {
-    fn default() -> Self {
+impl TTypeHandler {
+    fn new(encoding: SharedEncoding) -> Self {
         Self {
             state: TTypeRequestState::ClientName,
+            encoding,
         }
     }
-}
-
-impl TTypeHandler {
-    fn build_mtts_bitvector(&self) -> u16 {
-        // TODO Verify UTF8 support?
-        MTTS_ANSI + MTTS_UTF8 + MTTS_256COLOR + MTTS_TRUE_COLOR
-    }
 
     fn send_state(
         &self,
@@ -42,8 +51,10 @@ impl TTypeHandler {
     ) -> Result<(), TelnetError> {
         let name = match state {
             TTypeRequestState::ClientName => format!("IAIDO {}", crate_version!()),
-            TTypeRequestState::TermType => "ANSI-TRUECOLOR".to_string(), // ?
-            TTypeRequestState::MttsBitVector => format!("MTTS {}", self.build_mtts_bitvector()),
+            TTypeRequestState::TermType => TERMINAL_TYPE.to_string(), // ?
+            TTypeRequestState::MttsBitVector => {
+                format!("MTTS {}", mtts_bitvector(self.encoding.get()))
+            }
         };
         let name_bytes = name.as_bytes();
 
@@ -75,9 +86,9 @@ impl TelnetHandler for TTypeHandler {
     }
 }
 
-pub fn create() -> TelnetOptionHandler {
+pub fn create(encoding: SharedEncoding) -> TelnetOptionHandler {
     TelnetOptionHandler {
         interactor: TelnetOptionInteractor::accept_do(TelnetOption::TTYPE),
-        handler: Box::new(TTypeHandler::default()),
+        handler: Box::new(TTypeHandler::new(encoding)),
     }
 }
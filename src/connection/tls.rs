@@ -1,9 +1,17 @@
 use delegate::delegate;
-use native_tls::TlsStream;
+use native_tls::{Certificate, Identity, TlsConnector, TlsStream};
+use std::fs;
 use std::io::{self, Read, Write};
 use std::net::TcpStream;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use telnet::Telnet;
+
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::script::ScriptingManager;
+
+const FINGERPRINTS_FILE: &str = "tls_fingerprints";
 
 pub struct TlsTelnetStream(TlsStream<TcpStream>);
 
@@ -32,6 +40,334 @@ impl telnet::Stream for TlsTelnetStream {
     }
 }
 
-pub fn create_telnet(raw_tls: TlsStream<TcpStream>, buffer_size: usize) -> Telnet {
-    Telnet::from_stream(Box::new(TlsTelnetStream(raw_tls)), buffer_size)
+#[derive(Clone, Debug, PartialEq)]
+pub enum CertificatePin {
+    /// The SHA-256 fingerprint of the certificate we expect
+    Fingerprint(String),
+
+    /// Remember the fingerprint of the first certificate we see for
+    /// a host, and expect it for every subsequent connection
+    TrustOnFirstUse,
+}
+
+/// Per-connection TLS options, provided via query params on the url:
+///
+///   - `ca=<path>`: trust the certificate(s) in the given PEM file (may be repeated)
+///   - `cert=<path>`: identify with the client certificate in the given PKCS #12 file,
+///     optionally protected by `password=<password>`
+///   - `pin=<fingerprint>`: only accept a certificate with the given SHA-256 fingerprint
+///   - `pin=tofu`: remember the certificate's fingerprint the first time we connect to a
+///     host, and only accept that certificate after
+///   - `insecure=true`: accept invalid (IE: self-signed) certificates; consider combining
+///     this with `pin` for some protection
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TlsOptions {
+    pub ca_files: Vec<PathBuf>,
+    pub identity: Option<PathBuf>,
+    pub identity_password: String,
+    pub pin: Option<CertificatePin>,
+    pub insecure: bool,
+}
+
+impl TlsOptions {
+    pub fn from_uri(uri: &Url) -> io::Result<Self> {
+        let mut options = Self::default();
+        for (key, value) in uri.query_pairs() {
+            match key.as_ref() {
+                "ca" => options.ca_files.push(PathBuf::from(value.as_ref())),
+                "cert" => options.identity = Some(PathBuf::from(value.as_ref())),
+                "password" => options.identity_password = value.to_string(),
+                "pin" if value == "tofu" => options.pin = Some(CertificatePin::TrustOnFirstUse),
+                "pin" => match normalize_fingerprint(&value) {
+                    Some(fingerprint) => {
+                        options.pin = Some(CertificatePin::Fingerprint(fingerprint))
+                    }
+                    None => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("Invalid certificate fingerprint: {}", value),
+                        ))
+                    }
+                },
+                "insecure" => options.insecure = value.is_empty() || value == "true",
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+
+    fn create_connector(&self) -> io::Result<TlsConnector> {
+        let mut builder = TlsConnector::builder();
+
+        for path in &self.ca_files {
+            for cert in read_certificates(path)? {
+                builder.add_root_certificate(cert);
+            }
+        }
+
+        if let Some(path) = &self.identity {
+            let der = fs::read(path).map_err(|e| with_path(path, e))?;
+            let identity = Identity::from_pkcs12(&der, &self.identity_password)
+                .map_err(|e| tls_error(format!("{}: {}", path.to_string_lossy(), e)))?;
+            builder.identity(identity);
+        }
+
+        if self.insecure {
+            builder.danger_accept_invalid_certs(true);
+            builder.danger_accept_invalid_hostnames(true);
+        }
+
+        builder
+            .build()
+            .map_err(|e| tls_error(format!("Failed to initialize TLS: {}", e)))
+    }
+}
+
+fn tls_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+fn with_path(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.to_string_lossy(), e))
+}
+
+/// Read all certificates from a PEM bundle
+fn read_certificates(path: &Path) -> io::Result<Vec<Certificate>> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+
+    let pem = fs::read_to_string(path).map_err(|e| with_path(path, e))?;
+    let certs = pem
+        .match_indices(BEGIN)
+        .map(|(start, _)| {
+            let block = &pem[start..];
+            let end = block[BEGIN.len()..]
+                .find("-----BEGIN")
+                .map(|end| end + BEGIN.len())
+                .unwrap_or(block.len());
+            Certificate::from_pem(&block.as_bytes()[..end])
+                .map_err(|e| tls_error(format!("{}: {}", path.to_string_lossy(), e)))
+        })
+        .collect::<io::Result<Vec<Certificate>>>()?;
+
+    if certs.is_empty() {
+        Err(tls_error(format!(
+            "{}: No certificates found",
+            path.to_string_lossy()
+        )))
+    } else {
+        Ok(certs)
+    }
+}
+
+/// Format a SHA-256 fingerprint like `AB:CD:...`
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+/// Accepts fingerprints with or without separators, in any case
+fn normalize_fingerprint(input: &str) -> Option<String> {
+    let hex: String = input
+        .chars()
+        .filter(|ch| *ch != ':' && *ch != ' ')
+        .map(|ch| ch.to_ascii_uppercase())
+        .collect();
+    if hex.len() != 64 || !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let bytes: Vec<String> = hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| String::from_utf8_lossy(pair).to_string())
+        .collect();
+    Some(bytes.join(":"))
+}
+
+/// Fingerprints remembered for `pin=tofu`, stored as `host:port fingerprint` lines
+#[derive(Default)]
+struct KnownFingerprints {
+    entries: Vec<(String, String)>,
+}
+
+impl KnownFingerprints {
+    fn path() -> Option<PathBuf> {
+        ScriptingManager::config_dir().map(|dir| dir.join(FINGERPRINTS_FILE))
+    }
+
+    fn load() -> Self {
+        match Self::path().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(contents) => Self::parse(&contents),
+            None => Self::default(),
+        }
+    }
+
+    fn parse(contents: &str) -> Self {
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                match (parts.next(), parts.next().and_then(normalize_fingerprint)) {
+                    (Some(host), Some(fingerprint)) => Some((host.to_string(), fingerprint)),
+                    _ => None,
+                }
+            })
+            .collect();
+        Self { entries }
+    }
+
+    fn get(&self, host: &str) -> Option<&String> {
+        self.entries
+            .iter()
+            .find(|(known, _)| known == host)
+            .map(|(_, fingerprint)| fingerprint)
+    }
+
+    fn insert(&mut self, host: String, fingerprint: String) {
+        self.entries.push((host, fingerprint));
+    }
+
+    fn format(&self) -> String {
+        let mut formatted = String::new();
+        for (host, fingerprint) in &self.entries {
+            formatted.push_str(&format!("{} {}\n", host, fingerprint));
+        }
+        formatted
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if let Some(path) = Self::path() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, self.format())
+        } else {
+            Err(tls_error(
+                "Unable to store certificate fingerprint: no config dir".to_string(),
+            ))
+        }
+    }
+}
+
+fn verify_pin(
+    stream: &TlsStream<TcpStream>,
+    host: &str,
+    port: u16,
+    pin: &CertificatePin,
+) -> io::Result<()> {
+    let cert = match stream.peer_certificate() {
+        Ok(Some(cert)) => cert,
+        Ok(None) => return Err(tls_error("Server provided no certificate".to_string())),
+        Err(e) => return Err(tls_error(format!("Unable to read certificate: {}", e))),
+    };
+    let der = cert
+        .to_der()
+        .map_err(|e| tls_error(format!("Unable to read certificate: {}", e)))?;
+    let actual = fingerprint(&der);
+
+    match pin {
+        CertificatePin::Fingerprint(expected) if expected == &actual => Ok(()),
+        CertificatePin::Fingerprint(expected) => Err(tls_error(format!(
+            "Certificate fingerprint mismatch! Expected {} but received {}",
+            expected, actual
+        ))),
+        CertificatePin::TrustOnFirstUse => {
+            let key = format!("{}:{}", host, port);
+            let mut known = KnownFingerprints::load();
+            match known.get(&key) {
+                Some(expected) if expected == &actual => Ok(()),
+                Some(expected) => Err(tls_error(format!(
+                    "Certificate for {} has changed! Expected {} but received {}. If this is \
+                    expected, remove the old entry from {}",
+                    key,
+                    expected,
+                    actual,
+                    KnownFingerprints::path()
+                        .map(|path| path.to_string_lossy().to_string())
+                        .unwrap_or_else(|| FINGERPRINTS_FILE.to_string()),
+                ))),
+                None => {
+                    crate::info!("Trusting certificate for {}: {}", key, actual);
+                    known.insert(key, actual);
+                    known.save()
+                }
+            }
+        }
+    }
+}
+
+pub fn connect(
+    host: &str,
+    port: u16,
+    tcp: TcpStream,
+    options: &TlsOptions,
+) -> io::Result<Box<dyn telnet::Stream>> {
+    let connector = options.create_connector()?;
+    let stream = connector
+        .connect(host, tcp)
+        .map_err(|e| tls_error(format!("TLS handshake failed: {}", e)))?;
+
+    if let Some(pin) = &options.pin {
+        verify_pin(&stream, host, port, pin)?;
+    }
+
+    Ok(Box::new(TlsTelnetStream(stream)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_from_uri() {
+        let uri = Url::parse(
+            "ssl://serenity.co:4000?ca=/tmp/a.pem&ca=/tmp/b.pem&cert=/tmp/me.p12&password=mal&insecure",
+        )
+        .unwrap();
+        let options = TlsOptions::from_uri(&uri).unwrap();
+        assert_eq!(
+            options.ca_files,
+            vec![PathBuf::from("/tmp/a.pem"), PathBuf::from("/tmp/b.pem")]
+        );
+        assert_eq!(options.identity, Some(PathBuf::from("/tmp/me.p12")));
+        assert_eq!(options.identity_password, "mal");
+        assert!(options.insecure);
+        assert_eq!(options.pin, None);
+
+        let uri = Url::parse("ssl://serenity.co:4000?pin=tofu").unwrap();
+        assert_eq!(
+            TlsOptions::from_uri(&uri).unwrap().pin,
+            Some(CertificatePin::TrustOnFirstUse)
+        );
+
+        let uri = Url::parse("ssl://serenity.co:4000?pin=serenity").unwrap();
+        assert!(TlsOptions::from_uri(&uri).is_err());
+    }
+
+    #[test]
+    fn fingerprints() {
+        let expected = "E3:B0:C4:42:98:FC:1C:14:9A:FB:F4:C8:99:6F:B9:24:\
+            27:AE:41:E4:64:9B:93:4C:A4:95:99:1B:78:52:B8:55";
+        assert_eq!(fingerprint(b""), expected);
+        assert_eq!(
+            normalize_fingerprint(
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            ),
+            Some(expected.to_string())
+        );
+    }
+
+    #[test]
+    fn known_fingerprints_round_trip() {
+        let mut known = KnownFingerprints::parse("serenity.co:4000 AB\n");
+        assert!(known.entries.is_empty());
+
+        known.insert("serenity.co:4000".to_string(), fingerprint(b""));
+        let parsed = KnownFingerprints::parse(&known.format());
+        assert_eq!(parsed.get("serenity.co:4000"), Some(&fingerprint(b"")));
+        assert_eq!(parsed.get("serenity.co:4001"), None);
+    }
 }
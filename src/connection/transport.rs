@@ -2,7 +2,7 @@ use std::{io, time::Duration};
 
 use crate::editing::Size;
 
-use super::ReadValue;
+use super::{GmcpMessage, MsdpVariable, ReadValue};
 
 pub trait Transport {
     fn read_timeout(&mut self, duration: Duration) -> io::Result<Option<ReadValue>>;
@@ -13,4 +13,12 @@ pub trait Transport {
     fn resize(&mut self, _new_size: Size) -> io::Result<()> {
         Ok(())
     }
+
+    fn send_gmcp(&mut self, _message: GmcpMessage) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn send_msdp(&mut self, _variable: MsdpVariable) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
 }
@@ -428,6 +428,40 @@ pub mod tests {
             buf.append_value(ReadValue::Text("serenity".into()));
             assert_visual_match(&buf, "serenity");
         }
+
+        #[test]
+        fn replace_line() {
+            let mut buf = MemoryBuffer::new(0);
+            buf.append_value(ReadValue::Text("Take my love".into()));
+            buf.append_value(ReadValue::Newline);
+            buf.append_value(ReadValue::Text("Take my land".into()));
+            buf.append_value(ReadValue::ReplaceLine("serenity".into()));
+            assert_visual_match(&buf, "Take my love\nserenity");
+        }
+
+        #[test]
+        fn prompt_on_own_line() {
+            let mut buf = MemoryBuffer::new(0);
+            buf.append_value(ReadValue::Text("Take my love".into()));
+            buf.append_value(ReadValue::Newline);
+            buf.append_value(ReadValue::Prompt("HP: 42>".into()));
+            buf.append_value(ReadValue::Text("Take my land".into()));
+            assert_visual_match(&buf, "Take my love\nHP: 42>\nTake my land");
+        }
+
+        #[test]
+        fn rewritten_lines() {
+            let mut buf = MemoryBuffer::new(0);
+            buf.append_value(ReadValue::Text("Take my love".into()));
+            buf.append_value(ReadValue::Rewritten(
+                "Take my love".into(),
+                Some("Take my land".into()),
+            ));
+            buf.append_value(ReadValue::Text("The weather is nice".into()));
+            buf.append_value(ReadValue::Rewritten("The weather is nice".into(), None));
+            buf.append_value(ReadValue::Text("serenity".into()));
+            assert_visual_match(&buf, "Take my land\nserenity");
+        }
     }
 
     #[cfg(test)]
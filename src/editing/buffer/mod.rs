@@ -211,6 +211,42 @@ pub trait Buffer: HasId + Send + Sync {
                     text,
                 );
             }
+            ReadValue::ReplaceLine(text) => {
+                let line = self.lines_count().saturating_sub(1);
+                let width = self.get_line_width(line).unwrap_or(0);
+                if width > 0 {
+                    self.delete_range(((line, 0), (line, width)).into());
+                }
+                self.append_value(ReadValue::Text(text));
+            }
+            ReadValue::Prompt(prompt) => {
+                if let BufferSource::ConnectionPromptForBuffer(_) = self.source() {
+                    // Prompt buffers only ever hold the most recent prompt
+                    self.clear();
+                    self.append(prompt.into());
+                } else {
+                    // Without a prompt buffer, the prompt gets a line of its own so
+                    // subsequent output doesn't continue after it
+                    self.append_value(ReadValue::Text(prompt));
+                    self.append_value(ReadValue::Newline);
+                }
+            }
+            ReadValue::Link(text, _) => {
+                self.append_value(ReadValue::Text(text));
+            }
+            ReadValue::Rewritten(_, Some(rewritten)) => {
+                self.append_value(ReadValue::ReplaceLine(rewritten));
+                self.append_value(ReadValue::Newline);
+            }
+            ReadValue::Rewritten(_, None) => {
+                // Gagged; the next line will be received in its place
+                self.append_value(ReadValue::ReplaceLine(TextLine::default()));
+            }
+            ReadValue::Gmcp(_)
+            | ReadValue::Msdp(_)
+            | ReadValue::Mssp(_)
+            | ReadValue::Bell
+            | ReadValue::Title(_) => {} // Not rendered
         };
     }
 
@@ -230,6 +266,7 @@ pub trait Buffer: HasId + Send + Sync {
         match self.source() {
             &BufferSource::Connection(_) => Some(self.id()),
             &BufferSource::ConnectionInputForBuffer(id) => Some(id),
+            &BufferSource::ConnectionPromptForBuffer(id) => Some(id),
             _ => None,
         }
     }
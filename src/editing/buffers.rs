@@ -34,6 +34,30 @@ impl Buffers {
         self.all.iter_mut().find(|buf| buf.id() == id)
     }
 
+    /// Returns the ID of the Buffer holding prompts for the Connection whose
+    /// output goes to the Buffer with the given ID, if any
+    pub fn prompt_buffer_for(&self, connection_buffer_id: Id) -> Option<Id> {
+        self.all
+            .iter()
+            .find(|buf| match buf.source() {
+                &BufferSource::ConnectionPromptForBuffer(id) => id == connection_buffer_id,
+                _ => false,
+            })
+            .map(|buf| buf.id())
+    }
+
+    /// Returns the ID of the input Buffer for the Connection whose output
+    /// goes to the Buffer with the given ID, if any
+    pub fn input_buffer_for(&self, connection_buffer_id: Id) -> Option<Id> {
+        self.all
+            .iter()
+            .find(|buf| match buf.source() {
+                &BufferSource::ConnectionInputForBuffer(id) => id == connection_buffer_id,
+                _ => false,
+            })
+            .map(|buf| buf.id())
+    }
+
     pub fn create(&mut self) -> &Box<dyn Buffer> {
         self.create_for_id();
         self.all.last().unwrap()
@@ -6,6 +6,12 @@ use super::Layout;
 
 pub struct ConnLayout {
     pub output: Box<Window>,
+
+    /// Displays the most recent prompt, if any, between output and input.
+    /// This Window is never focused, and is not considered part of the
+    /// Layout for most purposes (IE: `iter()`)
+    pub prompt: Box<Window>,
+
     pub input: Box<Window>,
 }
 
@@ -58,6 +64,8 @@ impl Layout for ConnLayout {
                     yield_!(&mut self.output);
                 } else if self.input.buffer == buffer_id {
                     yield_!(&mut self.input);
+                } else if self.prompt.buffer == buffer_id {
+                    yield_!(&mut self.prompt);
                 }
             })
             .into_iter(),
@@ -92,7 +100,7 @@ impl Layout for ConnLayout {
     fn size(&self) -> Size {
         Size {
             w: self.output.size.w,
-            h: self.output.size.h + self.input.size.h,
+            h: self.output.size.h + self.prompt.size.h + self.input.size.h,
         }
     }
 
@@ -107,6 +115,10 @@ impl Resizable for ConnLayout {
             w: new_size.w,
             h: new_size.h - 1,
         });
+        self.prompt.resize(Size {
+            w: new_size.w,
+            h: 0,
+        });
         self.input.resize(Size {
             w: new_size.w,
             h: 1,
@@ -20,6 +20,14 @@ pub enum BufferSource {
     /// The Buffer is in-memory only, as None, but serves to provide
     /// input to the Connection in the buffer with the given Id
     ConnectionInputForBuffer(Id),
+
+    /// The Buffer is in-memory only, and holds the most recent prompt
+    /// received by the Connection in the buffer with the given Id
+    ConnectionPromptForBuffer(Id),
+
+    /// The Buffer displays the server status (MSSP) data received from
+    /// the Connection with the given URL
+    ServerStatus(String),
 }
 
 impl BufferSource {
@@ -35,6 +43,7 @@ impl BufferSource {
             BufferSource::Connection(_) => true,
             BufferSource::Help => true,
             BufferSource::Log => true,
+            BufferSource::ServerStatus(_) => true,
 
             _ => false,
         }
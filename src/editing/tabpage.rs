@@ -39,6 +39,16 @@ impl Tabpage {
     pub fn new_connection(&mut self, buffers: &mut Buffers, output_buffer_id: Id) -> ConnLayout {
         let input_buffer = buffers.create_mut();
         input_buffer.set_source(BufferSource::ConnectionInputForBuffer(output_buffer_id));
+        let input_buffer_id = input_buffer.id();
+
+        let prompt_buffer_id = if let Some(existing) = buffers.prompt_buffer_for(output_buffer_id) {
+            existing
+        } else {
+            let prompt_buffer = buffers.create_mut();
+            prompt_buffer.set_source(BufferSource::ConnectionPromptForBuffer(output_buffer_id));
+            prompt_buffer.id()
+        };
+
         let mut output = Box::new(Window::with_focused(
             self.ids.next(),
             output_buffer_id,
@@ -46,10 +56,21 @@ impl Tabpage {
         ));
         output.flags = WindowFlags::PROTECTED;
 
-        let mut input = Box::new(Window::new(self.ids.next(), input_buffer.id()));
+        let mut prompt = Box::new(Window::with_focused(
+            self.ids.next(),
+            prompt_buffer_id,
+            false,
+        ));
+        prompt.flags = WindowFlags::PROTECTED | WindowFlags::LOCKED_BUFFER;
+
+        let mut input = Box::new(Window::new(self.ids.next(), input_buffer_id));
         input.flags = WindowFlags::PROTECTED | WindowFlags::LOCKED_BUFFER;
 
-        ConnLayout { output, input }
+        ConnLayout {
+            output,
+            prompt,
+            input,
+        }
     }
 
     pub fn current_window(&self) -> &Box<Window> {
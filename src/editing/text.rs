@@ -1,6 +1,9 @@
 use std::{cmp::min, ops::Range};
 
-use tui::text::{Span, Spans, Text};
+use tui::{
+    style::Style,
+    text::{Span, Spans, Text},
+};
 
 pub type TextLine = Spans<'static>;
 pub type TextLines = Text<'static>;
@@ -13,6 +16,8 @@ pub trait EditableLine {
         predicate: P,
     ) -> Option<usize>;
     fn replacing_range(self, range: Range<usize>, replacement: &mut TextLine) -> TextLine;
+    fn split_at(self, col: usize) -> (TextLine, TextLine);
+    fn patch_style(self, range: Range<usize>, style: Style) -> TextLine;
     fn subs(&self, start: usize, end: usize) -> Self;
     fn starts_with(&self, s: &str) -> bool;
     fn ends_with(&self, s: &str) -> bool;
@@ -36,6 +41,46 @@ impl EditableLine for TextLine {
         return with_replacement;
     }
 
+    /// Split into the text before the given (char) column and the text from it,
+    /// splitting the span containing that column if necessary
+    fn split_at(self, col: usize) -> (TextLine, TextLine) {
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        let mut offset = 0;
+        for span in self.0 {
+            let len = span.content.chars().count();
+            if offset + len <= col {
+                before.push(span);
+            } else if offset >= col {
+                after.push(span);
+            } else {
+                let split = span
+                    .content
+                    .char_indices()
+                    .nth(col - offset)
+                    .map_or(span.content.len(), |(i, _)| i);
+                let (head, tail) = span.content.split_at(split);
+                before.push(Span::styled(head.to_string(), span.style));
+                after.push(Span::styled(tail.to_string(), span.style));
+            }
+            offset += len;
+        }
+        (TextLine::from(before), TextLine::from(after))
+    }
+
+    /// Apply the given style on top of the existing styles of the text
+    /// within the given range, leaving everything outside it untouched
+    fn patch_style(self, range: Range<usize>, style: Style) -> TextLine {
+        let (mut before, rest) = self.split_at(range.start);
+        let (mut within, mut after) = rest.split_at(range.end.saturating_sub(range.start));
+        for span in &mut within.0 {
+            span.style = span.style.patch(style);
+        }
+        before.append(&mut within);
+        before.append(&mut after);
+        before
+    }
+
     fn position<P: Fn(char) -> bool>(
         &self,
         search_range: Range<usize>,
@@ -271,4 +316,35 @@ mod tests {
             assert_eq!(line.ends_with("serenitude"), false);
         }
     }
+
+    #[cfg(test)]
+    mod patch_style {
+        use tui::style::{Color, Modifier};
+
+        use super::*;
+
+        #[test]
+        fn split_within_span() {
+            let line: TextLine = "言葉 love".into();
+            let (before, after) = line.split_at(1);
+            assert_eq!(before, TextLine::from("言"));
+            assert_eq!(after, TextLine::from("葉 love"));
+        }
+
+        #[test]
+        fn preserves_spans_outside_range() {
+            let red = Style::default().fg(Color::Red);
+            let bold = Style::default().add_modifier(Modifier::BOLD);
+            let line = TextLine::from(vec![Span::styled("Take my ", red), Span::raw("love")]);
+            assert_eq!(
+                line.patch_style(5..10, bold),
+                TextLine::from(vec![
+                    Span::styled("Take ", red),
+                    Span::styled("my ", red.patch(bold)),
+                    Span::styled("lo", bold),
+                    Span::raw("ve"),
+                ])
+            );
+        }
+    }
 }
@@ -24,6 +24,10 @@ bitflags! {
 
         /// A LOCKED_BUFFER window may not have its buffer changed
         const LOCKED_BUFFER = 0b10;
+
+        /// A SHOW_GAGGED window shows connection output as it was received,
+        /// before any gags or substitutions were applied
+        const SHOW_GAGGED = 0b100;
     }
 }
 
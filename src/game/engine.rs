@@ -1,8 +1,10 @@
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use crate::connection::ReadValue;
-use crate::editing::text::EditableLine;
+use crate::connection::{GmcpMessage, MsdpValue, MsdpVariable, ReadValue};
+use crate::editing::text::{EditableLine, TextLine};
 use crate::input::completion::{
     BoxedSuggestions, CompletableContext, Completer, CompletionContext,
 };
@@ -10,14 +12,41 @@ use crate::input::history::History;
 use crate::input::maps::KeyResult;
 
 use super::completion::{CompletionSource, GameCompletionsFactory, ProcessFlags};
+use super::listeners::Listeners;
 use super::processing::alias::Alias;
-use super::processing::manager::TextProcessorManager;
-use super::processing::{ProcessedText, TextInput, TextProcessor};
+use super::processing::highlight::Highlight;
+use super::processing::manager::{ManagedProcessors, TextProcessorManager};
+use super::processing::trigger::{
+    FiredTrigger, FiredTriggers, SequenceLimits, SequenceTrigger, Trigger, TriggerAction,
+};
+use super::processing::{ProcessedText, ProcessorKind, TextInput, TextProcessor};
+use super::speedwalk::Speedwalk;
+use super::stacking::CommandStacking;
 
 pub struct GameEngine {
     pub aliases: TextProcessorManager<Alias>,
+    pub triggers: TextProcessorManager<Trigger>,
+    pub sequence_triggers: TextProcessorManager<SequenceTrigger>,
+    fired_triggers: FiredTriggers,
+
+    /// The text of the current received line so far, for Triggers
+    received_line: TextLine,
+
+    /// Gags and substitutions applied to received text
+    pub substitutions: TextProcessorManager<Alias>,
+
+    /// Styles applied to received text, after any substitutions
+    pub highlights: TextProcessorManager<Highlight>,
+
     pub completer: Option<Arc<Mutex<dyn CompletionSource + Send>>>,
     pub history: Option<History<String>>,
+    pub stacking: CommandStacking,
+    pub speedwalk: Speedwalk,
+    pub gmcp: Listeners<GmcpMessage>,
+    pub msdp: Listeners<MsdpVariable>,
+
+    /// The most recently-received values of each MSDP variable
+    pub msdp_values: HashMap<String, MsdpValue>,
 }
 
 impl Completer for Rc<Mutex<dyn CompletionSource>> {
@@ -46,8 +75,19 @@ impl Default for GameEngine {
     fn default() -> Self {
         Self {
             aliases: TextProcessorManager::new(),
+            triggers: TextProcessorManager::new(),
+            sequence_triggers: TextProcessorManager::new(),
+            fired_triggers: Default::default(),
+            received_line: Default::default(),
+            substitutions: TextProcessorManager::new(),
+            highlights: TextProcessorManager::new(),
             completer: Some(Arc::new(Mutex::new(GameCompletionsFactory::create()))),
             history: Some(Default::default()),
+            stacking: Default::default(),
+            speedwalk: Default::default(),
+            gmcp: Default::default(),
+            msdp: Default::default(),
+            msdp_values: Default::default(),
         }
     }
 }
@@ -62,9 +102,211 @@ impl GameEngine {
             }
         }
 
+        match &value {
+            ReadValue::Text(text) | ReadValue::Link(text, _) => {
+                self.received_line.append(&mut text.clone());
+            }
+            ReadValue::ReplaceLine(text) => self.received_line = text.clone(),
+            ReadValue::Newline => {
+                // NOTE: A line may arrive across several reads (or be rewritten by
+                // a ReplaceLine) so it's only processed once it's complete
+                let line = std::mem::take(&mut self.received_line);
+                self.process_received_line(line.clone());
+                return Some(self.rewrite_received(line));
+            }
+            _ => {}
+        }
+
         Some(value)
     }
 
+    /// Feed a complete received line to any Triggers
+    fn process_received_line(&mut self, line: TextLine) {
+        if let Err(e) = self
+            .triggers
+            .process_received(TextInput::Line(line.clone()))
+        {
+            crate::log_error!("Processing triggers: {:?}", e);
+        }
+        if let Err(e) = self
+            .sequence_triggers
+            .process_received(TextInput::Line(line))
+        {
+            crate::log_error!("Processing sequence triggers: {:?}", e);
+        }
+    }
+
+    /// Apply any gags, substitutions, and highlights to a complete received line,
+    /// returning the value that should end it
+    fn rewrite_received(&mut self, line: TextLine) -> ReadValue {
+        let (rewritten, substituted) = match self
+            .substitutions
+            .process_once(TextInput::Line(line.clone()))
+        {
+            Ok(ProcessedText::Processed(TextInput::Line(line), _)) => (Some(line), true),
+            Ok(ProcessedText::Removed(_)) => (None, true),
+            Ok(ProcessedText::Unprocessed(TextInput::Line(line))) => (Some(line), false),
+            Ok(_) => return ReadValue::Newline,
+            Err(e) => {
+                crate::log_error!("Processing substitutions: {:?}", e);
+                return ReadValue::Newline;
+            }
+        };
+
+        let (rewritten, highlighted) = match rewritten {
+            Some(line) => match self.highlights.process_once(TextInput::Line(line.clone())) {
+                Ok(ProcessedText::Processed(TextInput::Line(line), _)) => (Some(line), true),
+                Ok(_) => (Some(line), false),
+                Err(e) => {
+                    crate::log_error!("Processing highlights: {:?}", e);
+                    (Some(line), false)
+                }
+            },
+            None => (None, false),
+        };
+
+        if substituted || highlighted {
+            ReadValue::Rewritten(line, rewritten)
+        } else {
+            ReadValue::Newline
+        }
+    }
+
+    /// Called periodically while nothing is being received, so that partial
+    /// SequenceTriggers can time out even if the server has gone quiet
+    pub fn process_idle(&mut self) {
+        self.sequence_triggers.expire(Instant::now());
+    }
+
+    /// Register a Trigger whose action will be invoked with the captures
+    /// whenever received text matches `pattern`
+    pub fn insert_trigger(
+        &mut self,
+        pattern: String,
+        action_description: String,
+        action: TriggerAction,
+        one_shot: bool,
+    ) -> KeyResult {
+        let trigger = Trigger::compile(
+            pattern.to_string(),
+            action_description,
+            action,
+            one_shot,
+            self.fired_triggers.clone(),
+        )?;
+        self.triggers.insert(pattern, trigger);
+        Ok(())
+    }
+
+    /// Register a SequenceTrigger whose action will be invoked with the captures
+    /// accumulated across the lines that match each of `patterns`, in order
+    pub fn insert_sequence_trigger(
+        &mut self,
+        patterns: Vec<String>,
+        limits: SequenceLimits,
+        action_description: String,
+        action: TriggerAction,
+        one_shot: bool,
+    ) -> KeyResult {
+        let trigger = SequenceTrigger::compile(
+            patterns,
+            limits,
+            action_description,
+            action,
+            one_shot,
+            self.fired_triggers.clone(),
+        )?;
+        self.sequence_triggers
+            .insert(trigger.describe().to_string(), trigger);
+        Ok(())
+    }
+
+    /// Take the Triggers that have matched received text since the last call.
+    /// Their actions should be run without holding the lock on this Engine.
+    pub fn take_fired_triggers(&mut self) -> Vec<FiredTrigger> {
+        self.fired_triggers.take()
+    }
+
+    /// The managers holding processors of the given kind
+    pub fn processors(&self, kind: ProcessorKind) -> Vec<&dyn ManagedProcessors> {
+        match kind {
+            ProcessorKind::Alias => vec![&self.aliases],
+            ProcessorKind::Trigger => vec![&self.triggers, &self.sequence_triggers],
+            ProcessorKind::Substitution => vec![&self.substitutions],
+            ProcessorKind::Highlight => vec![&self.highlights],
+        }
+    }
+
+    pub fn processors_mut(&mut self, kind: ProcessorKind) -> Vec<&mut dyn ManagedProcessors> {
+        match kind {
+            ProcessorKind::Alias => vec![&mut self.aliases],
+            ProcessorKind::Trigger => vec![&mut self.triggers, &mut self.sequence_triggers],
+            ProcessorKind::Substitution => vec![&mut self.substitutions],
+            ProcessorKind::Highlight => vec![&mut self.highlights],
+        }
+    }
+
+    /// Apply `f` to the manager holding the processor of the given kind with the given
+    /// description, if any. Returns false if there is no such processor
+    pub fn with_processor(
+        &mut self,
+        kind: ProcessorKind,
+        description: &str,
+        f: impl FnOnce(&mut dyn ManagedProcessors),
+    ) -> bool {
+        match self
+            .processors_mut(kind)
+            .into_iter()
+            .find(|manager| manager.contains(description))
+        {
+            Some(manager) => {
+                f(manager);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Enable or disable the named group of processors of every kind
+    pub fn set_group_enabled(&mut self, group: &str, enabled: bool) {
+        for kind in ProcessorKind::ALL {
+            for manager in self.processors_mut(kind) {
+                manager.set_group_enabled(group, enabled);
+            }
+        }
+    }
+
+    /// The names of all groups used by any processor, along with whether each is enabled
+    pub fn groups(&self) -> Vec<(String, bool)> {
+        let mut groups: Vec<(String, bool)> = ProcessorKind::ALL
+            .iter()
+            .flat_map(|kind| self.processors(*kind))
+            .flat_map(|manager| {
+                manager.groups().into_iter().map(move |group| {
+                    let enabled = manager.is_group_enabled(&group);
+                    (group, enabled)
+                })
+            })
+            .collect();
+        groups.sort();
+        groups.dedup();
+        groups
+    }
+
+    /// Split a line of user input into the individual commands to send (which
+    /// will each be passed through [Self::process_to_send] when sent), expanding any
+    /// speedwalks along the way
+    pub fn expand_input(&self, input: &str) -> KeyResult<Vec<String>> {
+        let mut commands = vec![];
+        for command in self.stacking.split(input)? {
+            match self.speedwalk.expand(&command)? {
+                Some(expanded) => commands.extend(expanded),
+                None => commands.push(command),
+            }
+        }
+        Ok(commands)
+    }
+
     pub fn process_to_send(&mut self, value: String) -> KeyResult<Option<String>> {
         if let Some(completions) = self.completer.as_mut() {
             let text = value.to_string();
@@ -93,5 +335,136 @@ impl GameEngine {
     /// a script, for example, to clear previously-created state
     pub fn reset(&mut self) {
         self.aliases.clear();
+        self.triggers.clear();
+        self.sequence_triggers.clear();
+        self.fired_triggers.take();
+        self.substitutions.clear();
+        self.highlights.clear();
+        self.gmcp.clear();
+        self.msdp.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers_see_complete_lines() {
+        let mut engine = GameEngine::default();
+        engine
+            .insert_trigger(
+                "^You see a $1 here".to_string(),
+                "test".to_string(),
+                Arc::new(|_| Ok(())),
+                false,
+            )
+            .unwrap();
+
+        engine.process_received(ReadValue::Text("You see a sw".into()));
+        engine.process_received(ReadValue::Text("ord here".into()));
+        assert!(engine.take_fired_triggers().is_empty());
+
+        engine.process_received(ReadValue::Newline);
+        let fired = engine.take_fired_triggers();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].captures["1"], "sword");
+    }
+
+    #[test]
+    fn triggers_see_replaced_lines() {
+        let mut engine = GameEngine::default();
+        engine
+            .insert_trigger(
+                "^Loading done".to_string(),
+                "test".to_string(),
+                Arc::new(|_| Ok(())),
+                false,
+            )
+            .unwrap();
+
+        engine.process_received(ReadValue::Text("Loading...".into()));
+        engine.process_received(ReadValue::ReplaceLine("Loading done".into()));
+        engine.process_received(ReadValue::Newline);
+        assert_eq!(engine.take_fired_triggers().len(), 1);
+    }
+
+    #[test]
+    fn sequence_triggers_see_complete_lines() {
+        let mut engine = GameEngine::default();
+        engine
+            .insert_sequence_trigger(
+                vec!["^You see a $1 here".to_string(), "^Exits".to_string()],
+                Default::default(),
+                "test".to_string(),
+                Arc::new(|_| Ok(())),
+                false,
+            )
+            .unwrap();
+
+        for value in [
+            ReadValue::Text("You see a ".into()),
+            ReadValue::Text("sword".into()),
+            ReadValue::Text(" here".into()),
+            ReadValue::Newline,
+            ReadValue::Text("Exits: north".into()),
+            ReadValue::Newline,
+        ] {
+            engine.process_received(value);
+        }
+
+        let fired = engine.take_fired_triggers();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].captures["1"], "sword");
+    }
+
+    #[test]
+    fn gag_whole_lines() {
+        let mut engine = GameEngine::default();
+        engine
+            .substitutions
+            .insert_gag("weather is".to_string())
+            .unwrap();
+
+        let text = |s: &'static str| ReadValue::Text(s.into());
+        assert_eq!(
+            engine.process_received(text("The weather ")),
+            Some(text("The weather "))
+        );
+        assert_eq!(
+            engine.process_received(text("is nice")),
+            Some(text("is nice"))
+        );
+        match engine.process_received(ReadValue::Newline) {
+            Some(ReadValue::Rewritten(from, None)) => {
+                assert_eq!(from.to_string(), "The weather is nice");
+            }
+            unexpected => panic!("Expected gagged Rewritten; got {:?}", unexpected),
+        }
+
+        engine.process_received(text("Take my love"));
+        assert_eq!(
+            engine.process_received(ReadValue::Newline),
+            Some(ReadValue::Newline)
+        );
+    }
+
+    #[test]
+    fn keep_original_of_substitutions() {
+        let mut engine = GameEngine::default();
+        engine
+            .substitutions
+            .insert_text("love".to_string(), "land".to_string())
+            .unwrap();
+
+        engine.process_received(ReadValue::Text("Take my lo".into()));
+        engine.process_received(ReadValue::Text("ve".into()));
+        match engine.process_received(ReadValue::Newline) {
+            Some(ReadValue::Rewritten(from, Some(to))) => {
+                assert_eq!(from.to_string(), "Take my love");
+                assert_eq!(to.to_string(), "Take my land");
+            }
+            unexpected => panic!("Expected Rewritten; got {:?}", unexpected),
+        }
     }
 }
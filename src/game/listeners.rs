@@ -0,0 +1,86 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::input::maps::KeyResult;
+
+pub type Listener<T> = Arc<dyn Fn(T) -> KeyResult + Send + Sync>;
+
+/// Listeners are registered for a (case-insensitive) dotted key, like a
+/// GMCP package name. A listener registered for `Char` will be notified
+/// for `Char` as well as `Char.Vitals`, etc.
+pub struct Listeners<T> {
+    by_key: HashMap<String, Vec<Listener<T>>>,
+}
+
+impl<T> Default for Listeners<T> {
+    fn default() -> Self {
+        Self {
+            by_key: Default::default(),
+        }
+    }
+}
+
+impl<T> Listeners<T> {
+    pub fn clear(&mut self) {
+        self.by_key.clear();
+    }
+
+    pub fn insert(&mut self, key: &str, listener: Listener<T>) {
+        self.by_key
+            .entry(key.to_lowercase())
+            .or_default()
+            .push(listener);
+    }
+
+    /// Returns all the listeners that should be notified for the given key.
+    /// Listeners are returned (rather than invoked directly) so they may be
+    /// called without holding a lock on whatever owns this object.
+    pub fn matching(&self, key: &str) -> Vec<Listener<T>> {
+        let key = key.to_lowercase();
+        let mut matching = vec![];
+        let mut candidate = key.as_str();
+        loop {
+            if let Some(listeners) = self.by_key.get(candidate) {
+                matching.extend(listeners.iter().cloned());
+            }
+
+            if let Some(index) = candidate.rfind('.') {
+                candidate = &candidate[..index];
+            } else {
+                break;
+            }
+        }
+        matching
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn matches_parent_keys() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let mut listeners: Listeners<String> = Default::default();
+        for key in &["Char", "char.vitals", "Room"] {
+            let received = received.clone();
+            let key = key.to_string();
+            listeners.insert(
+                &key.clone(),
+                Arc::new(move |_| {
+                    received.lock().unwrap().push(key.clone());
+                    Ok(())
+                }),
+            );
+        }
+
+        for listener in listeners.matching("Char.Vitals") {
+            listener("".to_string()).unwrap();
+        }
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(received, vec!["Char", "char.vitals"]);
+    }
+}
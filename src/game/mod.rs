@@ -1,3 +1,6 @@
 pub mod completion;
 pub mod engine;
+pub mod listeners;
 pub mod processing;
+pub mod speedwalk;
+pub mod stacking;
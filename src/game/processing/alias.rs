@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::fmt::Display;
 
+use tui::{style::Style, text::Span};
+
 use crate::{
     editing::text::{EditableLine, TextLine},
     input::maps::KeyResult,
@@ -22,12 +24,21 @@ pub struct Alias {
 
 impl Alias {
     pub fn compile_text(input: String, replacement: String) -> KeyResult<Alias> {
+        Self::compile_styled(input, replacement, Style::default())
+    }
+
+    pub fn compile_styled(input: String, replacement: String, style: Style) -> KeyResult<Alias> {
         Self::compile_fn(
             input,
-            SubstitutionProcessor { replacement }.into_processor(),
+            SubstitutionProcessor { replacement, style }.into_processor(),
         )
     }
 
+    /// An Alias that removes any input it matches entirely
+    pub fn compile_gag(input: String) -> KeyResult<Alias> {
+        Self::compile_fn(input, Box::new(|_| Ok(None)))
+    }
+
     pub fn compile_fn(input: String, processor: Box<Processor>) -> KeyResult<Alias> {
         Ok(Alias {
             matcher: Matcher::compile(input)?,
@@ -81,11 +92,15 @@ impl TextProcessor for Alias {
 
 struct SubstitutionProcessor {
     replacement: String,
+    style: Style,
 }
 
 impl SubstitutionProcessor {
     pub fn into_processor(self) -> Box<Processor> {
-        Box::new(move |m| Ok(Some(m.expand(&self.replacement).into())))
+        Box::new(move |m| {
+            let expanded = m.expand(&self.replacement);
+            Ok(Some(Span::styled(expanded, self.style).into()))
+        })
     }
 }
 
@@ -96,6 +111,23 @@ impl TextProcessorManager<Alias> {
         Ok(())
     }
 
+    pub fn insert_styled(
+        &mut self,
+        pattern: String,
+        replacement: String,
+        style: Style,
+    ) -> KeyResult {
+        let alias = Alias::compile_styled(pattern.to_string(), replacement, style)?;
+        self.insert(pattern, alias);
+        Ok(())
+    }
+
+    pub fn insert_gag(&mut self, pattern: String) -> KeyResult {
+        let alias = Alias::compile_gag(pattern.to_string())?;
+        self.insert(pattern, alias);
+        Ok(())
+    }
+
     pub fn insert_fn(
         &mut self,
         pattern: String,
@@ -172,6 +204,34 @@ mod tests {
             assert_eq!(flags, ProcessedTextFlags::NONE);
         }
 
+        #[test]
+        fn styled_replacement() {
+            let style = Style::default().add_modifier(tui::style::Modifier::BOLD);
+            let mut alias =
+                Alias::compile_styled("it rains".to_string(), "RAIN".to_string(), style)
+                    .expect("Alias should compile!");
+            match alias.process(TextInput::Line("Look, it rains.".into())) {
+                Ok(ProcessedText::Processed(TextInput::Line(output), _)) => assert_eq!(
+                    output,
+                    TextLine::from(vec![
+                        Span::raw("Look, "),
+                        Span::styled("RAIN", style),
+                        Span::raw("."),
+                    ])
+                ),
+                unexpected => panic!("Expected Processed result; got {:?}", unexpected),
+            }
+        }
+
+        #[test]
+        fn gag_removes() {
+            let mut alias = Alias::compile_gag("rains".to_string()).expect("Alias should compile!");
+            assert!(matches!(
+                alias.process(TextInput::Line("It rains.".into())),
+                Ok(ProcessedText::Removed(_))
+            ));
+        }
+
         #[test]
         fn after_line_start() {
             let alias = Alias::compile_text("cook $1".to_string(), "heat up $1".to_string())
@@ -183,6 +243,8 @@ mod tests {
 
     #[cfg(test)]
     mod manager {
+        use crate::game::processing::manager::ManagedProcessors;
+
         use super::*;
 
         fn define(manager: &mut TextProcessorManager<Alias>, pattern: &str, replacement: &str) {
@@ -200,5 +262,51 @@ mod tests {
             let (output, _) = process(manager, "cook chorizo");
             assert_eq!(output, "Put chorizo into a HOT pan");
         }
+
+        fn process_once(manager: &mut TextProcessorManager<Alias>, input: &'static str) -> String {
+            match manager.process_once(TextInput::Line(input.into())) {
+                Ok(ProcessedText::Processed(TextInput::Line(output), _)) => output.to_string(),
+                Ok(ProcessedText::Unprocessed(TextInput::Line(output))) => output.to_string(),
+                unexpected => panic!("Expected a line; got {:?}", unexpected),
+            }
+        }
+
+        #[test]
+        fn priority_order() {
+            let mut manager: TextProcessorManager<Alias> = TextProcessorManager::new();
+            define(&mut manager, "pan", "wok");
+            define(&mut manager, "wok", "grill");
+            assert_eq!(process_once(&mut manager, "pan"), "grill");
+
+            assert!(manager.set_priority("wok", 10));
+            assert_eq!(process_once(&mut manager, "pan"), "wok");
+            assert_eq!(
+                manager.iter().map(|(d, _)| d.as_str()).collect::<Vec<_>>(),
+                vec!["wok", "pan"]
+            );
+
+            assert!(!manager.set_priority("grill", 10));
+        }
+
+        #[test]
+        fn disabled_items_and_groups() {
+            let mut manager: TextProcessorManager<Alias> = TextProcessorManager::new();
+            define(&mut manager, "cook", "braise");
+            define(&mut manager, "chorizo", "sausage");
+            assert!(manager.set_group("cook", Some("kitchen".to_string())));
+            assert!(manager.set_group("chorizo", Some("kitchen".to_string())));
+
+            manager.set_group_enabled("kitchen", false);
+            assert_eq!(process_once(&mut manager, "cook chorizo"), "cook chorizo");
+            assert!(manager
+                .info()
+                .iter()
+                .all(|info| info.enabled && !info.active));
+
+            manager.set_group_enabled("kitchen", true);
+            assert!(manager.set_enabled("chorizo", false));
+            assert_eq!(process_once(&mut manager, "cook chorizo"), "braise chorizo");
+            assert_eq!(manager.groups(), vec!["kitchen"]);
+        }
     }
 }
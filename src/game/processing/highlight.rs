@@ -0,0 +1,121 @@
+use tui::style::Style;
+
+use crate::{
+    editing::text::{EditableLine, TextLine},
+    input::maps::KeyResult,
+};
+
+use super::{
+    manager::TextProcessorManager, matcher::Matcher, ProcessedText, ProcessedTextFlags, TextInput,
+    TextProcessor,
+};
+
+pub struct Highlight {
+    matcher: Matcher,
+    style: Style,
+    whole_line: bool,
+}
+
+impl Highlight {
+    pub fn compile(input: String, style: Style, whole_line: bool) -> KeyResult<Highlight> {
+        Ok(Highlight {
+            matcher: Matcher::compile(input)?,
+            style,
+            whole_line,
+        })
+    }
+
+    fn apply(&self, line: TextLine) -> Option<TextLine> {
+        let found = self.matcher.find_all(&line);
+        if found.is_empty() {
+            return None;
+        }
+
+        if self.whole_line {
+            let width = line.width();
+            return Some(line.patch_style(0..width, self.style));
+        }
+
+        let mut line = line;
+        for m in found {
+            line = line.patch_style(m.start..m.end, self.style);
+        }
+        Some(line)
+    }
+}
+
+impl TextProcessor for Highlight {
+    fn describe(&self) -> &str {
+        &self.matcher.description
+    }
+
+    fn process(&mut self, input: TextInput) -> KeyResult<ProcessedText> {
+        match input {
+            TextInput::Newline => Ok(ProcessedText::Unprocessed(input)),
+            TextInput::Line(line) => match self.apply(line.clone()) {
+                Some(highlighted) => Ok(ProcessedText::Processed(
+                    TextInput::Line(highlighted),
+                    ProcessedTextFlags::NONE,
+                )),
+                None => Ok(ProcessedText::Unprocessed(TextInput::Line(line))),
+            },
+        }
+    }
+}
+
+impl TextProcessorManager<Highlight> {
+    pub fn insert_style(&mut self, pattern: String, style: Style, whole_line: bool) -> KeyResult {
+        let highlight = Highlight::compile(pattern.to_string(), style, whole_line)?;
+        self.insert(pattern, highlight);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tui::{
+        style::{Color, Modifier},
+        text::Span,
+    };
+
+    use super::*;
+
+    fn highlight(pattern: &str, style: Style, whole_line: bool, line: TextLine) -> TextLine {
+        let mut highlight = Highlight::compile(pattern.to_string(), style, whole_line)
+            .expect("Highlight should compile");
+        match highlight.process(TextInput::Line(line)) {
+            Ok(ProcessedText::Processed(TextInput::Line(output), _)) => output,
+            unexpected => panic!("Expected Processed result; got {:?}", unexpected),
+        }
+    }
+
+    #[test]
+    fn every_match() {
+        let yellow = Style::default().fg(Color::Yellow);
+        let output = highlight(r"/\d+/", yellow, false, "Hits for 12 and 7".into());
+        assert_eq!(
+            output,
+            TextLine::from(vec![
+                Span::raw("Hits for "),
+                Span::styled("12", yellow),
+                Span::raw(" and "),
+                Span::styled("7", yellow),
+            ])
+        );
+    }
+
+    #[test]
+    fn whole_line_keeps_server_colors() {
+        let red = Style::default().fg(Color::Red);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let line = TextLine::from(vec![Span::styled("Joe", red), Span::raw(" says hi")]);
+        let output = highlight("says", bold, true, line);
+        assert_eq!(
+            output,
+            TextLine::from(vec![
+                Span::styled("Joe", red.patch(bold)),
+                Span::styled(" says hi", bold),
+            ])
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 
 use crate::input::{maps::KeyResult, KeyError};
 
@@ -6,38 +6,135 @@ use super::{ProcessedText, ProcessedTextFlags, TextInput, TextProcessor};
 
 const MAX_ITERATIONS: usize = 50;
 
+/// A processor along with the settings that decide whether and when it runs
+struct ManagedProcessor<T> {
+    description: String,
+    processor: T,
+    priority: i32,
+    group: Option<String>,
+    enabled: bool,
+}
+
+/// A summary of a managed processor's settings, for display
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessorInfo {
+    pub description: String,
+    pub priority: i32,
+    pub group: Option<String>,
+    pub enabled: bool,
+
+    /// Whether the processor will actually run; that is, it is enabled and
+    /// its group (if any) is not disabled
+    pub active: bool,
+}
+
+/// Operations on a [TextProcessorManager] that don't depend on the type of processor
+/// it holds, so managers for different processor types can be handled together
+pub trait ManagedProcessors {
+    fn contains(&self, description: &str) -> bool;
+
+    /// Returns false if there is no processor with the given description
+    fn set_enabled(&mut self, description: &str, enabled: bool) -> bool;
+
+    /// Processors with a higher priority run first; processors with the same priority
+    /// run in the order they were defined. Returns false if there is no processor with
+    /// the given description
+    fn set_priority(&mut self, description: &str, priority: i32) -> bool;
+
+    /// Returns false if there is no processor with the given description
+    fn set_group(&mut self, description: &str, group: Option<String>) -> bool;
+
+    /// Enable or disable every processor in `group` as a unit, without affecting their
+    /// own enabled flags. This also applies to processors added to the group later
+    fn set_group_enabled(&mut self, group: &str, enabled: bool);
+
+    fn is_group_enabled(&self, group: &str) -> bool;
+
+    /// The names of all groups with at least one processor
+    fn groups(&self) -> Vec<String>;
+
+    /// All processors, in the order they run
+    fn info(&self) -> Vec<ProcessorInfo>;
+}
+
 pub struct TextProcessorManager<T: TextProcessor> {
-    processors: HashMap<String, T>,
+    /// Kept sorted in the order processors should run
+    processors: Vec<ManagedProcessor<T>>,
+    disabled_groups: HashSet<String>,
 }
 
 impl<T: TextProcessor> TextProcessorManager<T> {
     pub fn new() -> Self {
         Self {
             processors: Default::default(),
+            disabled_groups: Default::default(),
         }
     }
 
     pub fn clear(&mut self) {
         self.processors.clear();
+        self.disabled_groups.clear();
     }
 
+    /// Insert a processor at the default priority. Replacing an existing processor with
+    /// the same description keeps its priority, group, and enabled flag
     pub fn insert(&mut self, description: String, processor: T) -> Option<T> {
-        self.processors.insert(description, processor)
+        if let Some(existing) = self.find_mut(&description) {
+            return Some(std::mem::replace(&mut existing.processor, processor));
+        }
+
+        self.processors.push(ManagedProcessor {
+            description,
+            processor,
+            priority: 0,
+            group: None,
+            enabled: true,
+        });
+        self.sort();
+        None
+    }
+
+    pub fn remove(&mut self, description: &str) -> Option<T> {
+        let index = self
+            .processors
+            .iter()
+            .position(|p| p.description == description)?;
+        Some(self.processors.remove(index).processor)
+    }
+
+    /// All processors, in the order they run
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &T)> {
+        self.processors
+            .iter()
+            .map(|p| (&p.description, &p.processor))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut T)> {
+        self.processors
+            .iter_mut()
+            .map(|p| (&p.description, &mut p.processor))
     }
 
-    fn process_once(&mut self, input: TextInput) -> KeyResult<ProcessedText> {
+    /// Offer the input to each active processor a single time, in order. Unlike
+    /// [TextProcessor::process], output is not re-processed, so a processor can never
+    /// match its own output
+    pub fn process_once(&mut self, input: TextInput) -> KeyResult<ProcessedText> {
         let mut to_process = Some(input);
         let mut any_processed = false;
         let mut to_remove = vec![];
 
-        for (id, processor) in self.processors.iter_mut() {
+        for (index, managed) in self.processors.iter_mut().enumerate() {
+            if !managed.is_active(&self.disabled_groups) {
+                continue;
+            }
+
             let to_consume = if let Some(processable) = to_process.take() {
                 processable
             } else {
                 break;
             };
 
-            let (processed, flags) = match processor.process(to_consume)? {
+            let (processed, flags) = match managed.processor.process(to_consume)? {
                 ProcessedText::Unprocessed(unprocessed) => {
                     to_process = Some(unprocessed);
                     (false, ProcessedTextFlags::NONE)
@@ -52,12 +149,12 @@ impl<T: TextProcessor> TextProcessorManager<T> {
             any_processed |= processed;
 
             if flags.contains(ProcessedTextFlags::DESTROYED) {
-                to_remove.push(id.to_string());
+                to_remove.push(index);
             }
         }
 
-        for id in to_remove {
-            self.processors.remove(&id);
+        for index in to_remove.into_iter().rev() {
+            self.processors.remove(index);
         }
 
         Ok(if !any_processed {
@@ -68,6 +165,106 @@ impl<T: TextProcessor> TextProcessorManager<T> {
             ProcessedText::Removed(ProcessedTextFlags::NONE)
         })
     }
+
+    fn find_mut(&mut self, description: &str) -> Option<&mut ManagedProcessor<T>> {
+        self.processors
+            .iter_mut()
+            .find(|p| p.description == description)
+    }
+
+    fn sort(&mut self) {
+        // NOTE: sort_by_key is stable, so equal priorities keep their definition order
+        self.processors.sort_by_key(|p| -(p.priority as i64));
+    }
+}
+
+impl<T> ManagedProcessor<T> {
+    fn is_active(&self, disabled_groups: &HashSet<String>) -> bool {
+        self.enabled
+            && match &self.group {
+                Some(group) => !disabled_groups.contains(group),
+                None => true,
+            }
+    }
+}
+
+impl<T: TextProcessor> ManagedProcessors for TextProcessorManager<T> {
+    fn contains(&self, description: &str) -> bool {
+        self.processors.iter().any(|p| p.description == description)
+    }
+
+    fn set_enabled(&mut self, description: &str, enabled: bool) -> bool {
+        if let Some(managed) = self.find_mut(description) {
+            managed.enabled = enabled;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn set_priority(&mut self, description: &str, priority: i32) -> bool {
+        let index = match self
+            .processors
+            .iter()
+            .position(|p| p.description == description)
+        {
+            Some(index) => index,
+            None => return false,
+        };
+
+        // Re-prioritizing moves the processor after any others at the new priority,
+        // as if it had just been defined there
+        let mut managed = self.processors.remove(index);
+        managed.priority = priority;
+        self.processors.push(managed);
+        self.sort();
+        true
+    }
+
+    fn set_group(&mut self, description: &str, group: Option<String>) -> bool {
+        if let Some(managed) = self.find_mut(description) {
+            managed.group = group;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn set_group_enabled(&mut self, group: &str, enabled: bool) {
+        if enabled {
+            self.disabled_groups.remove(group);
+        } else {
+            self.disabled_groups.insert(group.to_string());
+        }
+    }
+
+    fn is_group_enabled(&self, group: &str) -> bool {
+        !self.disabled_groups.contains(group)
+    }
+
+    fn groups(&self) -> Vec<String> {
+        let mut groups: Vec<String> = self
+            .processors
+            .iter()
+            .filter_map(|p| p.group.clone())
+            .collect();
+        groups.sort();
+        groups.dedup();
+        groups
+    }
+
+    fn info(&self) -> Vec<ProcessorInfo> {
+        self.processors
+            .iter()
+            .map(|p| ProcessorInfo {
+                description: p.description.clone(),
+                priority: p.priority,
+                group: p.group.clone(),
+                enabled: p.enabled,
+                active: p.is_active(&self.disabled_groups),
+            })
+            .collect()
+    }
 }
 
 impl<T: TextProcessor> TextProcessor for TextProcessorManager<T> {
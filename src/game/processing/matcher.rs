@@ -43,35 +43,50 @@ impl Matcher {
     }
 
     pub fn find(&self, input: &TextLine) -> Option<Match> {
-        if let Some(captures) = self.regex.captures(&input.to_string()) {
-            let mut groups = HashMap::default();
-            for group in self.groups() {
-                let captured = if let Some(i) = group.index {
-                    captures.get(i)
-                } else if let Some(name) = group.group_name {
-                    captures.name(name)
-                } else {
-                    panic!(
-                        "Invalid capture group {} has neither name nor index",
-                        group.name
-                    );
-                };
-
-                if let Some(value) = captured {
-                    let text = input.subs(value.start(), value.end());
-                    groups.insert(group.name, text);
-                }
-            }
+        let text = input.to_string();
+        let captures = self.regex.captures(&text)?;
+        Some(self.to_match(input, &text, &captures))
+    }
+
+    /// Returns every non-overlapping Match in the input, in order
+    pub fn find_all(&self, input: &TextLine) -> Vec<Match> {
+        let text = input.to_string();
+        self.regex
+            .captures_iter(&text)
+            .map(|captures| self.to_match(input, &text, &captures))
+            .collect()
+    }
+
+    fn to_match(&self, input: &TextLine, text: &str, captures: &regex::Captures) -> Match {
+        // NOTE: Regex offsets are in bytes, but TextLine ranges are in chars
+        let col = |byte_offset: usize| text[..byte_offset].chars().count();
 
-            let (start, end) = if let Some(m) = captures.get(0) {
-                (m.start(), m.end())
+        let mut groups = HashMap::default();
+        for group in self.groups() {
+            let captured = if let Some(i) = group.index {
+                captures.get(i)
+            } else if let Some(name) = group.group_name {
+                captures.name(name)
             } else {
-                (0, input.width())
+                panic!(
+                    "Invalid capture group {} has neither name nor index",
+                    group.name
+                );
             };
 
-            return Some(Match { groups, start, end });
+            if let Some(value) = captured {
+                let text = input.subs(col(value.start()), col(value.end()));
+                groups.insert(group.name, text);
+            }
         }
-        None
+
+        let (start, end) = if let Some(m) = captures.get(0) {
+            (col(m.start()), col(m.end()))
+        } else {
+            (0, input.width())
+        };
+
+        Match { groups, start, end }
     }
 }
 
@@ -202,6 +217,27 @@ mod tests {
             assert_eq!(group(m, "1"), "peppers");
         }
 
+        #[test]
+        fn multibyte_columns() {
+            let matcher = re_compile(r"/(\w+) bites/");
+            let input = "Le café bites".into();
+            let m = matcher.find(&input).expect("Failed to match");
+            assert_eq!((m.start, m.end), (3, 13));
+            assert_eq!(group(m, "1"), "café");
+        }
+
+        #[test]
+        fn find_all() {
+            let matcher = re_compile(r"/\d+/");
+            let input = "You deal 12 damage, then 7".into();
+            let found: Vec<(usize, usize)> = matcher
+                .find_all(&input)
+                .iter()
+                .map(|m| (m.start, m.end))
+                .collect();
+            assert_eq!(found, vec![(9, 11), (25, 26)]);
+        }
+
         #[test]
         fn simple_named() {
             let matcher = re_compile(r"/^saute (?P<food>\w+)/");
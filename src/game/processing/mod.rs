@@ -1,8 +1,11 @@
 use bitflags::bitflags;
 
 pub mod alias;
+pub mod highlight;
 pub mod manager;
 mod matcher;
+pub mod style;
+pub mod trigger;
 
 use crate::{editing::text::TextLine, input::maps::KeyResult};
 
@@ -30,6 +33,44 @@ pub enum ProcessedText {
     Removed(ProcessedTextFlags),
 }
 
+/// The kinds of processors a [crate::game::engine::GameEngine] manages, for commands
+/// that enable, disable, or reorder them by description
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProcessorKind {
+    Alias,
+    Trigger,
+    Substitution,
+    Highlight,
+}
+
+impl ProcessorKind {
+    pub const ALL: [ProcessorKind; 4] = [
+        ProcessorKind::Alias,
+        ProcessorKind::Trigger,
+        ProcessorKind::Substitution,
+        ProcessorKind::Highlight,
+    ];
+
+    pub fn from_name(name: &str) -> Option<ProcessorKind> {
+        match name {
+            "alias" => Some(ProcessorKind::Alias),
+            "trigger" => Some(ProcessorKind::Trigger),
+            "gag" | "subst" => Some(ProcessorKind::Substitution),
+            "highlight" => Some(ProcessorKind::Highlight),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProcessorKind::Alias => "alias",
+            ProcessorKind::Trigger => "trigger",
+            ProcessorKind::Substitution => "gag",
+            ProcessorKind::Highlight => "highlight",
+        }
+    }
+}
+
 pub trait TextProcessor {
     fn describe(&self) -> &str;
     fn process(&mut self, input: TextInput) -> KeyResult<ProcessedText>;
@@ -0,0 +1,68 @@
+use tui::style::{Color, Modifier, Style};
+
+use crate::{
+    connection::mxp::parse_color,
+    input::{maps::KeyResult, KeyError},
+};
+
+fn parse_style_color(value: &str) -> Option<Color> {
+    if let Ok(index) = value.parse::<u8>() {
+        return Some(Color::Indexed(index));
+    }
+    parse_color(value)
+}
+
+/// Parse a style spec like `bold,fg=yellow,bg=#202020`. Colors may be names,
+/// `#RRGGBB` hex codes, or 256-color indices; the supported modifiers are
+/// `bold`, `dim`, `italic`, `underline`, and `reverse`.
+pub fn parse_style(spec: &str) -> KeyResult<Style> {
+    let mut style = Style::default();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        style = match part.split_once('=') {
+            Some(("fg", color)) => match parse_style_color(color) {
+                Some(color) => style.fg(color),
+                None => return Err(invalid_style(part)),
+            },
+            Some(("bg", color)) => match parse_style_color(color) {
+                Some(color) => style.bg(color),
+                None => return Err(invalid_style(part)),
+            },
+            Some(_) => return Err(invalid_style(part)),
+            None => style.add_modifier(match part {
+                "bold" => Modifier::BOLD,
+                "dim" => Modifier::DIM,
+                "italic" => Modifier::ITALIC,
+                "underline" => Modifier::UNDERLINED,
+                "reverse" => Modifier::REVERSED,
+                _ => return Err(invalid_style(part)),
+            }),
+        };
+    }
+    Ok(style)
+}
+
+fn invalid_style(part: &str) -> KeyError {
+    KeyError::InvalidInput(format!("Invalid style: {}", part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_spec() {
+        assert_eq!(
+            parse_style("bold, fg=yellow,bg=#102030").unwrap(),
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::Yellow)
+                .bg(Color::Rgb(0x10, 0x20, 0x30))
+        );
+        assert_eq!(
+            parse_style("fg=208").unwrap(),
+            Style::default().fg(Color::Indexed(208))
+        );
+        assert!(parse_style("fg=chartreuse").is_err());
+        assert!(parse_style("blink").is_err());
+    }
+}
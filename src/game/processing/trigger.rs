@@ -0,0 +1,601 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{
+    editing::text::{EditableLine, TextLine},
+    input::{maps::KeyResult, KeyError},
+};
+
+use super::{
+    manager::TextProcessorManager,
+    matcher::{Match, Matcher},
+    ProcessedText, ProcessedTextFlags, TextInput, TextProcessor,
+};
+
+/// The default number of lines within which a SequenceTrigger must complete
+pub const DEFAULT_SEQUENCE_LINES: usize = 100;
+
+/// The capture holding every line matched by a SequenceTrigger
+pub const SEQUENCE_LINES_CAPTURE: &str = "lines";
+
+/// Invoked with the captured groups whenever a Trigger matches
+pub type TriggerAction = Arc<dyn Fn(HashMap<String, String>) -> KeyResult + Send + Sync>;
+
+/// A Trigger whose pattern matched some received text
+pub struct FiredTrigger {
+    pub description: String,
+    pub action: TriggerAction,
+    pub captures: HashMap<String, String>,
+}
+
+impl FiredTrigger {
+    pub fn run(self) -> KeyResult {
+        (self.action)(self.captures)
+    }
+}
+
+/// Collects FiredTriggers so their actions may be run *after* processing,
+/// without holding a lock on the GameEngine
+#[derive(Clone, Default)]
+pub struct FiredTriggers(Arc<Mutex<Vec<FiredTrigger>>>);
+
+impl FiredTriggers {
+    fn push(&self, fired: FiredTrigger) {
+        self.0.lock().unwrap().push(fired);
+    }
+
+    pub fn take(&self) -> Vec<FiredTrigger> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+pub struct Trigger {
+    matcher: Matcher,
+    action_description: String,
+    action: TriggerAction,
+    one_shot: bool,
+    fired: FiredTriggers,
+}
+
+impl Trigger {
+    pub fn compile(
+        input: String,
+        action_description: String,
+        action: TriggerAction,
+        one_shot: bool,
+        fired: FiredTriggers,
+    ) -> KeyResult<Trigger> {
+        Ok(Trigger {
+            matcher: Matcher::compile(input)?,
+            action_description,
+            action,
+            one_shot,
+            fired,
+        })
+    }
+}
+
+impl Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} => {}",
+            self.matcher.description, self.action_description
+        )?;
+        if self.one_shot {
+            write!(f, " (once)")?;
+        }
+        Ok(())
+    }
+}
+
+impl TextProcessor for Trigger {
+    fn describe(&self) -> &str {
+        &self.matcher.description
+    }
+
+    fn process(&mut self, input: TextInput) -> KeyResult<ProcessedText> {
+        let found = match &input {
+            TextInput::Line(text) => self.matcher.find(text),
+            TextInput::Newline => None,
+        };
+
+        if let Some(found) = found {
+            self.fired.push(FiredTrigger {
+                description: self.matcher.description.to_string(),
+                action: self.action.clone(),
+                captures: found
+                    .groups
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            });
+
+            if self.one_shot {
+                // NOTE: Triggers never change the text, but "processing" it
+                // is the only way to request being destroyed
+                return Ok(ProcessedText::Processed(
+                    input,
+                    ProcessedTextFlags::DESTROYED,
+                ));
+            }
+        }
+
+        Ok(ProcessedText::Unprocessed(input))
+    }
+}
+
+/// When a SequenceTrigger gives up on a partially-matched sequence
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SequenceLimits {
+    /// The max number of lines the sequence may span, including its first line
+    pub lines: usize,
+
+    /// The max time after its first line by which the sequence must complete
+    pub timeout: Option<Duration>,
+}
+
+impl Default for SequenceLimits {
+    fn default() -> Self {
+        Self {
+            lines: DEFAULT_SEQUENCE_LINES,
+            timeout: None,
+        }
+    }
+}
+
+impl Display for SequenceLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "within {} lines", self.lines)?;
+        if let Some(timeout) = self.timeout {
+            write!(f, " and {}s", timeout.as_secs_f32())?;
+        }
+        Ok(())
+    }
+}
+
+struct SequenceProgress {
+    started: Instant,
+    next: usize,
+    lines: Vec<String>,
+    captures: HashMap<String, String>,
+}
+
+impl SequenceProgress {
+    fn accumulate(&mut self, found: Match) {
+        for (name, value) in found.groups {
+            self.captures.insert(name, value.to_string());
+        }
+        self.next += 1;
+    }
+}
+
+/// A Trigger that matches a sequence of complete lines: each pattern must match
+/// a line after the one matched by the previous pattern, and every line from the
+/// first through the one matching the last pattern belongs to the sequence.
+/// Captures are accumulated across the lines (later values replace earlier ones
+/// with the same name), and the action is invoked once the last pattern matches.
+///
+/// A partial sequence is abandoned once it exceeds its [SequenceLimits]; if the
+/// line that exceeded them, or any line that doesn't match the next pattern,
+/// matches the *first* pattern, a new sequence starts from that line.
+pub struct SequenceTrigger {
+    description: String,
+    matchers: Vec<Matcher>,
+    limits: SequenceLimits,
+    action_description: String,
+    action: TriggerAction,
+    one_shot: bool,
+    fired: FiredTriggers,
+    progress: Option<SequenceProgress>,
+}
+
+impl SequenceTrigger {
+    pub fn compile(
+        patterns: Vec<String>,
+        limits: SequenceLimits,
+        action_description: String,
+        action: TriggerAction,
+        one_shot: bool,
+        fired: FiredTriggers,
+    ) -> KeyResult<SequenceTrigger> {
+        if patterns.len() < 2 {
+            return Err(KeyError::InvalidInput(
+                "A sequence needs at least two patterns".to_string(),
+            ));
+        }
+
+        let matchers = patterns
+            .iter()
+            .cloned()
+            .map(Matcher::compile)
+            .collect::<KeyResult<Vec<Matcher>>>()?;
+        if matchers
+            .iter()
+            .flat_map(|matcher| matcher.groups())
+            .any(|group| group.name == SEQUENCE_LINES_CAPTURE)
+        {
+            return Err(KeyError::InvalidInput(format!(
+                "${{{}}} is reserved for the lines matched by a sequence",
+                SEQUENCE_LINES_CAPTURE
+            )));
+        }
+
+        Ok(SequenceTrigger {
+            description: patterns.join(" ++then "),
+            matchers,
+            limits,
+            action_description,
+            action,
+            one_shot,
+            fired,
+            progress: None,
+        })
+    }
+
+    fn is_expired(&self, progress: &SequenceProgress, now: Instant) -> bool {
+        progress.lines.len() >= self.limits.lines
+            || matches!(self.limits.timeout, Some(timeout) if now - progress.started >= timeout)
+    }
+
+    /// Abandon any partial sequence that has exceeded its limits, so one that
+    /// stalls doesn't linger until another line is received
+    fn expire(&mut self, now: Instant) {
+        if matches!(&self.progress, Some(progress) if self.is_expired(progress, now)) {
+            self.progress = None;
+        }
+    }
+
+    /// Returns true if the sequence completed with this line
+    fn process_line(&mut self, line: &TextLine, now: Instant) -> bool {
+        let text = line.to_string();
+
+        if let Some(progress) = self.progress.take() {
+            if !self.is_expired(&progress, now) {
+                let mut progress = progress;
+                progress.lines.push(text.clone());
+                if let Some(found) = self.matchers[progress.next].find(line) {
+                    progress.accumulate(found);
+                    if progress.next < self.matchers.len() {
+                        self.progress = Some(progress);
+                        return false;
+                    }
+
+                    let mut captures = progress.captures;
+                    captures.insert(
+                        SEQUENCE_LINES_CAPTURE.to_string(),
+                        progress.lines.join("\n"),
+                    );
+                    self.fired.push(FiredTrigger {
+                        description: self.description.to_string(),
+                        action: self.action.clone(),
+                        captures,
+                    });
+                    return true;
+                }
+                self.progress = Some(progress);
+            }
+        }
+
+        if let Some(found) = self.matchers[0].find(line) {
+            let mut progress = SequenceProgress {
+                started: now,
+                next: 0,
+                lines: vec![text],
+                captures: Default::default(),
+            };
+            progress.accumulate(found);
+            self.progress = Some(progress);
+        }
+        false
+    }
+}
+
+impl Display for SequenceTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} => {} ({}",
+            self.description, self.action_description, self.limits
+        )?;
+        if self.one_shot {
+            write!(f, ", once")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl TextProcessor for SequenceTrigger {
+    fn describe(&self) -> &str {
+        &self.description
+    }
+
+    fn process(&mut self, input: TextInput) -> KeyResult<ProcessedText> {
+        let completed = match &input {
+            TextInput::Line(line) => self.process_line(line, Instant::now()),
+            TextInput::Newline => false,
+        };
+
+        if completed && self.one_shot {
+            Ok(ProcessedText::Processed(
+                input,
+                ProcessedTextFlags::DESTROYED,
+            ))
+        } else {
+            Ok(ProcessedText::Unprocessed(input))
+        }
+    }
+}
+
+impl TextProcessorManager<SequenceTrigger> {
+    /// Offer a complete received line to each SequenceTrigger exactly once
+    pub fn process_received(&mut self, input: TextInput) -> KeyResult {
+        self.process_once(input)?;
+        Ok(())
+    }
+
+    /// Abandon any partial sequences that have timed out while waiting for more lines
+    pub fn expire(&mut self, now: Instant) {
+        for (_, trigger) in self.iter_mut() {
+            trigger.expire(now);
+        }
+    }
+}
+
+impl TextProcessorManager<Trigger> {
+    /// Offer a received line to each Trigger exactly once; since triggers don't
+    /// modify the text, there's no need to iterate like [TextProcessor::process]
+    pub fn process_received(&mut self, input: TextInput) -> KeyResult {
+        self.process_once(input)?;
+        Ok(())
+    }
+}
+
+/// Replace `$name` and `${name}` references in `template` with the
+/// corresponding captured values
+pub fn expand_captures(template: &str, captures: &HashMap<String, String>) -> String {
+    // NOTE: Longer names go first, so `$10` isn't clobbered by `$1`
+    let mut names: Vec<&String> = captures.keys().collect();
+    names.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+
+    let mut result = template.to_string();
+    for name in names {
+        let value = &captures[name];
+        result = result.replace(&format!("${{{}}}", name), value);
+        result = result.replace(&format!("${}", name), value);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording_action(received: &Arc<Mutex<Vec<String>>>, template: &str) -> TriggerAction {
+        let received = received.clone();
+        let template = template.to_string();
+        Arc::new(move |captures| {
+            received
+                .lock()
+                .unwrap()
+                .push(expand_captures(&template, &captures));
+            Ok(())
+        })
+    }
+
+    fn define(
+        manager: &mut TextProcessorManager<Trigger>,
+        fired: &FiredTriggers,
+        pattern: &str,
+        action: TriggerAction,
+        one_shot: bool,
+    ) {
+        let trigger = Trigger::compile(
+            pattern.to_string(),
+            "test".to_string(),
+            action,
+            one_shot,
+            fired.clone(),
+        )
+        .expect("Pattern should compile");
+        manager.insert(pattern.to_string(), trigger);
+    }
+
+    fn receive(manager: &mut TextProcessorManager<Trigger>, fired: &FiredTriggers, text: &str) {
+        manager
+            .process_received(TextInput::Line(text.to_string().into()))
+            .expect("Should process without error");
+        for trigger in fired.take() {
+            trigger.run().expect("Action should succeed");
+        }
+    }
+
+    #[test]
+    fn simple_and_regex_captures() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let fired = FiredTriggers::default();
+        let mut manager = TextProcessorManager::new();
+        define(
+            &mut manager,
+            &fired,
+            "${enemy} attacks you",
+            recording_action(&received, "kill ${enemy}"),
+            false,
+        );
+        define(
+            &mut manager,
+            &fired,
+            r"/You have (\d+) gold/",
+            recording_action(&received, "deposit $1"),
+            false,
+        );
+
+        receive(&mut manager, &fired, "A goblin attacks you!");
+        receive(&mut manager, &fired, "You have 42 gold");
+        receive(&mut manager, &fired, "A goblin attacks you!");
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec!["kill goblin", "deposit 42", "kill goblin"]
+        );
+    }
+
+    #[test]
+    fn one_shot_is_destroyed() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let fired = FiredTriggers::default();
+        let mut manager = TextProcessorManager::new();
+        define(
+            &mut manager,
+            &fired,
+            "^You are hungry",
+            recording_action(&received, "eat bread"),
+            true,
+        );
+
+        receive(&mut manager, &fired, "You are hungry.");
+        receive(&mut manager, &fired, "You are hungry.");
+
+        assert_eq!(*received.lock().unwrap(), vec!["eat bread"]);
+    }
+
+    fn sequence(
+        patterns: &[&str],
+        limits: SequenceLimits,
+        fired: &FiredTriggers,
+    ) -> SequenceTrigger {
+        SequenceTrigger::compile(
+            patterns.iter().map(|p| p.to_string()).collect(),
+            limits,
+            "test".to_string(),
+            Arc::new(|_| Ok(())),
+            false,
+            fired.clone(),
+        )
+        .expect("Patterns should compile")
+    }
+
+    fn feed(trigger: &mut SequenceTrigger, lines: &[&str], now: Instant) -> Vec<bool> {
+        lines
+            .iter()
+            .map(|line| trigger.process_line(&line.to_string().into(), now))
+            .collect()
+    }
+
+    #[test]
+    fn sequence_accumulates_captures() {
+        let fired = FiredTriggers::default();
+        let mut trigger = sequence(
+            &["You attack ${target}", r"/You deal (\d+) damage/"],
+            SequenceLimits::default(),
+            &fired,
+        );
+
+        let now = Instant::now();
+        let lines = &[
+            "You attack goblin",
+            "The goblin dodges",
+            "You deal 12 damage",
+        ];
+        assert_eq!(feed(&mut trigger, lines, now), vec![false, false, true]);
+
+        let captures = fired.take().pop().expect("Should have fired").captures;
+        assert_eq!(captures["target"], "goblin");
+        assert_eq!(captures["1"], "12");
+        assert_eq!(captures[SEQUENCE_LINES_CAPTURE], lines.join("\n"));
+    }
+
+    #[test]
+    fn sequence_abandoned_after_lines() {
+        let fired = FiredTriggers::default();
+        let limits = SequenceLimits {
+            lines: 2,
+            timeout: None,
+        };
+        let mut trigger = sequence(&["^Score", "^Total"], limits, &fired);
+
+        let now = Instant::now();
+        let lines = &["Score", "Str 10", "Total 10", "Score", "Total 20"];
+        assert_eq!(
+            feed(&mut trigger, lines, now),
+            vec![false, false, false, false, true]
+        );
+        assert_eq!(fired.take().len(), 1);
+    }
+
+    #[test]
+    fn sequence_abandoned_after_timeout() {
+        let fired = FiredTriggers::default();
+        let limits = SequenceLimits {
+            lines: DEFAULT_SEQUENCE_LINES,
+            timeout: Some(Duration::from_secs(5)),
+        };
+        let mut trigger = sequence(&["^Score", "^Total"], limits, &fired);
+
+        let start = Instant::now();
+        assert_eq!(feed(&mut trigger, &["Score"], start), vec![false]);
+        let later = start + Duration::from_secs(10);
+        assert_eq!(feed(&mut trigger, &["Total 10"], later), vec![false]);
+        assert!(fired.take().is_empty());
+    }
+
+    #[test]
+    fn sequence_expires_without_lines() {
+        let fired = FiredTriggers::default();
+        let limits = SequenceLimits {
+            lines: DEFAULT_SEQUENCE_LINES,
+            timeout: Some(Duration::from_secs(5)),
+        };
+        let mut trigger = sequence(&["^Score", "^Total"], limits, &fired);
+
+        let start = Instant::now();
+        assert_eq!(feed(&mut trigger, &["Score"], start), vec![false]);
+        trigger.expire(start + Duration::from_secs(1));
+        assert!(trigger.progress.is_some());
+
+        trigger.expire(start + Duration::from_secs(10));
+        assert!(trigger.progress.is_none());
+    }
+
+    #[test]
+    fn sequence_reserves_lines_capture() {
+        let fired = FiredTriggers::default();
+        let compile = |patterns: &[&str]| {
+            SequenceTrigger::compile(
+                patterns.iter().map(|p| p.to_string()).collect(),
+                SequenceLimits::default(),
+                "test".to_string(),
+                Arc::new(|_| Ok(())),
+                false,
+                fired.clone(),
+            )
+        };
+        assert!(compile(&["^Score", "^Total ${lines}"]).is_err());
+        assert!(compile(&["^Score", "/^Total (?P<lines>\\d+)/"]).is_err());
+        assert!(compile(&["^Score", "^Total ${total}"]).is_ok());
+    }
+
+    #[test]
+    fn sequence_restarts_on_first_pattern() {
+        let fired = FiredTriggers::default();
+        let mut trigger = sequence(&["^Round $1", "^End"], SequenceLimits::default(), &fired);
+
+        let now = Instant::now();
+        let lines = &["Round 1", "Round 2", "End"];
+        assert_eq!(feed(&mut trigger, lines, now), vec![false, false, true]);
+
+        let captures = fired.take().pop().expect("Should have fired").captures;
+        assert_eq!(captures["1"], "2");
+        assert_eq!(captures[SEQUENCE_LINES_CAPTURE], "Round 2\nEnd");
+    }
+
+    #[test]
+    fn expand_longer_names_first() {
+        let mut captures = HashMap::new();
+        captures.insert("1".to_string(), "one".to_string());
+        captures.insert("10".to_string(), "ten".to_string());
+        assert_eq!(expand_captures("$1 ${10} $10", &captures), "one ten ten");
+    }
+}
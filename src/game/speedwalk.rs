@@ -0,0 +1,199 @@
+use crate::input::{maps::KeyResult, KeyError};
+
+use super::stacking::MAX_REPEAT;
+
+const DEFAULT_DIRECTIONS: &[&str] = &["n", "s", "e", "w", "ne", "nw", "se", "sw", "u", "d"];
+
+/// Expands "speedwalk" input like `3n2e` into individual movement commands.
+/// Input prefixed with `prefix` (like `.news`) is always considered; otherwise,
+/// it must include a count to avoid expanding ordinary words. Input that would
+/// be expanded may be escaped with a leading backslash to send it unchanged
+/// (minus the backslash). Disabled by default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Speedwalk {
+    pub enabled: bool,
+    pub prefix: char,
+
+    /// Pairs of (direction, command to send)
+    directions: Vec<(String, String)>,
+}
+
+impl Default for Speedwalk {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            prefix: '.',
+            directions: DEFAULT_DIRECTIONS
+                .iter()
+                .map(|dir| (dir.to_string(), dir.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Speedwalk {
+    pub fn directions(&self) -> &[(String, String)] {
+        &self.directions
+    }
+
+    /// Add (or replace) a direction that, when walked, sends `command`
+    pub fn set_direction(&mut self, direction: String, command: String) {
+        if let Some(existing) = self.directions.iter_mut().find(|(d, _)| d == &direction) {
+            existing.1 = command;
+        } else {
+            self.directions.push((direction, command));
+        }
+    }
+
+    pub fn remove_direction(&mut self, direction: &str) -> bool {
+        let count = self.directions.len();
+        self.directions.retain(|(d, _)| d != direction);
+        count != self.directions.len()
+    }
+
+    /// Returns the commands to send if `input` should be expanded, or None
+    /// if it should be sent as-is
+    pub fn expand(&self, input: &str) -> KeyResult<Option<Vec<String>>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        if let Some(escaped) = input.strip_prefix('\\') {
+            // Only strip the backslash if it actually prevented an expansion:
+            return match self.expand_unescaped(escaped) {
+                Ok(None) => Ok(None),
+                _ => Ok(Some(vec![escaped.to_string()])),
+            };
+        }
+
+        self.expand_unescaped(input)
+    }
+
+    fn expand_unescaped(&self, input: &str) -> KeyResult<Option<Vec<String>>> {
+        let body = match input.strip_prefix(self.prefix) {
+            Some(body) => body,
+            None if input.chars().any(|ch| ch.is_ascii_digit()) => input,
+            None => return Ok(None),
+        };
+
+        // NOTE: The whole input must be a speedwalk before any step is checked
+        // against MAX_REPEAT, so input like `1000` is still sent as-is
+        let mut steps = vec![];
+        let mut rest = body.trim_start();
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|ch: char| !ch.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let (count, after_count) = rest.split_at(digits);
+
+            let (direction, command) = match self.longest_direction(after_count) {
+                Some(found) => found,
+                None => return Ok(None),
+            };
+            steps.push((count, command));
+            rest = after_count[direction.len()..].trim_start();
+        }
+
+        if steps.is_empty() {
+            return Ok(None);
+        }
+
+        let mut commands = vec![];
+        for (count, command) in steps {
+            let count = if count.is_empty() {
+                1
+            } else {
+                match count.parse::<usize>() {
+                    Ok(count) if count <= MAX_REPEAT => count,
+                    _ => {
+                        return Err(KeyError::InvalidInput(format!(
+                            "Too many steps: {} (max {})",
+                            count, MAX_REPEAT
+                        )))
+                    }
+                }
+            };
+            for _ in 0..count {
+                commands.push(command.to_string());
+            }
+        }
+        Ok(Some(commands))
+    }
+
+    fn longest_direction(&self, input: &str) -> Option<(&str, &str)> {
+        self.directions
+            .iter()
+            .filter(|(direction, _)| input.starts_with(direction.as_str()))
+            .max_by_key(|(direction, _)| direction.len())
+            .map(|(direction, command)| (direction.as_str(), command.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> Speedwalk {
+        Speedwalk {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    fn expand(speedwalk: &Speedwalk, input: &str) -> Option<Vec<String>> {
+        speedwalk.expand(input).unwrap()
+    }
+
+    fn walk(commands: &[&str]) -> Option<Vec<String>> {
+        Some(commands.iter().map(|c| c.to_string()).collect())
+    }
+
+    #[test]
+    fn expand_counts() {
+        let speedwalk = enabled();
+        assert_eq!(expand(&speedwalk, "3n2e"), walk(&["n", "n", "n", "e", "e"]));
+        assert_eq!(expand(&speedwalk, "2ne u"), walk(&["ne", "ne", "u"]));
+    }
+
+    #[test]
+    fn expand_prefixed() {
+        let speedwalk = enabled();
+        assert_eq!(
+            expand(&speedwalk, ".4s3w"),
+            walk(&["s", "s", "s", "s", "w", "w", "w"])
+        );
+        assert_eq!(expand(&speedwalk, ".nsd"), walk(&["n", "s", "d"]));
+    }
+
+    #[test]
+    fn leave_ordinary_text() {
+        let speedwalk = enabled();
+        assert_eq!(expand(&speedwalk, "news"), None);
+        assert_eq!(expand(&speedwalk, "buy 2 swords"), None);
+        assert_eq!(expand(&speedwalk, "2swords"), None);
+        assert_eq!(expand(&speedwalk, "1000"), None);
+        assert_eq!(expand(&speedwalk, "250"), None);
+        assert!(speedwalk.expand("1000n").is_err());
+
+        assert_eq!(expand(&Speedwalk::default(), "3n2e"), None);
+    }
+
+    #[test]
+    fn escape_only_speedwalks() {
+        let speedwalk = enabled();
+        assert_eq!(expand(&speedwalk, "\\3n2e"), walk(&["3n2e"]));
+        assert_eq!(expand(&speedwalk, "\\1000n"), walk(&["1000n"]));
+        assert_eq!(expand(&speedwalk, "\\o/"), None);
+        assert_eq!(expand(&speedwalk, "\\2swords"), None);
+    }
+
+    #[test]
+    fn custom_directions() {
+        let mut speedwalk = enabled();
+        speedwalk.set_direction("o".to_string(), "out".to_string());
+        assert_eq!(expand(&speedwalk, "2o1n"), walk(&["out", "out", "n"]));
+
+        assert!(speedwalk.remove_direction("o"));
+        assert_eq!(expand(&speedwalk, "2o1n"), None);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::input::{maps::KeyResult, KeyError};
+
+/// The most times a single command may be repeated via the repeat prefix
+pub const MAX_REPEAT: usize = 100;
+
+/// Splits a line of input into the individual commands to be sent. Commands
+/// are separated by `separator` (which may be escaped with a backslash), and
+/// any command like `#3 kill rat` (where `#` is the `repeat_prefix`) is sent
+/// that many times. Both are disabled by default, since either may
+/// legitimately appear in ordinary input.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommandStacking {
+    pub separator: Option<char>,
+    pub repeat_prefix: Option<char>,
+}
+
+impl CommandStacking {
+    pub fn split(&self, input: &str) -> KeyResult<Vec<String>> {
+        let mut commands = vec![];
+        for piece in self.split_separated(input) {
+            match self.parse_repeat(&piece)? {
+                Some((count, command)) => {
+                    for _ in 0..count {
+                        commands.push(command.to_string());
+                    }
+                }
+                None => commands.push(piece),
+            }
+        }
+        Ok(commands)
+    }
+
+    fn split_separated(&self, input: &str) -> Vec<String> {
+        let separator = match self.separator {
+            Some(separator) => separator,
+            None => return vec![input.to_string()],
+        };
+
+        let mut pieces = vec![];
+        let mut current = String::new();
+        let mut chars = input.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if ch == '\\' && chars.peek() == Some(&separator) {
+                current.push(separator);
+                chars.next();
+            } else if ch == separator {
+                pieces.push(current.trim().to_string());
+                current.clear();
+            } else {
+                current.push(ch);
+            }
+        }
+
+        if pieces.is_empty() {
+            // Leave a lone command untouched
+            return vec![current];
+        }
+
+        pieces.push(current.trim().to_string());
+
+        // Skip the empty commands from eg `n;` or `n;;e`:
+        pieces.retain(|piece| !piece.is_empty());
+        pieces
+    }
+
+    fn parse_repeat<'a>(&self, piece: &'a str) -> KeyResult<Option<(usize, &'a str)>> {
+        let rest = match self.repeat_prefix {
+            Some(prefix) if piece.starts_with(prefix) => &piece[prefix.len_utf8()..],
+            _ => return Ok(None),
+        };
+
+        let (count, command) = match rest.split_once(char::is_whitespace) {
+            Some((count, command)) if !command.trim().is_empty() => (count, command.trim()),
+            _ => return Ok(None),
+        };
+
+        match count.parse::<usize>() {
+            Ok(count) if count > MAX_REPEAT => Err(KeyError::InvalidInput(format!(
+                "Too many repeats: {} (max {})",
+                count, MAX_REPEAT
+            ))),
+            Ok(count) => Ok(Some((count, command))),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> CommandStacking {
+        CommandStacking {
+            separator: Some(';'),
+            repeat_prefix: Some('#'),
+        }
+    }
+
+    #[test]
+    fn split_commands() {
+        let stacking = enabled();
+        assert_eq!(
+            stacking.split(r"n; e ;say hi\; there").unwrap(),
+            vec!["n", "e", "say hi; there"]
+        );
+        assert_eq!(stacking.split("  say hi").unwrap(), vec!["  say hi"]);
+        assert_eq!(stacking.split("").unwrap(), vec![""]);
+    }
+
+    #[test]
+    fn skip_empty_commands() {
+        let stacking = enabled();
+        assert_eq!(stacking.split("n;").unwrap(), vec!["n"]);
+        assert_eq!(stacking.split("n;;e").unwrap(), vec!["n", "e"]);
+        assert_eq!(stacking.split(" ; ").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn repeat_commands() {
+        let stacking = enabled();
+        assert_eq!(
+            stacking.split("#3 kill rat;look").unwrap(),
+            vec!["kill rat", "kill rat", "kill rat", "look"]
+        );
+        assert_eq!(stacking.split("#3").unwrap(), vec!["#3"]);
+        assert_eq!(
+            stacking.split("#hashtag win").unwrap(),
+            vec!["#hashtag win"]
+        );
+        assert!(stacking.split("#1000 kill rat").is_err());
+    }
+
+    #[test]
+    fn disabled() {
+        let stacking = CommandStacking::default();
+        assert_eq!(
+            stacking.split("#3 kill rat;look").unwrap(),
+            vec!["#3 kill rat;look"]
+        );
+    }
+}
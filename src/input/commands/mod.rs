@@ -7,6 +7,7 @@ pub mod log;
 pub mod mapping;
 pub mod registry;
 pub mod script;
+pub mod triggers;
 pub mod window;
 
 mod helpers;
@@ -16,7 +17,7 @@ use std::time::Duration;
 use self::{
     colors::declare_colors, connection::declare_connection, core::declare_core, file::declare_file,
     help::declare_help, log::declare_log, mapping::declare_mapping, registry::CommandRegistry,
-    script::declare_script, window::declare_window,
+    script::declare_script, triggers::declare_triggers, window::declare_window,
 };
 use crate::delegate_keysource_with_map;
 
@@ -71,6 +72,30 @@ impl CommandHandlerContext<'_> {
         }
     }
 
+    /// Run the builtin command described by [Self::input]
+    pub fn run_command(&mut self) -> KeyResult {
+        if let Some(command) = self.command().map(|s| s.to_string()) {
+            if let Some((name, spec)) = self.state_mut().builtin_commands.take(&command) {
+                let result = (spec.handler)(self);
+                self.state_mut().builtin_commands.insert(name, spec);
+                result
+            } else {
+                Err(KeyError::NoSuchCommand(command))
+            }
+        } else {
+            // no command; nop is okay
+            Ok(())
+        }
+    }
+
+    /// Run the given input as though it had been entered at the `:` prompt
+    pub fn execute_command(&mut self, input: String) -> KeyResult {
+        let previous = std::mem::replace(&mut self.input, input);
+        let result = self.run_command();
+        self.input = previous;
+        result
+    }
+
     fn split_input(&self) -> impl Iterator<Item = &str> {
         // TODO handle quoted input
         self.input.split_ascii_whitespace()
@@ -103,6 +128,11 @@ impl KeySource for CommandHandlerContext<'_> {
 
 pub fn create_builtin_commands() -> CommandRegistry {
     let mut registry = CommandRegistry::default();
+
+    // NOTE: Later declarations take over any abbreviations they share with earlier ones,
+    // so these go first to keep eg: `:dis` meaning `:disconnect`, not `:disablegroup`
+    declare_triggers(&mut registry);
+
     declare_colors(&mut registry);
     declare_log(&mut registry);
     declare_mapping(&mut registry);
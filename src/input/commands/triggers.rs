@@ -0,0 +1,817 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tui::style::Style;
+
+use crate::{
+    app::dispatcher::DispatchSender,
+    editing::{window::WindowFlags, Id},
+    game::{
+        engine::GameEngine,
+        processing::{
+            manager::{ManagedProcessors, ProcessorInfo},
+            style::parse_style,
+            trigger::{expand_captures, SequenceLimits, TriggerAction},
+            ProcessorKind,
+        },
+    },
+    input::{
+        maps::{actions::connection::send_string_to_buffer, KeyResult},
+        KeyError, KeymapContext,
+    },
+};
+use command_decl::declare_commands;
+
+use super::{connection::get_associated_connection, CommandHandlerContext};
+
+const ONCE_FLAG: &str = "++once";
+const WITHIN_FLAG: &str = "++within=";
+const TIMEOUT_FLAG: &str = "++timeout=";
+const THEN_SEPARATOR: &str = "++then";
+const ACTION_SEPARATOR: &str = "=>";
+const STYLE_FLAG: &str = "++style=";
+const LINE_FLAG: &str = "++line";
+const GROUP_FLAG: &str = "++group=";
+const PRIORITY_FLAG: &str = "++priority=";
+const NO_GROUP: &str = "none";
+
+declare_commands!(declare_triggers {
+    //! Commands for reacting to text received from the server
+
+    /// Run an action whenever a line received by the connection associated with the current
+    /// buffer matches a pattern. Use like:
+    ///
+    ///   :trigger [++once] {pattern} => {action}
+    ///
+    /// The pattern may use `$1` or `${name}` placeholders to capture single words, or may be a
+    /// regex when surrounded by slashes, like `/^(\w+) attacks you/`. Captured values may be
+    /// referenced in the action the same way. An action starting with `:` is run as a command;
+    /// any other action is sent to the server. Prefix the pattern with `++once` to remove the
+    /// trigger after it first fires. With no arguments, all triggers are listed.
+    ///
+    /// Like gags, substitutions, and highlights, a trigger may be put in a group with
+    /// `++group={name}`, so it can be enabled and disabled along with the rest of the group
+    /// by `:enablegroup` and `:disablegroup`, and may be given a `++priority={n}`. Those
+    /// with a higher priority are checked first, and those with the same priority are
+    /// checked in the order they were defined. The default priority is 0.
+    ///
+    /// A trigger may also match a sequence of complete lines, by separating patterns with
+    /// `++then`:
+    ///
+    ///   :trigger [++within={lines}] [++timeout={seconds}] {first} ++then {second} => {action}
+    ///
+    /// Each pattern must match a line after the one matched by the previous pattern, and
+    /// every line from the first match through the last belongs to the sequence, so the
+    /// last pattern acts as a terminator. The action runs once, when the last pattern
+    /// matches, with the captures from every pattern (later captures replace earlier ones
+    /// with the same name) plus `${lines}`, which holds all the lines of the sequence, so
+    /// patterns may not capture their own `${lines}`. A partial sequence is abandoned once
+    /// it spans more than `++within` lines (100 by default) or takes longer than
+    /// `++timeout` seconds (no limit by default). Any line that doesn't match the next
+    /// pattern but does match the first one starts the sequence over.
+    pub fn trigger(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let definition = command_args(&context.input).to_string();
+        if definition.is_empty() {
+            return list_triggers(context, buffer_id);
+        }
+
+        let (patterns, action) = match definition.split_once(ACTION_SEPARATOR) {
+            Some((patterns, action)) if !action.trim().is_empty() => {
+                (TriggerPatterns::parse(patterns)?, action.trim().to_string())
+            }
+            _ => {
+                return Err(KeyError::InvalidInput(
+                    "Usage: trigger [++once] [++group={name}] [++priority={n}] {pattern} => {action}"
+                        .to_string(),
+                ))
+            }
+        };
+
+        let dispatcher = context.state().dispatcher.sender.clone();
+        let handler = create_action(dispatcher, buffer_id, action.clone());
+        context.state_mut().connections.with_buffer_engine(buffer_id, move |engine| {
+            patterns.insert_into(engine, action, handler)
+        })
+    }
+
+    /// Remove the trigger with the given `pattern` (or sequence of patterns) from the
+    /// connection associated with the current buffer.
+    pub fn untrigger(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let pattern = TriggerPatterns::parse(command_args(&context.input))?.description();
+        let removed = context
+            .state_mut()
+            .connections
+            .with_buffer_engine(buffer_id, |engine| {
+                engine.triggers.remove(&pattern).is_some()
+                    || engine.sequence_triggers.remove(&pattern).is_some()
+            });
+        if !removed {
+            return Err(KeyError::InvalidInput(format!("No such trigger: {}", pattern)));
+        }
+        Ok(())
+    }
+
+    /// Hide any line received by the connection associated with the current buffer that
+    /// matches `pattern`, which is written like the pattern for `:trigger`. With no
+    /// arguments, all gags and substitutions are listed. Use `:showgagged` to see the
+    /// original lines anyway. The `++group` and `++priority` options work as for `:trigger`:
+    ///
+    ///   :gag [++group={name}] [++priority={n}] {pattern}
+    pub fn gag(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let definition = command_args(&context.input).to_string();
+        if definition.is_empty() {
+            return list_substitutions(context, buffer_id);
+        }
+
+        let (flags, pattern) = split_flags(&definition);
+        let mut options = ProcessorOptions::default();
+        for flag in flags {
+            if !options.parse_flag(flag)? {
+                return Err(unknown_option(flag));
+            }
+        }
+        if pattern.is_empty() {
+            return Err(KeyError::InvalidInput("Missing pattern".to_string()));
+        }
+
+        let pattern = pattern.to_string();
+        context.state_mut().connections.with_buffer_engine(buffer_id, |engine| {
+            engine.substitutions.insert_gag(pattern.clone())?;
+            options.apply(engine, ProcessorKind::Substitution, &pattern);
+            Ok(())
+        })
+    }
+
+    /// Replace the part of any line received by the connection associated with the current
+    /// buffer that matches a pattern. Use like:
+    ///
+    ///   :subst [++style={style}] [++group={name}] [++priority={n}] {pattern} => {replacement}
+    ///
+    /// Patterns, captures, groups, and priorities work as in `:trigger`. Substitutions and
+    /// gags share a single priority order. The replacement may be given a style
+    /// like `bold,fg=yellow,bg=#202020`, where colors may be names, `#RRGGBB` codes, or
+    /// 256-color indices, and the modifiers `bold`, `dim`, `italic`, `underline`, and
+    /// `reverse` are supported. An empty replacement removes the matched text.
+    pub fn subst(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let definition = command_args(&context.input).to_string();
+        let (flags, definition) = split_flags(&definition);
+        let mut options = ProcessorOptions::default();
+        let mut style = Style::default();
+        for flag in flags {
+            if let Some(spec) = flag.strip_prefix(STYLE_FLAG) {
+                style = parse_style(spec)?;
+            } else if !options.parse_flag(flag)? {
+                return Err(unknown_option(flag));
+            }
+        }
+        let (pattern, replacement) = match definition.split_once(ACTION_SEPARATOR) {
+            Some((pattern, replacement)) if !pattern.trim().is_empty() => {
+                (pattern.trim().to_string(), replacement.trim().to_string())
+            }
+            _ => {
+                return Err(KeyError::InvalidInput(
+                    "Usage: subst [++style={style}] [++group={name}] [++priority={n}] {pattern} => {replacement}"
+                        .to_string(),
+                ))
+            }
+        };
+
+        context.state_mut().connections.with_buffer_engine(buffer_id, |engine| {
+            engine.substitutions.insert_styled(pattern.clone(), replacement, style)?;
+            options.apply(engine, ProcessorKind::Substitution, &pattern);
+            Ok(())
+        })
+    }
+
+    /// Remove the gag or substitution with the given `pattern` from the connection
+    /// associated with the current buffer.
+    pub fn ungag(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let pattern = command_args(&context.input).to_string();
+        let removed = context
+            .state_mut()
+            .connections
+            .with_buffer_engine(buffer_id, |engine| engine.substitutions.remove(&pattern));
+        if removed.is_none() {
+            return Err(KeyError::InvalidInput(format!("No such gag: {}", pattern)));
+        }
+        Ok(())
+    }
+
+    /// Restyle text received by the connection associated with the current buffer that
+    /// matches a pattern, regardless of any colors sent by the server. Use like:
+    ///
+    ///   :highlight [++line] [++group={name}] [++priority={n}] {pattern} => {style}
+    ///
+    /// Patterns, groups, and priorities work as in `:trigger`, and styles are written as
+    /// for `:subst`. Every match on a line is restyled, or the whole line if `++line` is
+    /// given. Styles are applied on top of existing colors, so eg: `bold` keeps the
+    /// server's colors. With no arguments, all highlights are listed.
+    pub fn highlight(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let definition = command_args(&context.input).to_string();
+        if definition.is_empty() {
+            return list_highlights(context, buffer_id);
+        }
+
+        let (flags, definition) = split_flags(&definition);
+        let mut options = ProcessorOptions::default();
+        let mut whole_line = false;
+        for flag in flags {
+            if flag == LINE_FLAG {
+                whole_line = true;
+            } else if !options.parse_flag(flag)? {
+                return Err(unknown_option(flag));
+            }
+        }
+        let (pattern, style) = match definition.rsplit_once(ACTION_SEPARATOR) {
+            Some((pattern, style)) if !pattern.trim().is_empty() => {
+                (pattern.trim().to_string(), parse_style(style.trim())?)
+            }
+            _ => {
+                return Err(KeyError::InvalidInput(
+                    "Usage: highlight [++line] [++group={name}] [++priority={n}] {pattern} => {style}"
+                        .to_string(),
+                ))
+            }
+        };
+
+        context.state_mut().connections.with_buffer_engine(buffer_id, |engine| {
+            engine.highlights.insert_style(pattern.clone(), style, whole_line)?;
+            options.apply(engine, ProcessorKind::Highlight, &pattern);
+            Ok(())
+        })
+    }
+
+    /// Remove the highlight with the given `pattern` from the connection associated with
+    /// the current buffer.
+    pub fn unhighlight(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let pattern = command_args(&context.input).to_string();
+        let removed = context
+            .state_mut()
+            .connections
+            .with_buffer_engine(buffer_id, |engine| engine.highlights.remove(&pattern));
+        if removed.is_none() {
+            return Err(KeyError::InvalidInput(format!("No such highlight: {}", pattern)));
+        }
+        Ok(())
+    }
+
+    /// Enable the alias, trigger, gag, or highlight with the given `pattern` on the
+    /// connection associated with the current buffer, after it was disabled by `:disable`.
+    /// Use like:
+    ///
+    ///   :enable {alias|trigger|gag|highlight} {pattern}
+    ///
+    /// Substitutions are managed as gags. Note that it will still not run if its group is
+    /// disabled.
+    pub fn enable(context) {
+        set_processor_enabled(context, true)
+    }
+
+    /// Stop the alias, trigger, gag, or highlight with the given `pattern` on the
+    /// connection associated with the current buffer from running, without removing it.
+    /// Use like:
+    ///
+    ///   :disable {alias|trigger|gag|highlight} {pattern}
+    pub fn disable(context) {
+        set_processor_enabled(context, false)
+    }
+
+    /// Change the priority of the alias, trigger, gag, or highlight with the given
+    /// `pattern` on the connection associated with the current buffer. Use like:
+    ///
+    ///   :priority {alias|trigger|gag|highlight} {n} {pattern}
+    ///
+    /// Those with a higher priority run first; those with the same priority run in the
+    /// order they were defined (or given that priority).
+    pub fn priority(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let (kind, args) = parse_kind(command_args(&context.input))?;
+        let (priority, pattern) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let priority: i32 = parse_option(priority, priority)?;
+        let description = processor_description(kind, pattern)?;
+        update_processor(context, buffer_id, kind, &description, |manager| {
+            manager.set_priority(&description, priority);
+        })
+    }
+
+    /// Move the alias, trigger, gag, or highlight with the given `pattern` on the
+    /// connection associated with the current buffer into a group, or out of any group
+    /// if the group is `none`. Use like:
+    ///
+    ///   :group {alias|trigger|gag|highlight} {group} {pattern}
+    pub fn group(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let (kind, args) = parse_kind(command_args(&context.input))?;
+        let (group, pattern) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let group = match group {
+            NO_GROUP => None,
+            group => Some(group.to_string()),
+        };
+        let description = processor_description(kind, pattern)?;
+        update_processor(context, buffer_id, kind, &description, |manager| {
+            manager.set_group(&description, group);
+        })
+    }
+
+    /// Enable every alias, trigger, gag, and highlight in the named group on the
+    /// connection associated with the current buffer. Any that were disabled individually
+    /// remain disabled.
+    pub fn enablegroup(context, group: String) {
+        let buffer_id = connection_buffer_id(context)?;
+        context
+            .state_mut()
+            .connections
+            .with_buffer_engine(buffer_id, |engine| engine.set_group_enabled(&group, true));
+        Ok(())
+    }
+
+    /// Disable every alias, trigger, gag, and highlight in the named group on the
+    /// connection associated with the current buffer, including any added to the group
+    /// later, until it is re-enabled with `:enablegroup`.
+    pub fn disablegroup(context, group: String) {
+        let buffer_id = connection_buffer_id(context)?;
+        context
+            .state_mut()
+            .connections
+            .with_buffer_engine(buffer_id, |engine| engine.set_group_enabled(&group, false));
+        Ok(())
+    }
+
+    /// List the groups used on the connection associated with the current buffer, and
+    /// whether each is enabled.
+    pub fn groups(context) {
+        let buffer_id = connection_buffer_id(context)?;
+        let groups = context
+            .state_mut()
+            .connections
+            .with_buffer_engine(buffer_id, |engine| engine.groups());
+        let groups: Vec<String> = groups
+            .into_iter()
+            .map(|(group, enabled)| format!("{} ({})", group, if enabled { "on" } else { "off" }))
+            .collect();
+        echo_patterns(context, groups, "No groups");
+        Ok(())
+    }
+
+    /// List the aliases, triggers, gags, and highlights on the connection associated with
+    /// the current buffer in the order they run, with their priority, group, and whether
+    /// they are enabled. Pass a kind to list only those, like `:processors trigger`.
+    pub fn processors(context, kind: Option<String>) {
+        let buffer_id = connection_buffer_id(context)?;
+        let kinds = match kind {
+            Some(kind) => vec![parse_kind(&kind)?.0],
+            None => ProcessorKind::ALL.to_vec(),
+        };
+        let lines: Vec<String> =
+            context
+                .state_mut()
+                .connections
+                .with_buffer_engine(buffer_id, |engine| {
+                    kinds
+                        .into_iter()
+                        .flat_map(|kind| {
+                            let mut info: Vec<ProcessorInfo> = engine
+                                .processors(kind)
+                                .into_iter()
+                                .flat_map(|manager| manager.info())
+                                .collect();
+
+                            // NOTE: Triggers and sequence triggers are checked separately,
+                            // so only their relative order within each matters
+                            info.sort_by_key(|info| -(info.priority as i64));
+                            info.into_iter().map(move |info| describe_processor(kind, &info))
+                        })
+                        .collect()
+                });
+        echo_patterns(context, lines, "Nothing defined");
+        Ok(())
+    }
+
+    /// Toggle whether output windows for the connection associated with the current buffer
+    /// show received lines as they were received, ignoring gags and substitutions.
+    /// Pass `on` or `off` to set it explicitly.
+    pub fn showgagged(context, value: Option<String>) {
+        let buffer_id = connection_buffer_id(context)?;
+        let showing = context
+            .state_mut()
+            .tabpages
+            .windows_for_buffer(buffer_id)
+            .any(|window| window.flags.contains(WindowFlags::SHOW_GAGGED));
+        let show = match value.as_deref() {
+            None => !showing,
+            Some("on") => true,
+            Some("off") => false,
+            Some(other) => return Err(KeyError::InvalidInput(format!("Expected on or off: {}", other))),
+        };
+
+        for window in context.state_mut().tabpages.windows_for_buffer(buffer_id) {
+            window.flags.set(WindowFlags::SHOW_GAGGED, show);
+        }
+
+        let message = format!("showgagged={}", if show { "on" } else { "off" });
+        context.state_mut().echom(message);
+        Ok(())
+    }
+});
+
+/// Everything after the command name, for commands whose arguments may contain whitespace
+fn command_args(input: &str) -> &str {
+    let input = input.trim();
+    match input.find(char::is_whitespace) {
+        Some(index) => input[index..].trim(),
+        None => "",
+    }
+}
+
+fn connection_buffer_id(context: &CommandHandlerContext) -> KeyResult<Id> {
+    match get_associated_connection(context) {
+        Some((buffer_id, _)) => Ok(buffer_id),
+        None => Err(KeyError::InvalidInput(
+            "No connection for current buffer".to_string(),
+        )),
+    }
+}
+
+fn unknown_option(flag: &str) -> KeyError {
+    KeyError::InvalidInput(format!("Unknown option: {}", flag))
+}
+
+/// Split any leading `++` options from a definition
+fn split_flags(definition: &str) -> (Vec<&str>, &str) {
+    let mut flags = vec![];
+    let mut rest = definition.trim();
+    while rest.starts_with("++") {
+        let (flag, remaining) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        flags.push(flag);
+        rest = remaining.trim_start();
+    }
+    (flags, rest)
+}
+
+/// Split the kind of processor from the rest of the arguments
+fn parse_kind(args: &str) -> KeyResult<(ProcessorKind, &str)> {
+    let (kind, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    Ok((parse_kind_name(kind)?, rest.trim()))
+}
+
+pub fn parse_kind_name(name: &str) -> KeyResult<ProcessorKind> {
+    match ProcessorKind::from_name(name) {
+        Some(kind) => Ok(kind),
+        None => Err(KeyError::InvalidInput(format!(
+            "Expected alias, trigger, gag, or highlight: {}",
+            name
+        ))),
+    }
+}
+
+/// The description a processor of the given kind is stored under
+pub fn processor_description(kind: ProcessorKind, pattern: &str) -> KeyResult<String> {
+    if pattern.trim().is_empty() {
+        return Err(KeyError::InvalidInput("Missing pattern".to_string()));
+    }
+    match kind {
+        ProcessorKind::Trigger => Ok(TriggerPatterns::parse(pattern)?.description()),
+        _ => Ok(pattern.trim().to_string()),
+    }
+}
+
+/// Apply `f` to the manager holding the processor of the given kind and description on
+/// the connection associated with `buffer_id`, or fail if there is no such processor
+pub fn update_processor(
+    context: &mut CommandHandlerContext,
+    buffer_id: Id,
+    kind: ProcessorKind,
+    description: &str,
+    f: impl FnOnce(&mut dyn ManagedProcessors),
+) -> KeyResult {
+    let found = context
+        .state_mut()
+        .connections
+        .with_buffer_engine(buffer_id, |engine| {
+            engine.with_processor(kind, description, f)
+        });
+    if !found {
+        return Err(KeyError::InvalidInput(format!(
+            "No such {}: {}",
+            kind.name(),
+            description
+        )));
+    }
+    Ok(())
+}
+
+fn set_processor_enabled(context: &mut CommandHandlerContext, enabled: bool) -> KeyResult {
+    let buffer_id = connection_buffer_id(context)?;
+    let (kind, pattern) = parse_kind(command_args(&context.input))?;
+    let description = processor_description(kind, pattern)?;
+    update_processor(context, buffer_id, kind, &description, |manager| {
+        manager.set_enabled(&description, enabled);
+    })
+}
+
+fn describe_processor(kind: ProcessorKind, info: &ProcessorInfo) -> String {
+    let mut description = format!("{} {}", kind.name(), info.description);
+    if info.priority != 0 {
+        description.push_str(&format!(" ++priority={}", info.priority));
+    }
+    if let Some(group) = &info.group {
+        description.push_str(&format!(" ++group={}", group));
+    }
+    if !info.enabled {
+        description.push_str(" (disabled)");
+    } else if !info.active {
+        description.push_str(" (group disabled)");
+    }
+    description
+}
+
+fn list_triggers(context: &mut CommandHandlerContext, buffer_id: Id) -> KeyResult {
+    let mut triggers: Vec<String> =
+        context
+            .state_mut()
+            .connections
+            .with_buffer_engine(buffer_id, |engine| {
+                let triggers = engine.triggers.iter().map(|(_, t)| t.to_string());
+                let sequences = engine.sequence_triggers.iter().map(|(_, t)| t.to_string());
+                triggers.chain(sequences).collect()
+            });
+
+    if triggers.is_empty() {
+        context.state_mut().echom("No triggers");
+    } else {
+        triggers.sort();
+        context.state_mut().echom(triggers.join("\n"));
+    }
+    Ok(())
+}
+
+fn list_substitutions(context: &mut CommandHandlerContext, buffer_id: Id) -> KeyResult {
+    let patterns = context
+        .state_mut()
+        .connections
+        .with_buffer_engine(buffer_id, |engine| patterns(engine.substitutions.iter()));
+    echo_patterns(context, patterns, "No gags or substitutions");
+    Ok(())
+}
+
+fn list_highlights(context: &mut CommandHandlerContext, buffer_id: Id) -> KeyResult {
+    let patterns = context
+        .state_mut()
+        .connections
+        .with_buffer_engine(buffer_id, |engine| patterns(engine.highlights.iter()));
+    echo_patterns(context, patterns, "No highlights");
+    Ok(())
+}
+
+fn patterns<'a, T: 'a>(processors: impl Iterator<Item = (&'a String, &'a T)>) -> Vec<String> {
+    let mut patterns: Vec<String> = processors.map(|(pattern, _)| pattern.to_string()).collect();
+    patterns.sort();
+    patterns
+}
+
+fn echo_patterns(context: &mut CommandHandlerContext, patterns: Vec<String>, empty: &str) {
+    if patterns.is_empty() {
+        context.state_mut().echom(empty.to_string());
+    } else {
+        context.state_mut().echom(patterns.join("\n"));
+    }
+}
+
+/// The pattern(s) of a trigger along with any options, as written for `:trigger`
+pub struct TriggerPatterns {
+    pub patterns: Vec<String>,
+    pub one_shot: bool,
+    pub limits: SequenceLimits,
+    pub options: ProcessorOptions,
+}
+
+impl TriggerPatterns {
+    pub fn parse(spec: &str) -> KeyResult<TriggerPatterns> {
+        let (flags, rest) = split_flags(spec);
+        let mut one_shot = false;
+        let mut limited = false;
+        let mut limits = SequenceLimits::default();
+        let mut options = ProcessorOptions::default();
+
+        for flag in flags {
+            if flag == ONCE_FLAG {
+                one_shot = true;
+            } else if let Some(lines) = flag.strip_prefix(WITHIN_FLAG) {
+                limits.lines = parse_option(flag, lines)?;
+                limited = true;
+            } else if let Some(seconds) = flag.strip_prefix(TIMEOUT_FLAG) {
+                let seconds = parse_option(flag, seconds)?;
+                let timeout = Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| KeyError::InvalidInput(format!("Invalid option: {}", flag)))?;
+                limits.timeout = Some(timeout);
+                limited = true;
+            } else if !options.parse_flag(flag)? {
+                return Err(unknown_option(flag));
+            }
+        }
+
+        let patterns: Vec<String> = rest
+            .split(THEN_SEPARATOR)
+            .map(|pattern| pattern.trim().to_string())
+            .collect();
+        if patterns.iter().any(|pattern| pattern.is_empty()) {
+            return Err(KeyError::InvalidInput("Missing pattern".to_string()));
+        }
+        if limited && patterns.len() < 2 {
+            return Err(KeyError::InvalidInput(
+                "++within and ++timeout only apply to sequences of patterns".to_string(),
+            ));
+        }
+        if limits.lines < patterns.len() {
+            return Err(KeyError::InvalidInput(format!(
+                "++within must be at least the number of patterns ({})",
+                patterns.len()
+            )));
+        }
+
+        Ok(TriggerPatterns {
+            patterns,
+            one_shot,
+            limits,
+            options,
+        })
+    }
+
+    /// The name the trigger is stored under
+    pub fn description(&self) -> String {
+        self.patterns.join(" ++then ")
+    }
+
+    pub fn insert_into(
+        mut self,
+        engine: &mut GameEngine,
+        action_description: String,
+        action: TriggerAction,
+    ) -> KeyResult {
+        let description = self.description();
+        if self.patterns.len() > 1 {
+            engine.insert_sequence_trigger(
+                self.patterns,
+                self.limits,
+                action_description,
+                action,
+                self.one_shot,
+            )?;
+        } else {
+            let pattern = self.patterns.remove(0);
+            engine.insert_trigger(pattern, action_description, action, self.one_shot)?;
+        }
+        self.options
+            .apply(engine, ProcessorKind::Trigger, &description);
+        Ok(())
+    }
+}
+
+/// The `++group` and `++priority` options accepted when defining any kind of processor
+#[derive(Default)]
+pub struct ProcessorOptions {
+    pub group: Option<String>,
+    pub priority: Option<i32>,
+}
+
+impl ProcessorOptions {
+    /// Returns false if `flag` is not one of these options
+    pub fn parse_flag(&mut self, flag: &str) -> KeyResult<bool> {
+        if let Some(group) = flag.strip_prefix(GROUP_FLAG) {
+            if group.is_empty() {
+                return Err(KeyError::InvalidInput(format!("Invalid option: {}", flag)));
+            }
+            self.group = Some(group.to_string());
+        } else if let Some(priority) = flag.strip_prefix(PRIORITY_FLAG) {
+            self.priority = Some(parse_option(flag, priority)?);
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Apply these options to the newly-defined processor with the given description
+    pub fn apply(&self, engine: &mut GameEngine, kind: ProcessorKind, description: &str) {
+        engine.with_processor(kind, description, |manager| {
+            if let Some(group) = &self.group {
+                manager.set_group(description, Some(group.clone()));
+            }
+            if let Some(priority) = self.priority {
+                manager.set_priority(description, priority);
+            }
+        });
+    }
+}
+
+fn parse_option<T: FromStr>(flag: &str, value: &str) -> KeyResult<T> {
+    value
+        .parse()
+        .map_err(|_| KeyError::InvalidInput(format!("Invalid option: {}", flag)))
+}
+
+/// Create a TriggerAction from its textual form: a `:`-prefixed action is run as a command,
+/// and anything else is sent to the connection associated with `buffer_id`. In either case,
+/// any captures are expanded first. The action is performed on the main thread.
+pub fn create_action(dispatcher: DispatchSender, buffer_id: Id, action: String) -> TriggerAction {
+    let dispatcher = Mutex::new(dispatcher);
+    Arc::new(move |captures| {
+        let expanded = expand_captures(&action, &captures);
+        let dispatcher = dispatcher.lock().unwrap();
+        let record = if let Some(command) = expanded.strip_prefix(':') {
+            let command = command.to_string();
+            dispatcher.spawn_command(move |ctx| {
+                if let Err(e) = ctx.execute_command(command) {
+                    ctx.state_mut().echom_error(e);
+                }
+            })
+        } else {
+            dispatcher.spawn_command(move |ctx| {
+                if let Err(e) = send_triggered(ctx, buffer_id, expanded) {
+                    ctx.state_mut().echom_error(e);
+                }
+            })
+        };
+        record.background();
+        Ok(())
+    })
+}
+
+fn send_triggered(ctx: &mut CommandHandlerContext, buffer_id: Id, text: String) -> KeyResult {
+    let commands = ctx
+        .state_mut()
+        .connections
+        .with_buffer_engine(buffer_id, |engine| engine.expand_input(&text))?;
+    for command in commands {
+        send_string_to_buffer(ctx, buffer_id, command)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_args_keeps_whitespace() {
+        assert_eq!(
+            command_args(" trigger  $1 attacks => kill $1 "),
+            "$1 attacks => kill $1"
+        );
+        assert_eq!(command_args("trigger"), "");
+    }
+
+    #[test]
+    fn parse_sequence_patterns() {
+        let parsed = TriggerPatterns::parse("++once ++within=3  ^Score  ++then ^Total $1").unwrap();
+        assert_eq!(parsed.patterns, vec!["^Score", "^Total $1"]);
+        assert!(parsed.one_shot);
+        assert_eq!(parsed.limits.lines, 3);
+        assert_eq!(parsed.limits.timeout, None);
+        assert_eq!(parsed.description(), "^Score ++then ^Total $1");
+
+        assert!(TriggerPatterns::parse("++within=3 ^Score").is_err());
+        assert!(TriggerPatterns::parse("^Score ++then").is_err());
+        assert!(TriggerPatterns::parse("++sometimes ^Score").is_err());
+    }
+
+    #[test]
+    fn parse_sequence_timeout() {
+        let parsed = TriggerPatterns::parse("++timeout=1.5 ^Score ++then ^Total").unwrap();
+        assert_eq!(parsed.limits.timeout, Some(Duration::from_millis(1500)));
+
+        assert!(TriggerPatterns::parse("++timeout=-1 ^Score ++then ^Total").is_err());
+        assert!(TriggerPatterns::parse("++timeout=NaN ^Score ++then ^Total").is_err());
+        assert!(TriggerPatterns::parse("++timeout=inf ^Score ++then ^Total").is_err());
+        assert!(TriggerPatterns::parse("++timeout=1e30 ^Score ++then ^Total").is_err());
+    }
+
+    #[test]
+    fn parse_sequence_within() {
+        let parsed = TriggerPatterns::parse("++within=3 ^Score ++then ^Bonus ++then ^Total");
+        assert_eq!(parsed.unwrap().limits.lines, 3);
+
+        assert!(TriggerPatterns::parse("++within=0 ^Score ++then ^Total").is_err());
+        assert!(TriggerPatterns::parse("++within=2 ^Score ++then ^Bonus ++then ^Total").is_err());
+    }
+
+    #[test]
+    fn parse_processor_options() {
+        let parsed = TriggerPatterns::parse("++group=combat ++priority=-5 ++once ^Score").unwrap();
+        assert_eq!(parsed.options.group.as_deref(), Some("combat"));
+        assert_eq!(parsed.options.priority, Some(-5));
+        assert!(parsed.one_shot);
+
+        assert!(TriggerPatterns::parse("++group= ^Score").is_err());
+        assert!(TriggerPatterns::parse("++priority=high ^Score").is_err());
+    }
+}
@@ -13,12 +13,15 @@ use crate::{
 };
 
 /// Send the contents of the current Connection input buffer to
-/// its associated connection (if any)
+/// its associated connection (if any), split into separate commands
+/// per the engine's stacking settings (see `:cmdsep`) with any
+/// speedwalks expanded
 ///
 /// Returns `io::ErrorKind::NotConnected` if there is no Connection
 /// associated with the current buffer.
 pub fn send_current_input_buffer<T: BoxableKeymap>(mut ctx: KeyHandlerContext<T>) -> KeyResult {
     let buffer = ctx.state().current_buffer();
+    let buffer_id = buffer.id();
     let to_send = buffer.get_contents();
     let conn_buffer_id =
         if let BufferSource::ConnectionInputForBuffer(conn_buffer_id) = buffer.source() {
@@ -27,13 +30,23 @@ pub fn send_current_input_buffer<T: BoxableKeymap>(mut ctx: KeyHandlerContext<T>
             return Err(KeyError::IO(io::ErrorKind::NotConnected.into()));
         };
 
-    let result = send_string_to_buffer(&mut ctx, conn_buffer_id, to_send);
+    // NOTE: Split *before* alias processing, so aliases apply to each command.
+    // Input is sent as-is when masked, however, since it's probably a password
+    let masked = ctx.state().connections.is_masked_input(buffer_id);
+    let commands = if masked {
+        vec![to_send]
+    } else {
+        ctx.state_mut()
+            .connections
+            .with_buffer_engine(conn_buffer_id, |engine| engine.expand_input(&to_send))?
+    };
 
-    if result.is_ok() {
-        ctx.state_mut().current_buffer_mut().clear();
+    for command in commands {
+        send_string_to_buffer(&mut ctx, conn_buffer_id, command)?;
     }
 
-    result
+    ctx.state_mut().current_buffer_mut().clear();
+    Ok(())
 }
 
 pub fn send_string_to_buffer<K: KeymapContext>(
@@ -68,3 +81,18 @@ pub fn send_string_to_buffer<K: KeymapContext>(
 
     Ok(())
 }
+
+/// Cancel any pending automatic reconnect for the connection associated
+/// with the current buffer
+pub fn cancel_reconnect<K: KeymapContext>(ctx: &mut K) -> KeyResult {
+    if let Some(buffer_id) = ctx.state().current_buffer().connection_buffer_id() {
+        let state = ctx.state_mut();
+        if state
+            .connections
+            .cancel_reconnect(&mut state.jobs, buffer_id)
+        {
+            state.echom("Reconnect canceled.");
+        }
+    }
+    Ok(())
+}
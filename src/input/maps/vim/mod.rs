@@ -283,7 +283,8 @@ impl Keymap for VimKeymap {
         } else {
             context.state_mut().keymap_widget = None;
             self.render_keys_buffer(context);
-            vim_normal_mode() + self.buffer_maps(buf_id, context.config(), &RemapMode::VimNormal)
+            vim_normal_mode(&buffer_source)
+                + self.buffer_maps(buf_id, context.config(), &RemapMode::VimNormal)
         };
 
         if !mode.shows_keys && !self.keys_buffer.is_empty() {
@@ -1,5 +1,6 @@
 mod change;
 pub mod count;
+mod links;
 mod registers;
 mod scroll;
 pub mod search;
@@ -8,21 +9,23 @@ mod window;
 use std::rc::Rc;
 
 use crate::{
-    editing::motion::char::CharMotion,
-    editing::motion::linewise::{ToLineEndMotion, ToLineStartMotion},
-    editing::motion::{Motion, MotionFlags, MotionRange},
-    editing::text::TextLine,
-};
-use crate::{
+    app::registers::BLACKHOLE_REGISTER,
     editing::source::BufferSource,
     input::{
         commands::CommandHandlerContext,
         completion::commands::CommandsCompleter,
+        maps::actions::connection::cancel_reconnect,
         maps::vim::cmdline::{self, CmdlineSink},
         maps::{KeyHandlerContext, KeyResult},
         KeyError, KeymapContext,
     },
 };
+use crate::{
+    editing::motion::char::CharMotion,
+    editing::motion::linewise::{ToLineEndMotion, ToLineStartMotion},
+    editing::motion::{Motion, MotionFlags, MotionRange},
+    editing::text::TextLine,
+};
 use crate::{key_handler, vim_tree};
 
 use super::{
@@ -32,19 +35,8 @@ use super::{
     VimKeymap, VimMode,
 };
 
-fn handle_command(mut context: &mut CommandHandlerContext) -> KeyResult {
-    if let Some(command) = context.command().and_then(|s| Some(s.to_string())) {
-        if let Some((name, spec)) = context.state_mut().builtin_commands.take(&command) {
-            let result = (spec.handler)(&mut context);
-            context.state_mut().builtin_commands.insert(name, spec);
-            result
-        } else {
-            Err(KeyError::NoSuchCommand(command))
-        }
-    } else {
-        // no command; nop is okay
-        Ok(())
-    }
+fn handle_command(context: &mut CommandHandlerContext) -> KeyResult {
+    context.run_command()
 }
 
 fn cmd_mode_access() -> KeyTreeNode {
@@ -108,8 +100,8 @@ fn cmd_mode_access() -> KeyTreeNode {
     }
 }
 
-pub fn vim_normal_mode() -> VimMode {
-    let mappings = vim_tree! {
+pub fn vim_normal_mode(source: &BufferSource) -> VimMode {
+    let mut mappings = vim_tree! {
         "a" => change |ctx| {
             ctx.state_mut().current_window_mut().set_inserting(true);
             CharMotion::Forward(1).apply_cursor(ctx.state_mut());
@@ -171,6 +163,10 @@ pub fn vim_normal_mode() -> VimMode {
             Ok(())
         },
 
+        "<ctrl-c>" => |ctx| {
+            cancel_reconnect(&mut ctx)
+        },
+
         "x" => change |ctx| {
             delete_with_motion(ctx, CharMotion::Forward(1))
         },
@@ -187,6 +183,12 @@ pub fn vim_normal_mode() -> VimMode {
         + vim_standard_motions()
         + vim_linewise_motions();
 
+    // Links only exist in a Connection's output; elsewhere, keys like <cr>
+    // keep their usual behavior
+    if let BufferSource::Connection(_) = source {
+        mappings = mappings + links::mappings();
+    }
+
     VimMode::new("n", mappings)
         .with_shows_keys(true)
         .on_default(key_handler!(
@@ -208,8 +210,22 @@ fn delete_with_motion<M: Motion>(mut ctx: KeyHandlerContext<VimKeymap>, motion:
     Ok(())
 }
 
+/// The register to use for deletes and yanks; text from masked input
+/// (IE: passwords) is never stored anywhere
+fn target_register(ctx: &KeyHandlerContext<VimKeymap>) -> Option<char> {
+    let state = ctx.state();
+    if state
+        .connections
+        .is_masked_input(state.current_buffer().id())
+    {
+        Some(BLACKHOLE_REGISTER)
+    } else {
+        ctx.keymap.selected_register
+    }
+}
+
 fn delete_range(ctx: &mut KeyHandlerContext<VimKeymap>, range: MotionRange) {
-    let register = ctx.keymap.selected_register;
+    let register = target_register(ctx);
     let yanked = ctx.state_mut().current_buffer_mut().delete_range(range);
     ctx.state_mut().registers.handle_deleted(register, yanked);
 }
@@ -537,4 +553,28 @@ mod tests {
             "});
         }
     }
+
+    #[cfg(test)]
+    mod links {
+        use super::super::*;
+        use super::*;
+
+        #[test]
+        fn link_keys_only_in_connection_output() {
+            let binds = |source: BufferSource, keys: &str| {
+                let key = keys.into_keys()[0];
+                vim_normal_mode(&source)
+                    .mappings
+                    .children
+                    .contains_key(&key)
+            };
+
+            let output = || BufferSource::Connection("serenity:5555".to_string());
+            assert!(binds(output(), "<cr>"));
+            assert!(binds(output(), "<tab>"));
+            assert!(!binds(BufferSource::None, "<cr>"));
+            assert!(!binds(BufferSource::None, "<tab>"));
+            assert!(!binds(BufferSource::None, "<s-tab>"));
+        }
+    }
 }
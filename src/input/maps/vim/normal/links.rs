@@ -0,0 +1,120 @@
+use crate::connection::{links, LinkTarget};
+use crate::editing::{buffer::CopiedRange, source::BufferSource, Id};
+use crate::input::maps::actions::connection::send_string_to_buffer;
+use crate::input::maps::vim::VimKeymap;
+use crate::input::maps::{vim::tree::KeyTreeNode, KeyHandlerContext, KeyResult};
+use crate::input::KeymapContext;
+use crate::vim_tree;
+
+pub fn mappings() -> KeyTreeNode {
+    vim_tree! {
+        "<tab>" => |?mut ctx| jump_to_link(ctx, true),
+        "<s-tab>" => |?mut ctx| jump_to_link(ctx, false),
+        "<cr>" => |?mut ctx| activate_link(ctx),
+        "gx" => |?mut ctx| open_link(ctx),
+        "gy" => |?mut ctx| yank_link(ctx),
+    }
+}
+
+/// If the current buffer is a Connection's output buffer, returns its ID
+fn connection_output_buffer(ctx: &KeyHandlerContext<VimKeymap>) -> Option<Id> {
+    let buffer = ctx.state().current_buffer();
+    match buffer.source() {
+        BufferSource::Connection(_) => Some(buffer.id()),
+        _ => None,
+    }
+}
+
+fn jump_to_link(mut ctx: KeyHandlerContext<VimKeymap>, forward: bool) -> KeyResult {
+    let buffer_id = match connection_output_buffer(&ctx) {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+
+    let cursor = ctx.state().current_window().cursor;
+    let target = ctx
+        .state_mut()
+        .connections
+        .by_buffer_id(buffer_id)
+        .and_then(|conn| {
+            if forward {
+                conn.links.next_after(cursor)
+            } else {
+                conn.links.prev_before(cursor)
+            }
+            .map(|link| link.start())
+        });
+
+    if let Some(target) = target {
+        ctx.state_mut().current_window_mut().cursor = target;
+    }
+
+    Ok(())
+}
+
+/// The target of the link under the cursor, if any
+fn link_at_cursor(ctx: &mut KeyHandlerContext<VimKeymap>, buffer_id: Id) -> Option<LinkTarget> {
+    let cursor = ctx.state().current_window().cursor;
+    ctx.state_mut()
+        .connections
+        .by_buffer_id(buffer_id)
+        .and_then(|conn| conn.links.at(cursor).map(|output| output.link.clone()))
+}
+
+fn open_url(ctx: &KeyHandlerContext<VimKeymap>, url: &str) -> KeyResult {
+    links::open_url(ctx.state().connections.url_opener(), url)?;
+    Ok(())
+}
+
+fn open_link(mut ctx: KeyHandlerContext<VimKeymap>) -> KeyResult {
+    let buffer_id = match connection_output_buffer(&ctx) {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+
+    match link_at_cursor(&mut ctx, buffer_id) {
+        Some(LinkTarget::Url(url)) => open_url(&ctx, &url),
+        _ => Ok(()),
+    }
+}
+
+fn yank_link(mut ctx: KeyHandlerContext<VimKeymap>) -> KeyResult {
+    let buffer_id = match connection_output_buffer(&ctx) {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+
+    if let Some(link) = link_at_cursor(&mut ctx, buffer_id) {
+        let register = super::target_register(&ctx);
+        let yanked = CopiedRange::from(link.text().to_string());
+        ctx.state_mut().registers.handle_yanked(register, yanked);
+    }
+    ctx.keymap.reset();
+
+    Ok(())
+}
+
+fn activate_link(mut ctx: KeyHandlerContext<VimKeymap>) -> KeyResult {
+    let buffer_id = match connection_output_buffer(&ctx) {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+
+    match link_at_cursor(&mut ctx, buffer_id) {
+        Some(LinkTarget::Send(link)) if link.prompt => {
+            if let Some(input_id) = ctx.state().buffers.input_buffer_for(buffer_id) {
+                if let Some(input) = ctx.state_mut().buffers.by_id_mut(input_id) {
+                    input.clear();
+                    input.append(link.command.into());
+                }
+                ctx.state_mut()
+                    .current_tab_mut()
+                    .set_focus_to_buffer(input_id);
+            }
+            Ok(())
+        }
+        Some(LinkTarget::Send(link)) => send_string_to_buffer(&mut ctx, buffer_id, link.command),
+        Some(LinkTarget::Url(url)) => open_url(&ctx, &url),
+        None => Ok(()),
+    }
+}
@@ -69,7 +69,7 @@ fn read_register(ctx: &mut KeyHandlerContext<VimKeymap>) -> Option<String> {
 }
 
 fn yank(ctx: &mut KeyHandlerContext<VimKeymap>, range: MotionRange) -> KeyResult {
-    let register = ctx.keymap.selected_register;
+    let register = super::target_register(ctx);
     let yanked = ctx.state_mut().current_buffer_mut().get_range(range);
     ctx.state_mut().registers.handle_yanked(register, yanked);
     ctx.keymap.reset();
@@ -4,9 +4,21 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+use tui::style::Style;
+
 use crate::{
     editing::Id,
-    input::{commands::CommandHandlerContext, maps::KeyResult, KeyError, KeymapContext},
+    game::processing::{
+        manager::ManagedProcessors, style::parse_style, trigger::TriggerAction, ProcessorKind,
+    },
+    input::{
+        commands::{
+            triggers::{self, ProcessorOptions, TriggerPatterns},
+            CommandHandlerContext,
+        },
+        maps::KeyResult,
+        KeyError, KeymapContext,
+    },
     script::{args::FnArgs, fns::ScriptingFnRef, poly::Either, ScriptingManager},
 };
 
@@ -39,7 +51,11 @@ impl BufferApiObject {
     #[property]
     pub fn connection(&self) -> Option<ConnectionApiObject> {
         if let Some(id) = self.connection_id() {
-            Some(ConnectionApiObject::new(self.api.clone(), id))
+            Some(ConnectionApiObject::new(
+                self.api.clone(),
+                self.fns.clone(),
+                id,
+            ))
         } else {
             None
         }
@@ -55,25 +71,271 @@ impl BufferApiObject {
         }
     }
 
+    /// Rewrite input sent to this buffer's connection that matches `pattern`. If `opts`
+    /// has a `group`, the alias is put in that group, as with the `++group` option of
+    /// the `:trigger` command.
     #[rpc(passing(self.id))]
     pub fn alias(
         context: &mut CommandHandlerContext,
         id: Id,
         pattern: String,
         replacement: Either<String, ScriptingFnRef>,
+        opts: Option<HashMap<String, FnArgs>>,
     ) -> KeyResult {
+        let options = processor_options(&opts);
         let scripting = context.state().scripting.clone();
-        context.state_mut().connections.with_buffer_engine(
-            id,
-            move |engine| match replacement {
-                Either::A(text) => engine.aliases.insert_text(pattern, text),
-                Either::B(f) => engine
-                    .aliases
-                    .insert_fn(pattern, create_user_processor(scripting, f)),
-            },
-        )?;
-        Ok(())
+        context
+            .state_mut()
+            .connections
+            .with_buffer_engine(id, move |engine| {
+                match replacement {
+                    Either::A(text) => engine.aliases.insert_text(pattern.clone(), text)?,
+                    Either::B(f) => engine
+                        .aliases
+                        .insert_fn(pattern.clone(), create_user_processor(scripting, f))?,
+                };
+                options.apply(engine, ProcessorKind::Alias, &pattern);
+                Ok(())
+            })
+    }
+
+    /// Hide any line received by this buffer's connection that matches `pattern`.
+    /// `opts` may have a `group`, as with `alias`.
+    #[rpc(passing(self.id))]
+    pub fn gag(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        pattern: String,
+        opts: Option<HashMap<String, FnArgs>>,
+    ) -> KeyResult {
+        let options = processor_options(&opts);
+        context
+            .state_mut()
+            .connections
+            .with_buffer_engine(id, move |engine| {
+                engine.substitutions.insert_gag(pattern.clone())?;
+                options.apply(engine, ProcessorKind::Substitution, &pattern);
+                Ok(())
+            })
+    }
+
+    /// Replace the part of any line received by this buffer's connection that matches
+    /// `pattern`. The replacement may be text, styled according to a `style` spec in
+    /// `opts` (as with the `:subst` command), or a function that is called with a map
+    /// of the captured values and returns the replacement, or nothing to gag the line.
+    /// `opts` may also have a `group`, as with `alias`.
+    #[rpc(passing(self.id))]
+    pub fn substitute(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        pattern: String,
+        replacement: Either<String, ScriptingFnRef>,
+        opts: Option<HashMap<String, FnArgs>>,
+    ) -> KeyResult {
+        let style = match opts.as_ref().and_then(|opts| opts.get("style")) {
+            Some(FnArgs::String(spec)) => parse_style(spec)?,
+            _ => Style::default(),
+        };
+        let options = processor_options(&opts);
+        let scripting = context.state().scripting.clone();
+        context
+            .state_mut()
+            .connections
+            .with_buffer_engine(id, move |engine| {
+                match replacement {
+                    Either::A(text) => {
+                        engine
+                            .substitutions
+                            .insert_styled(pattern.clone(), text, style)?
+                    }
+                    Either::B(f) => engine
+                        .substitutions
+                        .insert_fn(pattern.clone(), create_user_processor(scripting, f))?,
+                };
+                options.apply(engine, ProcessorKind::Substitution, &pattern);
+                Ok(())
+            })
+    }
+
+    /// Restyle text received by this buffer's connection that matches `pattern`. The
+    /// `style` is written as for the `:highlight` command. Every match is restyled
+    /// unless `opts` has `line` set, in which case the whole line is. `opts` may also
+    /// have a `group`, as with `alias`.
+    #[rpc(passing(self.id))]
+    pub fn highlight(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        pattern: String,
+        style: String,
+        opts: Option<HashMap<String, FnArgs>>,
+    ) -> KeyResult {
+        let style = parse_style(&style)?;
+        let options = processor_options(&opts);
+        let whole_line = matches!(
+            opts.as_ref().and_then(|opts| opts.get("line")),
+            Some(FnArgs::Bool(true))
+        );
+        context
+            .state_mut()
+            .connections
+            .with_buffer_engine(id, move |engine| {
+                engine
+                    .highlights
+                    .insert_style(pattern.clone(), style, whole_line)?;
+                options.apply(engine, ProcessorKind::Highlight, &pattern);
+                Ok(())
+            })
     }
+
+    /// Run an action whenever a line received by this buffer's connection matches
+    /// `pattern`, which may include the same options and `++then` sequences as the
+    /// `:trigger` command. The action may be text (handled like `:trigger`) or a
+    /// function, which is called with a map of the captured values. If `opts` has
+    /// `once` set, the trigger is removed after it first fires. `opts` may also have a
+    /// `group`, as with `alias`.
+    #[rpc(passing(self.id))]
+    pub fn trigger(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        pattern: String,
+        action: Either<String, ScriptingFnRef>,
+        opts: Option<HashMap<String, FnArgs>>,
+    ) -> KeyResult {
+        let mut patterns = TriggerPatterns::parse(&pattern)?;
+        patterns.one_shot |= matches!(
+            opts.as_ref().and_then(|opts| opts.get("once")),
+            Some(FnArgs::Bool(true))
+        );
+        if let Some(group) = processor_options(&opts).group {
+            patterns.options.group = Some(group);
+        }
+        let (description, handler) = match action {
+            Either::A(text) => {
+                let dispatcher = context.state().dispatcher.sender.clone();
+                let handler = triggers::create_action(dispatcher, id, text.clone());
+                (text, handler)
+            }
+            Either::B(f) => {
+                let scripting = context.state().scripting.clone();
+                ("<fn>".to_string(), create_trigger_action(scripting, f))
+            }
+        };
+        context
+            .state_mut()
+            .connections
+            .with_buffer_engine(id, move |engine| {
+                patterns.insert_into(engine, description, handler)
+            })
+    }
+
+    /// Enable the alias, trigger, gag, or highlight with the given `pattern` on this
+    /// buffer's connection, after it was disabled by `disable`. The `kind` is one of
+    /// `"alias"`, `"trigger"`, `"gag"` (which includes substitutions), or `"highlight"`.
+    #[rpc(passing(self.id))]
+    pub fn enable(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        kind: String,
+        pattern: String,
+    ) -> KeyResult {
+        update_processor(context, id, &kind, &pattern, |manager, description| {
+            manager.set_enabled(description, true);
+        })
+    }
+
+    /// Stop the alias, trigger, gag, or highlight with the given `pattern` on this
+    /// buffer's connection from running, without removing it. The `kind` is as for
+    /// `enable`.
+    #[rpc(passing(self.id))]
+    pub fn disable(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        kind: String,
+        pattern: String,
+    ) -> KeyResult {
+        update_processor(context, id, &kind, &pattern, |manager, description| {
+            manager.set_enabled(description, false);
+        })
+    }
+
+    /// Change the priority of the alias, trigger, gag, or highlight with the given
+    /// `pattern` on this buffer's connection, as with the `:priority` command. The
+    /// `kind` is as for `enable`.
+    #[rpc(passing(self.id))]
+    pub fn set_priority(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        kind: String,
+        pattern: String,
+        priority: i32,
+    ) -> KeyResult {
+        update_processor(context, id, &kind, &pattern, |manager, description| {
+            manager.set_priority(description, priority);
+        })
+    }
+
+    /// Move the alias, trigger, gag, or highlight with the given `pattern` on this
+    /// buffer's connection into `group`, or out of any group if none is given. The
+    /// `kind` is as for `enable`.
+    #[rpc(passing(self.id))]
+    pub fn set_group(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        kind: String,
+        pattern: String,
+        group: Option<String>,
+    ) -> KeyResult {
+        update_processor(context, id, &kind, &pattern, |manager, description| {
+            manager.set_group(description, group);
+        })
+    }
+
+    /// Enable every alias, trigger, gag, and highlight in `group` on this buffer's
+    /// connection, as with the `:enablegroup` command
+    #[rpc(passing(self.id))]
+    pub fn enable_group(context: &mut CommandHandlerContext, id: Id, group: String) {
+        set_group_enabled(context, id, &group, true);
+    }
+
+    /// Disable every alias, trigger, gag, and highlight in `group` on this buffer's
+    /// connection, as with the `:disablegroup` command
+    #[rpc(passing(self.id))]
+    pub fn disable_group(context: &mut CommandHandlerContext, id: Id, group: String) {
+        set_group_enabled(context, id, &group, false);
+    }
+}
+
+/// The processor options that may be given in `opts` when defining a processor
+fn processor_options(opts: &Option<HashMap<String, FnArgs>>) -> ProcessorOptions {
+    let group = match opts.as_ref().and_then(|opts| opts.get("group")) {
+        Some(FnArgs::String(group)) => Some(group.to_string()),
+        _ => None,
+    };
+    ProcessorOptions {
+        group,
+        priority: None,
+    }
+}
+
+fn update_processor(
+    context: &mut CommandHandlerContext,
+    id: Id,
+    kind: &str,
+    pattern: &str,
+    f: impl FnOnce(&mut dyn ManagedProcessors, &str),
+) -> KeyResult {
+    let kind = triggers::parse_kind_name(kind)?;
+    let description = triggers::processor_description(kind, pattern)?;
+    triggers::update_processor(context, id, kind, &description, |manager| {
+        f(manager, &description)
+    })
+}
+
+fn set_group_enabled(context: &mut CommandHandlerContext, id: Id, group: &str, enabled: bool) {
+    context
+        .state_mut()
+        .connections
+        .with_buffer_engine(id, |engine| engine.set_group_enabled(group, enabled));
 }
 
 fn create_user_processor(
@@ -92,3 +354,17 @@ fn create_user_processor(
         Err(_) => Err(KeyError::IO(std::io::ErrorKind::WouldBlock.into())),
     })
 }
+
+fn create_trigger_action(
+    scripting: Arc<Mutex<ScriptingManager>>,
+    f: ScriptingFnRef,
+) -> TriggerAction {
+    Arc::new(move |captures| match scripting.try_lock() {
+        Ok(scripting) => {
+            scripting.invoke(f, captures.into())?;
+            Ok(())
+        }
+
+        Err(_) => Err(KeyError::IO(std::io::ErrorKind::WouldBlock.into())),
+    })
+}
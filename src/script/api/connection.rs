@@ -1,19 +1,27 @@
-use std::fmt;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
+    connection::{GmcpMessage, MsdpValue, MsdpVariable},
     editing::Id,
+    game::listeners::Listener,
     input::{
         commands::{connection::on_disconnect, CommandHandlerContext},
         maps::{actions::connection::send_string_to_buffer, KeyResult},
         KeyError, KeymapContext,
     },
+    script::{args::FnArgs, fns::ScriptingFnRef, ScriptingManager},
 };
 
-use super::Api;
+use super::{Api, Fns};
 
 #[apigen::ns]
 pub struct ConnectionApiObject {
     api: Api,
+    fns: Fns,
     pub id: Id,
 }
 
@@ -25,8 +33,8 @@ impl fmt::Debug for ConnectionApiObject {
 
 #[apigen::ns_impl]
 impl ConnectionApiObject {
-    pub fn new(api: Api, id: Id) -> Self {
-        Self { api, id }
+    pub fn new(api: Api, fns: Fns, id: Id) -> Self {
+        Self { api, fns, id }
     }
 
     #[rpc(passing(self.id))]
@@ -45,4 +53,151 @@ impl ConnectionApiObject {
             Err(KeyError::IO(std::io::ErrorKind::NotConnected.into()))
         }
     }
+
+    /// Send a GMCP message for the given `package`, with optional JSON-encoded `json` data.
+    /// To advertise support for additional packages, send `Core.Supports.Add`.
+    #[rpc(passing(self.id))]
+    pub fn send_gmcp(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        package: String,
+        json: Option<String>,
+    ) -> KeyResult {
+        if let Some(conn) = context.state_mut().connections.by_id_mut(id) {
+            conn.send_gmcp(GmcpMessage::new(package, json))?;
+            Ok(())
+        } else {
+            Err(KeyError::IO(std::io::ErrorKind::NotConnected.into()))
+        }
+    }
+
+    /// Register `handler` to be called for each GMCP message received for `package`
+    /// (or any of its sub-packages). The handler receives a dict with the `package`
+    /// name and the raw `json` data, if any.
+    #[rpc(passing(self.id))]
+    pub fn on_gmcp(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        package: String,
+        handler: ScriptingFnRef,
+    ) -> KeyResult {
+        let scripting = context.state().scripting.clone();
+        if let Some(conn) = context.state_mut().connections.by_id_mut(id) {
+            conn.with_engine_mut(move |engine| {
+                engine
+                    .gmcp
+                    .insert(&package, create_gmcp_listener(scripting, handler))
+            });
+            Ok(())
+        } else {
+            Err(KeyError::IO(std::io::ErrorKind::NotConnected.into()))
+        }
+    }
+
+    /// Send an MSDP variable to the server. This is typically a command like
+    /// `REPORT`, `UNREPORT`, `LIST`, `SEND`, or `RESET` whose value is the
+    /// name of the variable (or list) in question.
+    #[rpc(passing(self.id))]
+    pub fn send_msdp(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        name: String,
+        value: String,
+    ) -> KeyResult {
+        if let Some(conn) = context.state_mut().connections.by_id_mut(id) {
+            conn.send_msdp(MsdpVariable::command(name, value))?;
+            Ok(())
+        } else {
+            Err(KeyError::IO(std::io::ErrorKind::NotConnected.into()))
+        }
+    }
+
+    /// Returns the most recently-received value of the MSDP variable with
+    /// the given `name`, if any. Arrays are returned as lists, and tables
+    /// as dicts.
+    #[rpc(passing(self.id))]
+    pub fn msdp(context: &mut CommandHandlerContext, id: Id, name: String) -> Option<FnArgs> {
+        context
+            .state()
+            .connections
+            .by_id(id)
+            .and_then(|conn| conn.with_engine(|engine| engine.msdp_values.get(&name).cloned()))
+            .map(MsdpValue::into)
+    }
+
+    /// Returns a dict of the server status (MSSP) variables provided by the
+    /// server, if any. Variables with multiple values are returned as lists.
+    #[rpc(passing(self.id))]
+    pub fn mssp(context: &mut CommandHandlerContext, id: Id) -> Option<FnArgs> {
+        let data = context.state().connections.by_id(id)?.mssp.as_ref()?;
+        let mut map: HashMap<String, FnArgs> = HashMap::default();
+        for (name, values) in &data.variables {
+            let value = if values.len() == 1 {
+                FnArgs::String(values[0].clone())
+            } else {
+                FnArgs::List(values.iter().cloned().map(FnArgs::String).collect())
+            };
+            map.insert(name.clone(), value);
+        }
+        Some(FnArgs::Map(map))
+    }
+
+    /// Register `handler` to be called whenever a new value for the MSDP
+    /// variable `name` is received. The handler receives a dict with the
+    /// variable `name` and its `value`. Remember to `REPORT` the variable!
+    #[rpc(passing(self.id))]
+    pub fn on_msdp(
+        context: &mut CommandHandlerContext,
+        id: Id,
+        name: String,
+        handler: ScriptingFnRef,
+    ) -> KeyResult {
+        let scripting = context.state().scripting.clone();
+        if let Some(conn) = context.state_mut().connections.by_id_mut(id) {
+            conn.with_engine_mut(move |engine| {
+                engine
+                    .msdp
+                    .insert(&name, create_msdp_listener(scripting, handler))
+            });
+            Ok(())
+        } else {
+            Err(KeyError::IO(std::io::ErrorKind::NotConnected.into()))
+        }
+    }
+}
+
+fn create_gmcp_listener(
+    scripting: Arc<Mutex<ScriptingManager>>,
+    f: ScriptingFnRef,
+) -> Listener<GmcpMessage> {
+    Arc::new(move |message| match scripting.try_lock() {
+        Ok(scripting) => {
+            let mut args: HashMap<String, String> = HashMap::default();
+            args.insert("package".to_string(), message.package);
+            if let Some(json) = message.json {
+                args.insert("json".to_string(), json);
+            }
+            scripting.invoke(f, args.into())?;
+            Ok(())
+        }
+
+        Err(_) => Err(KeyError::IO(std::io::ErrorKind::WouldBlock.into())),
+    })
+}
+
+fn create_msdp_listener(
+    scripting: Arc<Mutex<ScriptingManager>>,
+    f: ScriptingFnRef,
+) -> Listener<MsdpVariable> {
+    Arc::new(move |variable| match scripting.try_lock() {
+        Ok(scripting) => {
+            let mut args: HashMap<String, FnArgs> = HashMap::default();
+            args.insert("name".to_string(), FnArgs::String(variable.name));
+            args.insert("value".to_string(), variable.value.into());
+            scripting.invoke(f, FnArgs::Map(args))?;
+            Ok(())
+        }
+
+        Err(_) => Err(KeyError::IO(std::io::ErrorKind::WouldBlock.into())),
+    })
 }
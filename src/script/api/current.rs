@@ -52,7 +52,11 @@ impl CurrentObjects {
     #[property]
     pub fn connection(&self) -> Option<ConnectionApiObject> {
         if let Some(id) = self.connection_id() {
-            Some(ConnectionApiObject::new(self.api.clone(), id))
+            Some(ConnectionApiObject::new(
+                self.api.clone(),
+                self.fns.clone(),
+                id,
+            ))
         } else {
             None
         }
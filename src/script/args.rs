@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 
+use crate::connection::MsdpValue;
+
 #[derive(Clone, Debug)]
 pub enum FnArgs {
     None,
     Bool(bool),
     String(String),
+    List(Vec<FnArgs>),
     Map(HashMap<String, FnArgs>),
 }
 
@@ -19,3 +22,15 @@ impl Into<FnArgs> for HashMap<String, String> {
         FnArgs::Map(m)
     }
 }
+
+impl From<MsdpValue> for FnArgs {
+    fn from(value: MsdpValue) -> Self {
+        match value {
+            MsdpValue::String(s) => FnArgs::String(s),
+            MsdpValue::Array(values) => FnArgs::List(values.into_iter().map(Into::into).collect()),
+            MsdpValue::Table(table) => {
+                FnArgs::Map(table.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
@@ -14,6 +14,9 @@ impl IntoPyObject for FnArgs {
             FnArgs::None => vm.ctx.none(),
             FnArgs::Bool(b) => vm.ctx.new_bool(b),
             FnArgs::String(s) => vm.ctx.new_str(s),
+            FnArgs::List(items) => vm
+                .ctx
+                .new_list(items.into_iter().map(|v| v.into_pyobject(vm)).collect()),
             FnArgs::Map(m) => {
                 let dict = vm.ctx.new_dict();
                 for (k, v) in m {
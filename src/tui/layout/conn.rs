@@ -1,13 +1,16 @@
 use std::cmp::{max, min};
 
+use tui::style::{Modifier, Style};
+
 use crate::{
-    editing::{layout::conn::ConnLayout, Resizable, Size},
+    editing::{buffer::MemoryBuffer, layout::conn::ConnLayout, Buffer, Resizable, Size},
     tui::{measure::Measurable, LayoutContext, RenderContext, Renderable},
 };
 
 const MIN_OUTPUT_HEIGHT: u16 = 3;
 const MIN_INPUT_HEIGHT: u16 = 1; // input height should be *at least* 1
 const MAX_INPUT_HEIGHT: u16 = 5;
+const MAX_PROMPT_HEIGHT: u16 = 2;
 
 impl Renderable for ConnLayout {
     fn layout(&mut self, ctx: &LayoutContext) {
@@ -15,9 +18,15 @@ impl Renderable for ConnLayout {
         let Size { w, .. } = self.output.size;
         let input_buffer = ctx.buffer(self.input.buffer).unwrap();
         let preferred_height = input_buffer.measure_height(self.input.size.w);
-        let available_height = self.output.size.h + self.input.size.h;
+        let available_height = self.output.size.h + self.prompt.size.h + self.input.size.h;
+        let prompt_height = match ctx.buffer(self.prompt.buffer) {
+            Some(prompt) if !prompt.is_empty() => min(prompt.measure_height(w), MAX_PROMPT_HEIGHT),
+            _ => 0,
+        };
         let available_input_height = max(
-            available_height.checked_sub(MIN_OUTPUT_HEIGHT).unwrap_or(1),
+            available_height
+                .checked_sub(MIN_OUTPUT_HEIGHT + prompt_height)
+                .unwrap_or(1),
             1,
         );
         let input_height = max(
@@ -28,15 +37,20 @@ impl Renderable for ConnLayout {
             MIN_INPUT_HEIGHT,
         );
 
-        if self.input.size.h != input_height {
+        if self.input.size.h != input_height || self.prompt.size.h != prompt_height {
             self.output.resize(Size {
                 w,
-                h: available_height - input_height,
+                h: available_height.saturating_sub(input_height + prompt_height),
+            });
+            self.prompt.resize(Size {
+                w,
+                h: prompt_height,
             });
             self.input.resize(Size { w, h: input_height });
         }
 
         self.output.layout(ctx);
+        self.prompt.layout(ctx);
         self.input.layout(ctx);
     }
 
@@ -46,13 +60,68 @@ impl Renderable for ConnLayout {
         self.output.render(&mut ctx.with_area(layout_area));
 
         layout_area.y += self.output.size.h;
+        if self.prompt.size.h > 0 {
+            layout_area.height = self.prompt.size.h;
+            self.prompt.render(&mut ctx.with_area(layout_area));
+            layout_area.y += self.prompt.size.h;
+        }
+
+        // While the server has disabled echo (IE: for passwords), hide what's typed
+        let masked = if ctx.app.connections.is_masked_input(self.input.buffer) {
+            ctx.app
+                .buffers
+                .by_id(self.input.buffer)
+                .map(|buffer| masked_buffer(buffer.as_ref()))
+        } else {
+            None
+        };
+
         layout_area.height = self.input.size.h;
-        self.input.render(&mut ctx.with_area(layout_area));
+        let mut input_ctx = ctx.with_area(layout_area);
+        if let Some(masked) = &masked {
+            input_ctx.buffer_override = Some(masked);
+        }
+        self.input.render(&mut input_ctx);
+
+        // Indicate any messages waiting to be sent (see :ratelimit) at the end of the input
+        let pending = ctx
+            .app
+            .connections
+            .buffer_to_id(self.output.buffer)
+            .and_then(|id| ctx.app.connections.by_id(id))
+            .map(|conn| conn.queue.pending_count())
+            .unwrap_or(0);
+        if pending > 0 && layout_area.height > 0 {
+            let indicator = format!(" [{} queued]", pending);
+            let width = min(indicator.len() as u16, layout_area.width);
+            ctx.display.buffer.set_stringn(
+                layout_area.x + layout_area.width - width,
+                layout_area.y + layout_area.height - 1,
+                indicator,
+                width as usize,
+                Style::default().add_modifier(Modifier::DIM),
+            );
+        }
     }
 }
 
+/// Create a copy of `buffer` with every character replaced by `*`
+fn masked_buffer(buffer: &dyn Buffer) -> Box<dyn Buffer> {
+    let mut masked = MemoryBuffer::new(buffer.id());
+    if buffer.lines_count() > 0 {
+        let lines: Vec<String> = (0..buffer.lines_count())
+            .map(|i| "*".repeat(buffer.get(i).width()))
+            .collect();
+        masked.append(lines.join("\n").into());
+    }
+    Box::new(masked)
+}
+
 #[cfg(test)]
 mod tests {
+    use super::masked_buffer;
+    use crate::connection::ReadValue;
+    use crate::editing::{buffer::MemoryBuffer, Buffer};
     use crate::tui::Size;
     use crate::tui::{rendering::display::tests::TestableDisplay, tabpage::tests::TestableTabpage};
     use crate::{editing::Id, tui::tabpage::tests::tabpage};
@@ -74,6 +143,14 @@ mod tests {
         (tabpage, input_bufid)
     }
 
+    #[test]
+    fn mask_buffer_content() {
+        let mut buffer: Box<dyn Buffer> = Box::new(MemoryBuffer::new(0));
+        buffer.append("Take my love".into());
+        let masked = masked_buffer(buffer.as_ref());
+        assert_eq!(masked.get_contents(), "************");
+    }
+
     #[test]
     fn resize_input_to_match_content() {
         let (mut tabpage, input_bufid) = conn_tabpage(indoc! {"
@@ -94,6 +171,37 @@ mod tests {
         "});
     }
 
+    #[test]
+    fn render_prompt_above_input() {
+        let (mut tabpage, input_bufid) = conn_tabpage(indoc! {"
+            Take my love
+            Take my land
+        "});
+
+        let output_bufid = tabpage
+            .buffers
+            .by_id(input_bufid)
+            .and_then(|buf| buf.connection_buffer_id())
+            .unwrap();
+        let prompt_bufid = tabpage.buffers.prompt_buffer_for(output_bufid).unwrap();
+        let buffer = tabpage.buffers.by_id_mut(prompt_bufid).unwrap();
+        buffer.append_value(ReadValue::Prompt("HP: 42>".into()));
+        buffer.append_value(ReadValue::Prompt("HP: 9001>".into()));
+
+        let buffer = tabpage.buffers.by_id_mut(input_bufid).unwrap();
+        buffer.append("shiny".into());
+
+        tabpage.size = Size { w: 14, h: 6 };
+        tabpage.render().assert_visual_equals(indoc! {"
+
+
+            Take my love
+            Take my land
+            HP: 9001>
+            shiny
+        "});
+    }
+
     #[test]
     fn split_on_input_splits_output() {
         let (mut tabpage, input_bufid) = conn_tabpage(indoc! {"
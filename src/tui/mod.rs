@@ -4,9 +4,13 @@ use crate::{
     ui::UI,
 };
 
-use crossterm::terminal;
+use crossterm::{execute, terminal};
 use editing::window::Window;
-use std::{cmp::min, convert::TryInto, io};
+use std::{
+    cmp::min,
+    convert::TryInto,
+    io::{self, Write},
+};
 use tui::{
     backend::Backend,
     style::{Color, Style},
@@ -42,6 +46,9 @@ pub use rendering::Renderable;
 pub struct Tui {
     terminal: Terminal<CrosstermBackend<io::Stdout>>,
     cursor: CursorRenderer,
+
+    /// The title most recently set on the terminal
+    title: Option<String>,
 }
 
 impl Tui {
@@ -83,7 +90,9 @@ impl Tui {
         app.resize(display.size);
 
         // main UI:
-        app.tabpages.layout(&LayoutContext::new(&app.buffers));
+        app.tabpages
+            .layout(&LayoutContext::new(&app.buffers, &app.connections));
+        app.connections.resize_for_windows(&mut app.tabpages);
         app.tabpages
             .render(&mut RenderContext::new(&app, &mut display).with_area(size));
 
@@ -120,7 +129,32 @@ impl Tui {
             splash::render(&mut display);
         }
 
-        self.render_display(display)
+        self.render_display(display)?;
+        self.render_title(app)?;
+
+        if app.requested_bell {
+            app.requested_bell = false;
+            let backend = self.terminal.backend_mut();
+            backend.write_all(b"\x07")?;
+            Backend::flush(backend)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reflect the title set by the connection in the current window, if any
+    fn render_title(&mut self, app: &crate::app::State) -> io::Result<()> {
+        let buffer_id = app.current_window().buffer;
+        let title = app.connections.title_for_buffer(buffer_id);
+        if title == self.title.as_deref() {
+            return Ok(());
+        }
+
+        self.title = title.map(|title| title.to_string());
+        execute!(
+            self.terminal.backend_mut(),
+            terminal::SetTitle(title.unwrap_or(""))
+        )
     }
 
     fn render_echo(&mut self, app: &mut crate::app::State, display: &mut Display) {
@@ -295,5 +329,6 @@ pub fn create_ui() -> Result<Tui, io::Error> {
     Ok(Tui {
         cursor: CursorRenderer::default(),
         terminal,
+        title: None,
     })
 }
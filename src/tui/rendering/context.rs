@@ -1,6 +1,7 @@
 use tui::layout::Rect;
 
 use crate::{
+    connection::connections::Connections,
     editing::{buffers::Buffers, Buffer},
     tui::Display,
 };
@@ -8,13 +9,15 @@ use crate::{
 pub struct LayoutContext<'a> {
     pub buffers: Option<&'a Buffers>,
     pub buffer_override: Option<&'a Box<dyn Buffer>>,
+    pub connections: Option<&'a Connections>,
 }
 
 impl<'a> LayoutContext<'a> {
-    pub fn new(buffers: &'a Buffers) -> Self {
+    pub fn new(buffers: &'a Buffers, connections: &'a Connections) -> Self {
         Self {
             buffers: Some(&buffers),
             buffer_override: None,
+            connections: Some(connections),
         }
     }
 
@@ -22,6 +25,7 @@ impl<'a> LayoutContext<'a> {
         Self {
             buffers: None,
             buffer_override: Some(buffer),
+            connections: None,
         }
     }
 
@@ -76,6 +76,7 @@ pub mod tests {
             self.tab.layout(&mut LayoutContext {
                 buffers: Some(&state.buffers),
                 buffer_override: None,
+                connections: None,
             });
             self.tab.render(&mut context);
 
@@ -10,7 +10,13 @@ use tui::{
 };
 
 use super::{measure::render_into, LayoutContext, RenderContext, Renderable};
-use crate::editing::{self, text::TextLine, window::Window, Buffer};
+use crate::connection::{connections::Connections, OutputOriginals};
+use crate::editing::{
+    self,
+    text::TextLine,
+    window::{Window, WindowFlags},
+    Buffer, CursorPosition,
+};
 use crate::tui::Measurable;
 
 #[derive(Debug, PartialEq)]
@@ -38,7 +44,7 @@ struct RenderableContent<'a> {
 }
 
 impl<'a> RenderableContent<'a> {
-    fn new(window: &Window, buf: &Box<dyn Buffer>) -> Self {
+    fn new(window: &Window, buf: &Box<dyn Buffer>, originals: Option<&OutputOriginals>) -> Self {
         let count = buf.lines_count();
 
         let gutter_width = if let Some(gutter) = window.gutter.as_ref() {
@@ -58,11 +64,21 @@ impl<'a> RenderableContent<'a> {
         let end = count.checked_sub(scrolled_lines).unwrap_or(0);
         let start = end.checked_sub(window.size.h as usize).unwrap_or(0);
 
-        let lines: Vec<text::Spans> = (start..end).map(|i| buf.get(i).clone()).collect();
-        let line_heights: Vec<u16> = lines
+        // Any gagged lines shown are rendered as part of the line after them,
+        // just like the rows of a wrapped line
+        let shown: Vec<Vec<TextLine>> = (start..end)
+            .map(|i| shown_lines(buf.as_ref(), originals, i))
+            .collect();
+        let line_heights: Vec<u16> = shown
             .iter()
-            .map(|line| line.measure_height(available_width))
+            .map(|lines| {
+                lines
+                    .iter()
+                    .map(|line| line.measure_height(available_width))
+                    .sum()
+            })
             .collect();
+        let lines: Vec<text::Spans> = shown.into_iter().flatten().collect();
 
         // make sure we aren't scrolled too far due to an "undo," etc
         let bottom_height = *line_heights.last().unwrap_or(&0);
@@ -144,32 +160,65 @@ fn wrap_cursor(line: &TextLine, width: u16, cursor_col: usize) -> (u16, u16) {
     }
 }
 
-impl Renderable for Window {
-    fn layout(&mut self, context: &LayoutContext) {
-        if !self.focused {
-            // don't scroll to keep cursor in view unless focused
-            return;
-        }
+/// The lines rendered for the given buffer line: just the line itself,
+/// unless a window that SHOW_GAGGED has `originals` for it
+fn shown_lines(
+    buf: &dyn Buffer,
+    originals: Option<&OutputOriginals>,
+    line: usize,
+) -> Vec<TextLine> {
+    match originals {
+        Some(originals) => originals.received(line, buf.get(line)),
+        None => vec![buf.get(line).clone()],
+    }
+}
 
-        let buf = if let Some(buf) = context.buffer(self.buffer) {
-            buf
-        } else {
-            return;
-        };
+/// Like wrap_cursor, but for the given buffer line as shown, with the
+/// y offset counting any rows rendered for gagged lines above it
+fn shown_cursor(
+    buf: &dyn Buffer,
+    originals: Option<&OutputOriginals>,
+    width: u16,
+    cursor: CursorPosition,
+) -> (u16, u16) {
+    let mut lines = shown_lines(buf, originals, cursor.line);
+    let line = lines.pop().unwrap_or_default();
+    let gagged_height: u16 = lines.iter().map(|line| line.measure_height(width)).sum();
+    let (x, y) = wrap_cursor(&line, width, cursor.col);
+    (x, gagged_height + y)
+}
 
-        let renderable = RenderableContent::new(self, buf);
+impl Window {
+    /// The originals to show in place of this Window's buffer content,
+    /// if it SHOW_GAGGED and is showing a Connection's output
+    fn shown_originals<'a>(
+        &self,
+        connections: Option<&'a Connections>,
+    ) -> Option<&'a OutputOriginals> {
+        if !self.flags.contains(WindowFlags::SHOW_GAGGED) {
+            return None;
+        }
+        let connections = connections?;
+        connections
+            .buffer_to_id(self.buffer)
+            .and_then(|id| connections.by_id(id))
+            .map(|conn| &conn.originals)
+            .filter(|originals| !originals.is_empty())
+    }
+
+    fn layout_buffer(&mut self, buf: &Box<dyn Buffer>, originals: Option<&OutputOriginals>) {
+        let renderable = RenderableContent::new(self, buf, originals);
         self.scroll_offset = renderable.scroll_offset;
         self.scrolled_lines = renderable.scrolled_lines as u32;
 
         // Sanity check: always clamp cursor
         self.cursor = self.clamp_cursor(buf, self.cursor);
 
-        let cursor_line = match buf.checked_get(self.cursor.line) {
-            Some(line) => line,
-            None => return, // nothing we can do
-        };
+        if buf.checked_get(self.cursor.line).is_none() {
+            return; // nothing we can do
+        }
         let (_, cursor_y_offset) =
-            wrap_cursor(cursor_line, renderable.inner_width, self.cursor.col);
+            shown_cursor(buf.as_ref(), originals, renderable.inner_width, self.cursor);
 
         if self.cursor.line < renderable.start.line {
             self.scroll_offset = 0;
@@ -199,17 +248,13 @@ impl Renderable for Window {
         }
     }
 
-    fn render(&self, context: &mut RenderContext) {
-        let buf = if let Some(overridden) = context.buffer_override {
-            overridden
-        } else {
-            match context.app.buffers.by_id(self.buffer) {
-                None => return,
-                Some(buf) => buf,
-            }
-        };
-
-        let renderable = RenderableContent::new(self, buf);
+    fn render_buffer(
+        &self,
+        context: &mut RenderContext,
+        buf: &Box<dyn Buffer>,
+        originals: Option<&OutputOriginals>,
+    ) {
+        let renderable = RenderableContent::new(self, buf, originals);
         let paragraph = Paragraph::new(renderable.candidate_text)
             .wrap(Wrap { trim: false })
             .alignment(Alignment::Left)
@@ -234,7 +279,7 @@ impl Renderable for Window {
         if self.focused {
             let (x, y) = if buf.lines_count() > 0 {
                 let (cursor_x, cursor_y_offset) =
-                    wrap_cursor(buf.get(self.cursor.line), area.width, self.cursor.col);
+                    shown_cursor(buf.as_ref(), originals, area.width, self.cursor);
 
                 let cursor_virtual_lines = self
                     .cursor
@@ -285,6 +330,39 @@ impl Renderable for Window {
     }
 }
 
+impl Renderable for Window {
+    fn layout(&mut self, context: &LayoutContext) {
+        if !self.focused {
+            // don't scroll to keep cursor in view unless focused
+            return;
+        }
+
+        let buf = if let Some(buf) = context.buffer(self.buffer) {
+            buf
+        } else {
+            return;
+        };
+
+        let originals = self.shown_originals(context.connections);
+        self.layout_buffer(buf, originals);
+    }
+
+    fn render(&self, context: &mut RenderContext) {
+        let buf = if let Some(overridden) = context.buffer_override {
+            overridden
+        } else {
+            match context.app.buffers.by_id(self.buffer) {
+                None => return,
+                Some(buf) => buf,
+            }
+        };
+
+        // Show connection output as it was received, if requested
+        let originals = self.shown_originals(Some(&context.app.connections));
+        self.render_buffer(context, buf, originals);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use editing::{text::TextLine, text::TextLines, Cursor, CursorPosition, Resizable, Size};
@@ -776,4 +854,62 @@ mod tests {
             "});
         }
     }
+
+    #[cfg(test)]
+    mod show_gagged {
+        use crate::editing::motion::tests::TestWindow;
+
+        use super::*;
+
+        fn render_shown(ctx: &mut TestWindow, originals: &OutputOriginals) -> Display {
+            let mut display = Display::new(ctx.window.size);
+            ctx.window.layout_buffer(&ctx.buffer, Some(originals));
+
+            let state = State::default();
+            let mut context = RenderContext::new(&state, &mut display);
+            ctx.window
+                .render_buffer(&mut context, &ctx.buffer, Some(originals));
+            display
+        }
+
+        fn originals() -> OutputOriginals {
+            let mut originals = OutputOriginals::default();
+            originals.gagged(1, "Shiny".into());
+            originals.rewritten(2, "Take me there".into());
+            originals
+        }
+
+        #[test]
+        fn cursor_on_shown_line() {
+            let mut ctx = window(indoc! {"
+                Take my love
+                Take |my land
+                Take me where
+            "});
+            ctx.window.resize(Size { w: 13, h: 4 });
+
+            render_shown(&mut ctx, &originals()).assert_visual_match(indoc! {"
+                Take my love
+                Shiny
+                Take |my land
+                Take me there
+            "});
+        }
+
+        #[test]
+        fn scroll_to_cursor_after_gagged() {
+            let mut ctx = window(indoc! {"
+                Take my love
+                |Take my land
+                Take me where
+            "});
+            ctx.window.resize(Size { w: 13, h: 2 });
+
+            render_shown(&mut ctx, &originals()).assert_visual_match(indoc! {"
+                |Take my land
+                Take me there
+            "});
+            assert_eq!(ctx.window.cursor, (1, 0).into());
+        }
+    }
 }